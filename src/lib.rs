@@ -1,6 +1,8 @@
 #![deny(unused_must_use)]
 pub mod minecraft;
+pub mod panic_archive;
 pub mod websocket;
+pub mod world;
 
 #[cfg(test)]
 mod tests;
@@ -1,3 +1 @@
-pub mod websocket;
-
 fn main() {}
@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display};
 
-use serde::Deserialize;
-use serde_json::Value;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::minecraft::computercraft::computer_types::lua_types::{LuaPackedTable, LuaValue};
 
@@ -10,11 +11,199 @@ use crate::minecraft::computercraft::computer_types::lua_types::{LuaPackedTable,
 /// These contain a lot of funky data, and thus are basically un-derivable.
 ///
 /// See panicking.md
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LuaPanic {
-    stack_trace: String,
-    locals: LuaPackedTable,
-    up_values: LuaPackedTable,
+    /// The raw output of `debug.traceback`, message included.
+    pub stack_trace: String,
+    pub locals: LuaPackedTable,
+    pub up_values: LuaPackedTable,
     #[serde(flatten)] // Grab all of the fields that are unmatched.
-    unknown_extra_data: HashMap<String, LuaValue>,
+    pub unknown_extra_data: HashMap<String, LuaValue>,
+}
+
+impl LuaPanic {
+    /// Make sense of this panic.
+    pub fn report(self) -> PanicReport {
+        PanicReport::from(self)
+    }
+}
+
+/// A single line out of a stack traceback.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StackFrame {
+    /// The file the frame is in, `[C]` for builtin functions.
+    pub file: String,
+    /// Not known for builtin functions.
+    pub line: Option<u32>,
+    /// What lua called the function, IE `panic.assert`, `<mesh_os.lua:12>` for
+    /// anonymous functions, or `main chunk`. None if lua had no idea.
+    pub function: Option<String>,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        match &self.function {
+            Some(function) => write!(f, " in {function}"),
+            None => write!(f, " in ?"),
+        }
+    }
+}
+
+/// A panic, picked apart into something a person (or test) can read.
+///
+/// The locals and up values are kept as tables, so you can dig through them
+/// with indexing, IE `report.locals["task"]["definition"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicReport {
+    /// What the panic said.
+    pub message: String,
+    /// Innermost frame first, same as lua prints them.
+    pub frames: Vec<StackFrame>,
+    pub locals: LuaValue,
+    pub up_values: LuaValue,
+    /// Anything else that came along with the panic.
+    pub extra: HashMap<String, LuaValue>,
+}
+
+impl From<LuaPanic> for PanicReport {
+    fn from(panic: LuaPanic) -> Self {
+        let (message, frames) = parse_traceback(&panic.stack_trace);
+        Self {
+            message,
+            frames,
+            locals: LuaValue::Table(panic.locals),
+            up_values: LuaValue::Table(panic.up_values),
+            extra: panic.unknown_extra_data,
+        }
+    }
+}
+
+/// `file:line: in what`, the line is missing for builtins.
+static FRAME_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<file>.+?)(?::(?P<line>\d+))?: in (?P<what>.+)$").expect("Valid regex")
+});
+
+/// `function 'name'`, `local 'name'`, `upvalue 'name'`, etc.
+static NAMED_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:function|local|global|upvalue|field|method) '(?P<name>.+)'$")
+        .expect("Valid regex")
+});
+
+/// Split the output of `debug.traceback` into the message and its frames.
+///
+/// Lines that are not frames, like `(...tail calls...)`, are skipped. If there
+/// is no traceback at all, the whole thing is the message.
+pub fn parse_traceback(traceback: &str) -> (String, Vec<StackFrame>) {
+    let Some((message, trace)) = traceback.split_once("stack traceback:") else {
+        return (traceback.trim().to_string(), Vec::new());
+    };
+
+    let frames = trace
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| FRAME_REGEX.captures(line))
+        .map(|captures| {
+            let what = &captures["what"];
+            let function = match NAMED_REGEX.captures(what) {
+                Some(named) => Some(named["name"].to_string()),
+                None if what == "?" => None,
+                // `main chunk`, `function <file:line>`
+                None => Some(what.trim_start_matches("function ").to_string()),
+            };
+            StackFrame {
+                file: captures["file"].to_string(),
+                line: captures
+                    .name("line")
+                    .and_then(|line| line.as_str().parse().ok()),
+                function,
+            }
+        })
+        .collect();
+
+    (message.trim().to_string(), frames)
+}
+
+impl Display for PanicReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Panic: {}", self.message)?;
+        writeln!(f, "Stack trace:")?;
+        for frame in &self.frames {
+            writeln!(f, "    {frame}")?;
+        }
+        writeln!(f, "Locals: {:#}", self.locals)?;
+        write!(f, "Up values: {:#}", self.up_values)?;
+        for (key, value) in &self.extra {
+            write!(f, "\n{key}: {value:#}")?;
+        }
+        Ok(())
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Tracebacks in the shape cc:tweaked gives us should come apart cleanly.
+fn parsing_panics() {
+    let json = r#"{
+        "stack_trace": "Unwrapped on a nil!\nstack traceback:\n\t[C]: in function 'error'\n\tpanic.lua:160: in function 'panic.unwrap'\n\tmesh_os.lua:42: in local 'startTask'\n\tmesh_os.lua:90: in function <mesh_os.lua:80>\n\t(...tail calls...)\n\t[C]: in ?",
+        "locals": {"pairs": [
+            {"key": "task", "value": {"pairs": [
+                {"key": "definition", "value": {"pairs": [{"key": "name", "value": "spelunk"}]}}
+            ]}},
+            {"key": "attempts", "value": 3}
+        ]},
+        "up_values": {"pairs": null}
+    }"#;
+    let report = serde_json::from_str::<LuaPanic>(json).unwrap().report();
+
+    assert_eq!(report.message, "Unwrapped on a nil!");
+    assert_eq!(
+        report.frames,
+        vec![
+            StackFrame {
+                file: "[C]".into(),
+                line: None,
+                function: Some("error".into())
+            },
+            StackFrame {
+                file: "panic.lua".into(),
+                line: Some(160),
+                function: Some("panic.unwrap".into())
+            },
+            StackFrame {
+                file: "mesh_os.lua".into(),
+                line: Some(42),
+                function: Some("startTask".into())
+            },
+            StackFrame {
+                file: "mesh_os.lua".into(),
+                line: Some(90),
+                function: Some("<mesh_os.lua:80>".into())
+            },
+            StackFrame {
+                file: "[C]".into(),
+                line: None,
+                function: None
+            },
+        ]
+    );
+    assert_eq!(
+        report.locals["task"]["definition"]["name"].as_str(),
+        Some("spelunk")
+    );
+    assert_eq!(report.locals["attempts"].as_i64(), Some(3));
+
+    let printed = report.to_string();
+    assert!(printed.starts_with("Panic: Unwrapped on a nil!\nStack trace:\n    [C] in error\n"));
+    assert!(printed.contains("    mesh_os.lua:42 in startTask\n"));
+    assert!(printed.contains("Up values: {}"));
+
+    // No trace at all.
+    assert_eq!(parse_traceback("oh no"), ("oh no".to_string(), Vec::new()));
 }
@@ -0,0 +1,818 @@
+// A compact encoding for the biggest things turtles send us.
+//
+// Walkbacks and world deltas are almost entirely positions and blocks. As json,
+// every position is an `x:1|y:2|z:3` string key, and every block repeats its
+// full name and states. The compact encoding instead packs positions as varints
+// relative to the position before them, and stores every distinct block once in
+// a palette.
+//
+// Turtles only use it if they offered it in their hello, see
+// [Encoding::negotiate]. The bytes are sent as base64 inside the usual json
+// envelope, as `{"compact": "..."}`, so acks, captures and replays never need
+// to know about it.
+//
+// Layout. Every number is an unsigned LEB128 varint, signed numbers are
+// zigzagged first:
+// - The format version, see [COMPACT_VERSION].
+// - World deltas: the sequence, full (0 or 1), a block section, then the
+//   visited positions as a position list.
+// - Walkbacks: the current x, y and z, the facing (0 for none, then n e s w u
+//   d), the walkback chain as a position list, all seen positions as a
+//   position list, then a block section.
+// - A position list is a count, then the x, y and z of every position, each as
+//   the difference from the position before it. The first is relative to
+//   0, 0, 0.
+// - A block section is the palette, then the blocks. The palette is a count,
+//   then for every entry the length of the name, the name, a bitmask of which
+//   of the states in [STATE_FIELDS] are set, and then those states. The blocks
+//   are a count, then every block as a position (like a position list) and its
+//   palette index.
+//
+// See `compactWorldDelta` and `compactWalkback` in `networking.lua` for the
+// other side.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::minecraft::{
+    computercraft::computer_types::{walkback_type::Walkback, world_delta::WorldDelta},
+    types::{CoordinatePosition, MinecraftCardinalDirection, MinecraftPosition},
+    vanilla::block_type::{
+        HasMinecraftBlock, LuaMinecraftBlockState, MinecraftBlock, PositionedMinecraftBlock,
+    },
+};
+
+/// The version of the compact layout. Bump this whenever it changes.
+///
+/// Must match `COMPACT_VERSION` in `networking.lua`.
+pub const COMPACT_VERSION: u64 = 1;
+
+/// The block states that are kept, in the order of their bits in the palette
+/// bitmask. The rest are dropped, same as they are when reading json.
+pub const STATE_FIELDS: [&str; 6] = ["age", "eye", "honey_level", "level", "lit", "stage"];
+
+/// Facings in the order they are numbered, starting from 1.
+const FACINGS: [MinecraftCardinalDirection; 6] = [
+    MinecraftCardinalDirection::North,
+    MinecraftCardinalDirection::East,
+    MinecraftCardinalDirection::South,
+    MinecraftCardinalDirection::West,
+    MinecraftCardinalDirection::Up,
+    MinecraftCardinalDirection::Down,
+];
+
+/// How a turtle sends its large packets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Plain json. Every turtle speaks this.
+    #[default]
+    Json,
+    /// See the top of `compact.rs`.
+    Compact,
+}
+
+impl Encoding {
+    /// Look up an encoding by the name turtles use for it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Encoding::Json),
+            "compact" => Some(Encoding::Compact),
+            _ => None,
+        }
+    }
+
+    /// Pick what a turtle should use, out of the encodings it offered.
+    pub fn negotiate(offered: &[Encoding]) -> Self {
+        if offered.contains(&Encoding::Compact) {
+            Encoding::Compact
+        } else {
+            Encoding::Json
+        }
+    }
+}
+
+// ==================
+// Errors
+// ==================
+
+/// Everything that can go wrong when decoding the compact encoding.
+#[derive(Debug)]
+pub enum CompactError {
+    /// The payload was not valid base64.
+    InvalidBase64,
+    /// Ran out of bytes partway through.
+    UnexpectedEnd,
+    /// There were bytes left over once everything was decoded.
+    TrailingBytes(usize),
+    /// Made with a version of the layout we do not know.
+    UnsupportedVersion(u64),
+    /// A varint did not fit in 64 bits.
+    VarintOverflow,
+    /// A number was too big for what it was being decoded into.
+    OutOfRange {
+        /// What was being decoded, IE `facing`.
+        what: &'static str,
+        value: u64,
+    },
+    /// A block name was not valid utf-8.
+    InvalidName(std::string::FromUtf8Error),
+    /// A block name we have never heard of.
+    UnknownBlock(String),
+    /// A block pointed past the end of the palette.
+    BadPaletteIndex(u64),
+}
+
+impl Display for CompactError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompactError::InvalidBase64 => write!(f, "payload is not valid base64"),
+            CompactError::UnexpectedEnd => write!(f, "payload ended early"),
+            CompactError::TrailingBytes(count) => {
+                write!(f, "{count} bytes left over at the end of the payload")
+            }
+            CompactError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "compact version {version} is not supported, expected {COMPACT_VERSION}"
+                )
+            }
+            CompactError::VarintOverflow => write!(f, "varint does not fit in 64 bits"),
+            CompactError::OutOfRange { what, value } => {
+                write!(f, "{value} is out of range for a {what}")
+            }
+            CompactError::InvalidName(error) => write!(f, "block name is not utf-8: {error}"),
+            CompactError::UnknownBlock(name) => write!(f, "unknown block `{name}`"),
+            CompactError::BadPaletteIndex(index) => {
+                write!(f, "palette index {index} is past the end of the palette")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompactError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompactError::InvalidName(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// ==================
+// Packet data
+// ==================
+
+/// Wrap encoded bytes up as the `data` of a packet.
+pub fn to_packet_data(bytes: &[u8]) -> Value {
+    serde_json::json!({ "compact": to_base64(bytes) })
+}
+
+/// Pull the encoded bytes back out of the `data` of a packet. `None` if the
+/// data is not compact at all, IE it is plain json.
+pub fn from_packet_data(data: &Value) -> Option<Result<Vec<u8>, CompactError>> {
+    let encoded = data.as_object()?.get("compact")?;
+    Some(
+        encoded
+            .as_str()
+            .ok_or(CompactError::InvalidBase64)
+            .and_then(from_base64),
+    )
+}
+
+// ==================
+// World deltas
+// ==================
+
+pub fn encode_world_delta(delta: &WorldDelta) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.varint(delta.sequence);
+    writer.varint(delta.full as u64);
+    writer.blocks(&delta.blocks);
+    writer.positions(&sorted(delta.positions.iter().copied()));
+    writer.bytes
+}
+
+pub fn decode_world_delta(bytes: &[u8]) -> Result<WorldDelta, CompactError> {
+    let mut reader = Reader::new(bytes)?;
+    let sequence = reader.varint()?;
+    let full = match reader.varint()? {
+        0 => false,
+        1 => true,
+        value => {
+            return Err(CompactError::OutOfRange {
+                what: "bool",
+                value,
+            });
+        }
+    };
+    let blocks = reader.blocks()?;
+    let positions: HashSet<CoordinatePosition> = reader.positions()?.into_iter().collect();
+    reader.finish()?;
+    Ok(WorldDelta {
+        sequence,
+        full,
+        blocks,
+        positions,
+    })
+}
+
+// ==================
+// Walkbacks
+// ==================
+
+/// `chain_seen_positions` is not encoded, since it can be rebuilt from the
+/// chain.
+pub fn encode_walkback(walkback: &Walkback) -> Vec<u8> {
+    let mut writer = Writer::new();
+    let position = walkback.cur_position.position;
+    writer.signed(position.x);
+    writer.signed(position.y);
+    writer.signed(position.z);
+    let facing = walkback
+        .cur_position
+        .facing
+        .and_then(|facing| FACINGS.iter().position(|known| *known == facing))
+        .map_or(0, |index| index as u64 + 1);
+    writer.varint(facing);
+    writer.positions(walkback.walkback_chain.as_deref().unwrap_or_default());
+    let seen = walkback
+        .all_seen_positions
+        .iter()
+        .flat_map(|seen| seen.keys().copied());
+    writer.positions(&sorted(seen));
+    match &walkback.all_seen_blocks {
+        Some(blocks) => writer.blocks(blocks),
+        None => writer.blocks(&HashMap::new()),
+    }
+    writer.bytes
+}
+
+pub fn decode_walkback(bytes: &[u8]) -> Result<Walkback, CompactError> {
+    let mut reader = Reader::new(bytes)?;
+    let position = CoordinatePosition {
+        x: reader.signed()?,
+        y: reader.signed()?,
+        z: reader.signed()?,
+    };
+    let facing = match reader.varint()? {
+        0 => None,
+        value => Some(
+            *FACINGS
+                .get(value as usize - 1)
+                .ok_or(CompactError::OutOfRange {
+                    what: "facing",
+                    value,
+                })?,
+        ),
+    };
+    let chain = reader.positions()?;
+    // Chain indexes start at 1 on the lua side.
+    let chain_seen_positions = chain
+        .iter()
+        .enumerate()
+        .map(|(index, position)| (*position, index as u16 + 1))
+        .collect();
+    let all_seen_positions = reader
+        .positions()?
+        .into_iter()
+        .map(|position| (position, true))
+        .collect();
+    let all_seen_blocks = reader.blocks()?;
+    reader.finish()?;
+    Ok(Walkback {
+        cur_position: MinecraftPosition { position, facing },
+        walkback_chain: Some(chain),
+        chain_seen_positions: Some(chain_seen_positions),
+        all_seen_positions: Some(all_seen_positions),
+        all_seen_blocks: Some(all_seen_blocks),
+    })
+}
+
+/// Positions sorted so neighbors end up next to each other, which keeps the
+/// differences between them small.
+fn sorted(positions: impl Iterator<Item = CoordinatePosition>) -> Vec<CoordinatePosition> {
+    let mut positions: Vec<CoordinatePosition> = positions.collect();
+    positions.sort_unstable_by_key(|position| (position.y, position.z, position.x));
+    positions
+}
+
+// ==================
+// Writing
+// ==================
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    /// Starts with the version already written.
+    fn new() -> Self {
+        let mut writer = Self { bytes: Vec::new() };
+        writer.varint(COMPACT_VERSION);
+        writer
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn signed(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn position(&mut self, position: CoordinatePosition, previous: &mut CoordinatePosition) {
+        self.signed(position.x.wrapping_sub(previous.x));
+        self.signed(position.y.wrapping_sub(previous.y));
+        self.signed(position.z.wrapping_sub(previous.z));
+        *previous = position;
+    }
+
+    fn positions(&mut self, positions: &[CoordinatePosition]) {
+        self.varint(positions.len() as u64);
+        let mut previous = CoordinatePosition { x: 0, y: 0, z: 0 };
+        for position in positions {
+            self.position(*position, &mut previous);
+        }
+    }
+
+    fn blocks(&mut self, blocks: &HashMap<CoordinatePosition, PositionedMinecraftBlock>) {
+        let mut palette: HashMap<(String, LuaMinecraftBlockState), u64> = HashMap::new();
+        let mut entries: Vec<(CoordinatePosition, u64)> = Vec::with_capacity(blocks.len());
+        let mut palette_writer = Writer { bytes: Vec::new() };
+        for (position, block) in blocks {
+            let key = (
+                block.get_full_name().into_owned(),
+                block.block().lua_state(),
+            );
+            let next = palette.len() as u64;
+            let index = *palette.entry(key).or_insert_with_key(|(name, state)| {
+                palette_writer.palette_entry(name, state);
+                next
+            });
+            entries.push((*position, index));
+        }
+        entries.sort_unstable_by_key(|(position, _)| (position.y, position.z, position.x));
+
+        self.varint(palette.len() as u64);
+        self.bytes.extend(palette_writer.bytes);
+        self.varint(entries.len() as u64);
+        let mut previous = CoordinatePosition { x: 0, y: 0, z: 0 };
+        for (position, index) in entries {
+            self.position(position, &mut previous);
+            self.varint(index);
+        }
+    }
+
+    fn palette_entry(&mut self, name: &str, state: &LuaMinecraftBlockState) {
+        self.varint(name.len() as u64);
+        self.bytes.extend_from_slice(name.as_bytes());
+        let values = [
+            state.age,
+            state.eye.map(u32::from),
+            state.honey_level,
+            state.level,
+            state.lit.map(u32::from),
+            state.stage,
+        ];
+        let mask = values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_some())
+            .fold(0, |mask, (bit, _)| mask | 1 << bit);
+        self.varint(mask);
+        for value in values.into_iter().flatten() {
+            self.varint(value as u64);
+        }
+    }
+}
+
+// ==================
+// Reading
+// ==================
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the version before anything else is read.
+    fn new(bytes: &'a [u8]) -> Result<Self, CompactError> {
+        let mut reader = Self { bytes, offset: 0 };
+        match reader.varint()? {
+            COMPACT_VERSION => Ok(reader),
+            version => Err(CompactError::UnsupportedVersion(version)),
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], CompactError> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(CompactError::UnexpectedEnd)?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, CompactError> {
+        let mut value: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.take(1)?[0];
+            let bits = (byte & 0x7f) as u64;
+            if shift >= 64 || (shift == 63 && bits > 1) {
+                return Err(CompactError::VarintOverflow);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn signed(&mut self) -> Result<i64, CompactError> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    /// A count of things that each take at least one byte. Checked against
+    /// what is left, so a bad count can't allocate the world.
+    fn count(&mut self) -> Result<usize, CompactError> {
+        let count = self.varint()?;
+        if count > (self.bytes.len() - self.offset) as u64 {
+            return Err(CompactError::UnexpectedEnd);
+        }
+        Ok(count as usize)
+    }
+
+    fn position(
+        &mut self,
+        previous: &mut CoordinatePosition,
+    ) -> Result<CoordinatePosition, CompactError> {
+        let position = CoordinatePosition {
+            x: previous.x.wrapping_add(self.signed()?),
+            y: previous.y.wrapping_add(self.signed()?),
+            z: previous.z.wrapping_add(self.signed()?),
+        };
+        *previous = position;
+        Ok(position)
+    }
+
+    fn positions(&mut self) -> Result<Vec<CoordinatePosition>, CompactError> {
+        let count = self.count()?;
+        let mut previous = CoordinatePosition { x: 0, y: 0, z: 0 };
+        (0..count).map(|_| self.position(&mut previous)).collect()
+    }
+
+    fn blocks(
+        &mut self,
+    ) -> Result<HashMap<CoordinatePosition, PositionedMinecraftBlock>, CompactError> {
+        let palette_size = self.count()?;
+        let palette = (0..palette_size)
+            .map(|_| self.palette_entry())
+            .collect::<Result<Vec<MinecraftBlock>, CompactError>>()?;
+
+        let count = self.count()?;
+        let mut blocks = HashMap::with_capacity(count);
+        let mut previous = CoordinatePosition { x: 0, y: 0, z: 0 };
+        for _ in 0..count {
+            let position = self.position(&mut previous)?;
+            let index = self.varint()?;
+            let block = palette
+                .get(index as usize)
+                .ok_or(CompactError::BadPaletteIndex(index))?;
+            blocks.insert(
+                position,
+                PositionedMinecraftBlock::new(block.clone(), position),
+            );
+        }
+        Ok(blocks)
+    }
+
+    fn palette_entry(&mut self) -> Result<MinecraftBlock, CompactError> {
+        let length = self.count()?;
+        let name =
+            String::from_utf8(self.take(length)?.to_vec()).map_err(CompactError::InvalidName)?;
+        let mask = self.varint()?;
+        if mask >> STATE_FIELDS.len() != 0 {
+            return Err(CompactError::OutOfRange {
+                what: "state bitmask",
+                value: mask,
+            });
+        }
+        let mut values = [None; STATE_FIELDS.len()];
+        for (bit, value) in values.iter_mut().enumerate() {
+            if mask & 1 << bit != 0 {
+                let read = self.varint()?;
+                *value = Some(u32::try_from(read).map_err(|_| CompactError::OutOfRange {
+                    what: STATE_FIELDS[bit],
+                    value: read,
+                })?);
+            }
+        }
+        let [age, eye, honey_level, level, lit, stage] = values;
+        let state = LuaMinecraftBlockState {
+            age,
+            eye: eye.map(|eye| eye != 0),
+            honey_level,
+            level,
+            lit: lit.map(|lit| lit != 0),
+            stage,
+        };
+        MinecraftBlock::from_lua_state(&name, Some(state)).ok_or(CompactError::UnknownBlock(name))
+    }
+
+    /// Make sure nothing was left over.
+    fn finish(self) -> Result<(), CompactError> {
+        match self.bytes.len() - self.offset {
+            0 => Ok(()),
+            left => Err(CompactError::TrailingBytes(left)),
+        }
+    }
+}
+
+// ==================
+// Base64
+// ==================
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64, with padding. Must match `toBase64` in `networking.lua`.
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - index * 8)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - index * 6)) & 0x3f;
+                out.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn from_base64(text: &str) -> Result<Vec<u8>, CompactError> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return Err(CompactError::InvalidBase64);
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (chunk_index, chunk) in text.chunks(4).enumerate() {
+        let last = chunk_index == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err(CompactError::InvalidBase64);
+        }
+        let mut group = 0u32;
+        for (index, byte) in chunk[..4 - padding].iter().enumerate() {
+            let sextet = match byte {
+                b'A'..=b'Z' => byte - b'A',
+                b'a'..=b'z' => byte - b'a' + 26,
+                b'0'..=b'9' => byte - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return Err(CompactError::InvalidBase64),
+            };
+            group |= (sextet as u32) << (18 - index * 6);
+        }
+        let bytes = group.to_be_bytes();
+        out.extend_from_slice(&bytes[1..4 - padding]);
+    }
+    Ok(out)
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+/// A json block, in the format `detailsToBlock` makes.
+fn json_block(name: &str, x: i64, y: i64, z: i64) -> Value {
+    let state = match name {
+        "minecraft:furnace" => serde_json::json!({ "lit": true, "facing": "north" }),
+        "minecraft:oak_log" => serde_json::json!({ "axis": "y" }),
+        _ => serde_json::json!({}),
+    };
+    serde_json::json!({
+        "name": name,
+        "pos": { "x": x, "y": y, "z": z },
+        "state": state,
+        "tag": [],
+    })
+}
+
+#[cfg(test)]
+/// A full world delta as json, with a block at every position in a cube.
+fn world_delta_json(side: i64) -> Value {
+    let names = [
+        "minecraft:stone",
+        "minecraft:dirt",
+        "minecraft:air",
+        "minecraft:coal_ore",
+        "minecraft:oak_log",
+        "minecraft:furnace",
+    ];
+    let mut blocks = serde_json::Map::new();
+    let mut positions = serde_json::Map::new();
+    for x in 0..side {
+        for y in -side / 2..side / 2 {
+            for z in 0..side {
+                let key = format!("x:{x}|y:{y}|z:{z}");
+                let name = names[((x * 7 + y * 3 + z) as usize) % names.len()];
+                blocks.insert(key.clone(), json_block(name, x, y, z));
+                if (x + z) % 4 == 0 {
+                    positions.insert(key, Value::Bool(true));
+                }
+            }
+        }
+    }
+    serde_json::json!({
+        "sequence": 4,
+        "full": true,
+        "blocks": blocks,
+        "positions": positions,
+    })
+}
+
+#[cfg(test)]
+/// Wrap packet data up as a packet from turtle 1.
+fn packet_json(packet_type: &str, data: &Value) -> String {
+    serde_json::json!({
+        "id": 1,
+        "uuid": "COMPACTS",
+        "timestamp": 0,
+        "packet_type": packet_type,
+        "data": data,
+    })
+    .to_string()
+}
+
+#[test]
+/// Everything should come back out of the compact encoding the way it went in.
+fn compact_round_trip() {
+    use crate::minecraft::computercraft::computer_types::packet_types::TurtlePacket;
+
+    for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", &[0, 255, 128, 7, 9]] {
+        assert_eq!(from_base64(&to_base64(bytes)).unwrap(), bytes);
+    }
+    assert_eq!(to_base64(b"foob"), "Zm9vYg==");
+    assert!(from_base64("Zm9vYg=").is_err());
+    assert!(from_base64("Zm=vYg==").is_err());
+
+    let mut writer = Writer { bytes: Vec::new() };
+    for value in [0, 1, -1, 63, -64, i64::MAX, i64::MIN] {
+        writer.signed(value);
+    }
+    writer.varint(u64::MAX);
+    let mut reader = Reader {
+        bytes: &writer.bytes,
+        offset: 0,
+    };
+    for value in [0, 1, -1, 63, -64, i64::MAX, i64::MIN] {
+        assert_eq!(reader.signed().unwrap(), value);
+    }
+    assert_eq!(reader.varint().unwrap(), u64::MAX);
+    reader.finish().unwrap();
+
+    let json: WorldDelta = serde_json::from_value(world_delta_json(6)).unwrap();
+    let compact = decode_world_delta(&encode_world_delta(&json)).unwrap();
+    assert_eq!((compact.sequence, compact.full), (4, true));
+    assert_eq!(compact.positions, json.positions);
+    assert_eq!(compact.blocks.len(), json.blocks.len());
+    for (position, block) in &json.blocks {
+        let other = &compact.blocks[position];
+        assert_eq!(other.position(), *position);
+        assert_eq!(other.get_full_name(), block.get_full_name());
+        assert_eq!(other.block().lua_state(), block.block().lua_state());
+    }
+    let furnace = json
+        .blocks
+        .values()
+        .find(|block| block.get_name() == "furnace")
+        .unwrap();
+    assert_eq!(furnace.block().lua_state().lit, Some(true));
+
+    // Compact packets decode just like json ones.
+    let packet = packet_json("world_delta", &to_packet_data(&encode_world_delta(&json)));
+    let TurtlePacket::WorldDelta(packet) = TurtlePacket::from_json(&packet).unwrap() else {
+        panic!("Not a world delta packet!")
+    };
+    assert_eq!(packet.delta.blocks.len(), json.blocks.len());
+
+    let walkback: Walkback = serde_json::from_value(serde_json::json!({
+        "cur_position": { "position": { "x": -3, "y": 70, "z": 12 }, "facing": "w" },
+        "walkback_chain": [
+            { "x": -1, "y": 70, "z": 12 },
+            { "x": -2, "y": 70, "z": 12 },
+            { "x": -3, "y": 70, "z": 12 },
+        ],
+        "all_seen_positions": {
+            "x:-1|y:70|z:12": true,
+            "x:-2|y:70|z:12": true,
+            "x:-3|y:70|z:12": true,
+            "x:-2|y:71|z:12": true,
+        },
+        "all_seen_blocks": {
+            "x:-4|y:70|z:12": json_block("minecraft:stone", -4, 70, 12),
+        },
+    }))
+    .unwrap();
+    let compact = decode_walkback(&encode_walkback(&walkback)).unwrap();
+    assert_eq!(compact.cur_position, walkback.cur_position);
+    assert_eq!(compact.walkback_chain, walkback.walkback_chain);
+    assert_eq!(compact.all_seen_positions, walkback.all_seen_positions);
+    let chain_seen = compact.chain_seen_positions.unwrap();
+    assert_eq!(chain_seen[&"x:-3|y:70|z:12".parse().unwrap()], 3);
+    assert_eq!(compact.all_seen_blocks.unwrap().len(), 1);
+
+    // Broken payloads are caught, not panicked on.
+    let bytes = encode_world_delta(&json);
+    assert!(matches!(
+        decode_world_delta(&bytes[..bytes.len() - 1]),
+        Err(CompactError::UnexpectedEnd)
+    ));
+    assert!(matches!(
+        decode_world_delta(&[2]),
+        Err(CompactError::UnsupportedVersion(2))
+    ));
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        decode_world_delta(&trailing),
+        Err(CompactError::TrailingBytes(1))
+    ));
+}
+
+#[test]
+/// Negotiation should only pick encodings the turtle offered.
+fn negotiating_encodings() {
+    assert_eq!(Encoding::negotiate(&[]), Encoding::Json);
+    assert_eq!(Encoding::negotiate(&[Encoding::Json]), Encoding::Json);
+    assert_eq!(
+        Encoding::negotiate(&[Encoding::Compact, Encoding::Json]),
+        Encoding::Compact
+    );
+    assert_eq!(Encoding::from_name("compact"), Some(Encoding::Compact));
+    assert_eq!(Encoding::from_name("protobuf"), None);
+}
+
+#[test]
+#[ignore = "benchmark, run with `cargo test --release compact_benchmark -- --ignored --nocapture`"]
+/// Decode a 100k block dump as json, then again as compact, and compare.
+fn compact_benchmark() {
+    use std::time::Instant;
+
+    use crate::minecraft::computercraft::computer_types::packet_types::TurtlePacket;
+
+    const RUNS: u32 = 5;
+
+    // 47 * 46 * 47 is just over 100k blocks.
+    let data = world_delta_json(47);
+    let delta: WorldDelta = serde_json::from_value(data.clone()).unwrap();
+    assert!(delta.blocks.len() >= 100_000);
+
+    let json = packet_json("world_delta", &data);
+    let compact = packet_json("world_delta", &to_packet_data(&encode_world_delta(&delta)));
+
+    let time = |text: &str| {
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            let TurtlePacket::WorldDelta(packet) = TurtlePacket::from_json(text).unwrap() else {
+                panic!("Not a world delta packet!")
+            };
+            assert_eq!(packet.delta.blocks.len(), delta.blocks.len());
+        }
+        start.elapsed() / RUNS
+    };
+    let json_time = time(&json);
+    let compact_time = time(&compact);
+
+    println!(
+        "{} blocks. json: {} bytes in {json_time:?}, compact: {} bytes in {compact_time:?} ({:.1}x smaller, {:.1}x faster)",
+        delta.blocks.len(),
+        json.len(),
+        compact.len(),
+        json.len() as f64 / compact.len() as f64,
+        json_time.as_secs_f64() / compact_time.as_secs_f64(),
+    );
+    assert!(compact.len() < json.len());
+}
@@ -0,0 +1,174 @@
+// The hello handshake. Turtles send this as the very first packet after
+// connecting, so we can make sure we both speak the same dialect.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::minecraft::types::MinecraftPosition;
+
+/// The version of the packet format. Bump this whenever the format of any
+/// packet changes in a way that old turtles would not understand.
+///
+/// Must match `NETWORKING.PROTOCOL_VERSION` in `networking.lua`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The contents of `hello_world.json`. See `HelloWorld` in `aliases.lua`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct HelloWorld {
+    /// Wether this turtle has never been turned on before.
+    pub new: bool,
+    /// Where the turtle was when it last saved its state.
+    pub position: MinecraftPosition,
+}
+
+/// What we think of a turtle's hello.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum HelloResponse {
+    /// All good.
+    Accepted,
+    /// The turtle speaks our protocol, but some of its libraries do not match
+    /// ours. The turtle may keep going, but should be updated.
+    Outdated {
+        /// The file names of the libraries that do not match.
+        stale_libraries: Vec<String>,
+    },
+    /// We can't talk to this turtle.
+    Rejected { reason: String },
+}
+
+/// The hashes of a set of lua libraries, keyed by file name.
+///
+/// Libraries are installed flat in the root of the computer, so only the file
+/// name is kept, not the path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryManifest {
+    hashes: HashMap<String, u32>,
+}
+
+impl LibraryManifest {
+    /// Hash every file in the list. Takes the same paths that
+    /// `MeshpitLibraries::to_files` returns.
+    pub fn from_files(paths: impl IntoIterator<Item = PathBuf>) -> std::io::Result<Self> {
+        let mut hashes = HashMap::new();
+        for path in paths {
+            let contents = std::fs::read(&path)?;
+            hashes.insert(file_name(&path), adler32(&contents));
+        }
+        Ok(Self { hashes })
+    }
+
+    /// Every library that ships with meshpit.
+    ///
+    /// `startup.lua` is skipped, since every computer has its own.
+    pub fn bundled() -> std::io::Result<Self> {
+        let lua_folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/minecraft/computercraft/turtle/lua");
+        let paths = WalkDir::new(lua_folder)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .filter(|path| file_name(path) != "startup.lua");
+        Self::from_files(paths)
+    }
+
+    /// The hash of a library, if we know about it.
+    pub fn get(&self, file_name: &str) -> Option<u32> {
+        self.hashes.get(file_name).copied()
+    }
+
+    /// Decide what to do with a turtle based on what it told us in its hello.
+    ///
+    /// Turtles may have any subset of the libraries, so only the ones they do
+    /// have are checked. Libraries we have never heard of are ignored.
+    pub fn judge(&self, protocol_version: u32, libraries: &HashMap<String, u32>) -> HelloResponse {
+        if protocol_version != PROTOCOL_VERSION {
+            return HelloResponse::Rejected {
+                reason: format!(
+                    "protocol version {protocol_version} is not supported, expected {PROTOCOL_VERSION}"
+                ),
+            };
+        }
+
+        let mut stale_libraries: Vec<String> = libraries
+            .iter()
+            .filter(|(name, hash)| self.get(name).is_some_and(|ours| ours != **hash))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        if stale_libraries.is_empty() {
+            return HelloResponse::Accepted;
+        }
+        stale_libraries.sort();
+        HelloResponse::Outdated { stale_libraries }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Adler-32 of some bytes. Used since it is trivial to compute on the lua side
+/// without any bit operations. See `hashFile` in `networking.lua`.
+pub fn adler32(bytes: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for byte in bytes {
+        a = (a + *byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Known value, straight from wikipedia.
+fn adler32_matches_reference() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    assert_eq!(adler32(b""), 1);
+}
+
+#[test]
+/// Stale libraries should be flagged, and bad protocol versions rejected.
+fn judging_hellos() {
+    let manifest = LibraryManifest::bundled().unwrap();
+    let real_hash = manifest
+        .get("networking.lua")
+        .expect("Networking should be bundled.");
+    assert!(manifest.get("startup.lua").is_none());
+
+    let mut libraries = HashMap::from([
+        ("networking.lua".to_string(), real_hash),
+        ("some_other_file.lua".to_string(), 1234),
+    ]);
+    assert_eq!(
+        manifest.judge(PROTOCOL_VERSION, &libraries),
+        HelloResponse::Accepted
+    );
+
+    libraries.insert("networking.lua".to_string(), real_hash.wrapping_add(1));
+    assert_eq!(
+        manifest.judge(PROTOCOL_VERSION, &libraries),
+        HelloResponse::Outdated {
+            stale_libraries: vec!["networking.lua".to_string()]
+        }
+    );
+
+    assert!(matches!(
+        manifest.judge(PROTOCOL_VERSION + 1, &libraries),
+        HelloResponse::Rejected { .. }
+    ));
+}
@@ -0,0 +1,879 @@
+// Going between `LuaValue`s and anything serde can handle.
+//
+// Serializing something into a `LuaValue` and then into json gives the packed
+// `pairs` format, which keeps non-string keys and mixed tables intact, unlike
+// plain json. `helpers.unpackTable` turns that back into a normal table on the
+// lua side, which every server packet's data goes through, and
+// `helpers.packTable` makes tables we can read back out here.
+
+use std::fmt::Display;
+
+use serde::{
+    Deserializer, Serialize,
+    de::{
+        self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected,
+        VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{self, Serializer},
+};
+
+use crate::minecraft::computercraft::computer_types::lua_types::{
+    LuaKeyValuePair, LuaPackedTable, LuaValue,
+};
+
+/// Something could not be turned into, or pulled out of, a [LuaValue].
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaSerdeError {
+    /// Lua integers are signed 64 bit, bigger numbers do not fit.
+    IntegerTooLarge(u64),
+    /// Whatever serde or the type being (de)serialized had to say.
+    Custom(String),
+}
+
+impl Display for LuaSerdeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LuaSerdeError::IntegerTooLarge(number) => {
+                write!(f, "{number} is too large to be a lua integer")
+            }
+            LuaSerdeError::Custom(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for LuaSerdeError {}
+
+impl ser::Error for LuaSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        LuaSerdeError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for LuaSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        LuaSerdeError::Custom(msg.to_string())
+    }
+}
+
+/// Turn anything serializable into a [LuaValue].
+///
+/// Sequences become tables keyed `1..=n`, maps keep whatever keys they had,
+/// and enums are externally tagged, IE `{ variant = data }`.
+pub fn to_lua_value<T: Serialize + ?Sized>(value: &T) -> Result<LuaValue, LuaSerdeError> {
+    value.serialize(LuaSerializer)
+}
+
+/// Read anything deserializable straight out of a [LuaValue].
+///
+/// Since lua sends empty tables as null, null can also be read as an empty
+/// sequence or map.
+pub fn from_lua_value<T: DeserializeOwned>(value: LuaValue) -> Result<T, LuaSerdeError> {
+    T::deserialize(value)
+}
+
+// ======
+// Serializing
+// ======
+
+/// Serializes into a [LuaValue]. See [to_lua_value].
+pub struct LuaSerializer;
+
+impl Serializer for LuaSerializer {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    type SerializeSeq = TableBuilder;
+    type SerializeTuple = TableBuilder;
+    type SerializeTupleStruct = TableBuilder;
+    type SerializeTupleVariant = TableBuilder;
+    type SerializeMap = TableBuilder;
+    type SerializeStruct = TableBuilder;
+    type SerializeStructVariant = TableBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<LuaValue, LuaSerdeError> {
+        i64::try_from(v)
+            .map(LuaValue::Integer)
+            .map_err(|_| LuaSerdeError::IntegerTooLarge(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<LuaValue, LuaSerdeError> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<LuaValue, LuaSerdeError> {
+        let mut table = TableBuilder::new(None);
+        for byte in v {
+            table.push_item(LuaValue::Integer((*byte).into()));
+        }
+        Ok(table.build())
+    }
+
+    fn serialize_none(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<LuaValue, LuaSerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<LuaValue, LuaSerdeError> {
+        Ok(LuaValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<LuaValue, LuaSerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<LuaValue, LuaSerdeError> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(None))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<TableBuilder, LuaSerdeError> {
+        Ok(TableBuilder::new(Some(variant)))
+    }
+}
+
+/// `{ variant = value }`
+fn tagged(variant: &str, value: LuaValue) -> LuaValue {
+    LuaValue::Table(LuaPackedTable {
+        pairs: vec![LuaKeyValuePair {
+            key: LuaValue::String(variant.to_string()),
+            value,
+        }],
+    })
+}
+
+/// Builds up a table for every kind of compound type serde has.
+pub struct TableBuilder {
+    pairs: Vec<LuaKeyValuePair>,
+    /// Set for enum variants, which get wrapped in `{ variant = table }`.
+    variant: Option<&'static str>,
+    /// Keys of maps come in before their values.
+    pending_key: Option<LuaValue>,
+}
+
+impl TableBuilder {
+    fn new(variant: Option<&'static str>) -> Self {
+        Self {
+            pairs: Vec::new(),
+            variant,
+            pending_key: None,
+        }
+    }
+
+    /// Add the next item of an array. Lua arrays start at 1.
+    fn push_item(&mut self, value: LuaValue) {
+        let key = LuaValue::Integer(self.pairs.len() as i64 + 1);
+        self.pairs.push(LuaKeyValuePair { key, value });
+    }
+
+    fn build(self) -> LuaValue {
+        let table = LuaValue::Table(LuaPackedTable { pairs: self.pairs });
+        match self.variant {
+            Some(variant) => tagged(variant, table),
+            None => table,
+        }
+    }
+}
+
+impl ser::SerializeSeq for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LuaSerdeError> {
+        self.push_item(to_lua_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeTuple for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LuaSerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeTupleStruct for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LuaSerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeTupleVariant for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LuaSerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeMap for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), LuaSerdeError> {
+        self.pending_key = Some(to_lua_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), LuaSerdeError> {
+        let key = self
+            .pending_key
+            .take()
+            .ok_or_else(|| <LuaSerdeError as ser::Error>::custom("map value without a key"))?;
+        self.pairs.push(LuaKeyValuePair {
+            key,
+            value: to_lua_value(value)?,
+        });
+        Ok(())
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeStruct for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), LuaSerdeError> {
+        self.pairs.push(LuaKeyValuePair {
+            key: LuaValue::String(key.to_string()),
+            value: to_lua_value(value)?,
+        });
+        Ok(())
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+impl ser::SerializeStructVariant for TableBuilder {
+    type Ok = LuaValue;
+    type Error = LuaSerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), LuaSerdeError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<LuaValue, LuaSerdeError> {
+        Ok(self.build())
+    }
+}
+
+// ======
+// Deserializing
+// ======
+
+impl LuaPackedTable {
+    /// The values of this table in order, if it is an array, IE every key is an
+    /// integer and they run `1..=n` with no holes. Empty tables are arrays.
+    pub fn into_array(mut self) -> Result<Vec<LuaValue>, LuaPackedTable> {
+        if !self
+            .pairs
+            .iter()
+            .all(|pair| matches!(pair.key, LuaValue::Integer(_)))
+        {
+            return Err(self);
+        }
+        self.pairs.sort_by_key(|pair| pair.key.as_i64());
+        let in_order = self
+            .pairs
+            .iter()
+            .enumerate()
+            .all(|(index, pair)| pair.key.as_i64() == Some(index as i64 + 1));
+        if !in_order {
+            return Err(self);
+        }
+        Ok(self.pairs.into_iter().map(|pair| pair.value).collect())
+    }
+}
+
+impl LuaValue {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            LuaValue::Table(_) => Unexpected::Map,
+            LuaValue::String(string) => Unexpected::Str(string),
+            LuaValue::Integer(integer) => Unexpected::Signed(*integer),
+            LuaValue::Float(float) => Unexpected::Float(*float),
+            LuaValue::Bool(bool) => Unexpected::Bool(*bool),
+            LuaValue::Null => Unexpected::Unit,
+        }
+    }
+}
+
+/// Lua only has doubles on older versions, so whole floats are accepted as
+/// integers.
+macro_rules! deserialize_integer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LuaSerdeError> {
+                match self {
+                    LuaValue::Float(float)
+                        if float.fract() == 0.0 && float.abs() < i64::MAX as f64 =>
+                    {
+                        visitor.visit_i64(float as i64)
+                    }
+                    other => other.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for LuaValue {
+    type Error = LuaSerdeError;
+
+    /// Tables that look like arrays are handed over as sequences, everything
+    /// else as maps.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LuaSerdeError> {
+        match self {
+            LuaValue::Table(table) => match table.into_array() {
+                Ok(items) if !items.is_empty() => visitor.visit_seq(ArrayAccess(items.into_iter())),
+                Ok(_) => visitor.visit_map(TableAccess::new(Vec::new())),
+                Err(table) => visitor.visit_map(TableAccess::new(table.pairs)),
+            },
+            LuaValue::String(string) => visitor.visit_string(string),
+            LuaValue::Integer(integer) => visitor.visit_i64(integer),
+            LuaValue::Float(float) => visitor.visit_f64(float),
+            LuaValue::Bool(bool) => visitor.visit_bool(bool),
+            LuaValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LuaSerdeError> {
+        match self {
+            LuaValue::Null => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LuaSerdeError> {
+        match self {
+            LuaValue::Table(table) => match table.into_array() {
+                Ok(items) => visitor.visit_seq(ArrayAccess(items.into_iter())),
+                Err(_) => Err(de::Error::invalid_type(Unexpected::Map, &visitor)),
+            },
+            LuaValue::Null => visitor.visit_seq(ArrayAccess(Vec::new().into_iter())),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, LuaSerdeError> {
+        match self {
+            LuaValue::Table(table) => visitor.visit_map(TableAccess::new(table.pairs)),
+            LuaValue::Null => visitor.visit_map(TableAccess::new(Vec::new())),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Unit variants are just their name, everything else is a table with a
+    /// single `variant = data` pair.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        match self {
+            LuaValue::String(variant) => visitor.visit_enum(Variant {
+                name: LuaValue::String(variant),
+                data: None,
+            }),
+            LuaValue::Table(mut table) if table.pairs.len() == 1 => {
+                let pair = table.pairs.remove(0);
+                visitor.visit_enum(Variant {
+                    name: pair.key,
+                    data: Some(pair.value),
+                })
+            }
+            other => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"an enum variant",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf unit unit_struct identifier ignored_any
+    }
+}
+
+struct ArrayAccess(std::vec::IntoIter<LuaValue>);
+
+impl<'de> SeqAccess<'de> for ArrayAccess {
+    type Error = LuaSerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, LuaSerdeError> {
+        self.0.next().map(|item| seed.deserialize(item)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct TableAccess {
+    pairs: std::vec::IntoIter<LuaKeyValuePair>,
+    /// The value for the key we just handed out.
+    value: Option<LuaValue>,
+}
+
+impl TableAccess {
+    fn new(pairs: Vec<LuaKeyValuePair>) -> Self {
+        Self {
+            pairs: pairs.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for TableAccess {
+    type Error = LuaSerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, LuaSerdeError> {
+        let Some(pair) = self.pairs.next() else {
+            return Ok(None);
+        };
+        self.value = Some(pair.value);
+        seed.deserialize(pair.key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <LuaSerdeError as de::Error>::custom("table value without a key"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len())
+    }
+}
+
+struct Variant {
+    name: LuaValue,
+    /// None for unit variants.
+    data: Option<LuaValue>,
+}
+
+impl<'de> EnumAccess<'de> for Variant {
+    type Error = LuaSerdeError;
+    type Variant = VariantData;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantData), LuaSerdeError> {
+        Ok((seed.deserialize(self.name)?, VariantData(self.data)))
+    }
+}
+
+struct VariantData(Option<LuaValue>);
+
+impl<'de> VariantAccess<'de> for VariantData {
+    type Error = LuaSerdeError;
+
+    fn unit_variant(self) -> Result<(), LuaSerdeError> {
+        match self.0 {
+            None | Some(LuaValue::Null) => Ok(()),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"a unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, LuaSerdeError> {
+        match self.0 {
+            Some(data) => seed.deserialize(data),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"a newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        match self.0 {
+            Some(data) => data.deserialize_seq(visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, LuaSerdeError> {
+        match self.0 {
+            Some(data) => data.deserialize_map(visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &visitor)),
+        }
+    }
+}
+
+// ======
+// Json
+// ======
+
+impl From<serde_json::Value> for LuaValue {
+    /// Arrays become tables keyed `1..=n`. Numbers that fit in an `i64` become
+    /// integers.
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => LuaValue::Null,
+            serde_json::Value::Bool(bool) => LuaValue::Bool(bool),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(integer) => LuaValue::Integer(integer),
+                None => LuaValue::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(string) => LuaValue::String(string),
+            serde_json::Value::Array(items) => {
+                let mut table = TableBuilder::new(None);
+                for item in items {
+                    table.push_item(item.into());
+                }
+                table.build()
+            }
+            serde_json::Value::Object(map) => LuaValue::Table(LuaPackedTable {
+                pairs: map
+                    .into_iter()
+                    .map(|(key, value)| LuaKeyValuePair {
+                        key: LuaValue::String(key),
+                        value: value.into(),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl From<LuaValue> for serde_json::Value {
+    /// Tables that are arrays become json arrays, and other tables become
+    /// objects, with any keys that are not strings written out as lua would.
+    /// Empty tables become null, same as `helpers.serializeJSON`.
+    ///
+    /// NaN and infinity have no json equivalent, and also become null.
+    fn from(value: LuaValue) -> Self {
+        match value {
+            LuaValue::Table(table) => match table.into_array() {
+                Ok(items) if items.is_empty() => serde_json::Value::Null,
+                Ok(items) => serde_json::Value::Array(items.into_iter().map(Into::into).collect()),
+                Err(table) => serde_json::Value::Object(
+                    table
+                        .pairs
+                        .into_iter()
+                        .map(|pair| {
+                            let key = match pair.key {
+                                LuaValue::String(string) => string,
+                                other => other.to_string(),
+                            };
+                            (key, pair.value.into())
+                        })
+                        .collect(),
+                ),
+            },
+            LuaValue::String(string) => serde_json::Value::String(string),
+            LuaValue::Integer(integer) => integer.into(),
+            LuaValue::Float(float) => serde_json::Number::from_f64(float)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            LuaValue::Bool(bool) => serde_json::Value::Bool(bool),
+            LuaValue::Null => serde_json::Value::Null,
+        }
+    }
+}
+
+// ======
+// Tests
+// ======
+
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+enum TestShape {
+    Empty,
+    Wrapped(u8),
+    Pair(i32, String),
+    Named { x: f64, tags: Vec<String> },
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+struct TestThing {
+    name: String,
+    slots: BTreeMap<u32, String>,
+    shapes: Vec<TestShape>,
+    maybe: Option<bool>,
+    nothing: Vec<u8>,
+}
+
+#[test]
+/// Everything should come back out the same as it went in, even after a trip
+/// through json.
+fn lua_values_round_trip() {
+    let thing = TestThing {
+        name: "turtle".into(),
+        slots: BTreeMap::from([(1, "minecraft:coal".into()), (16, "minecraft:stone".into())]),
+        shapes: vec![
+            TestShape::Empty,
+            TestShape::Wrapped(7),
+            TestShape::Pair(-3, "three".into()),
+            TestShape::Named {
+                x: 1.5,
+                tags: vec!["a".into()],
+            },
+        ],
+        maybe: None,
+        nothing: Vec::new(),
+    };
+
+    let lua = to_lua_value(&thing).unwrap();
+    // Integer keys stay integers, and arrays start at 1.
+    assert_eq!(lua["slots"][16].as_str(), Some("minecraft:stone"));
+    assert_eq!(lua["shapes"][1].as_str(), Some("Empty"));
+    assert_eq!(lua["shapes"][3]["Pair"][2].as_str(), Some("three"));
+
+    let json = serde_json::to_string(&lua).unwrap();
+    assert!(json.contains(r#"{"key":16,"value":"minecraft:stone"}"#));
+    let back: LuaValue = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, lua);
+    assert_eq!(from_lua_value::<TestThing>(back).unwrap(), thing);
+
+    // Lua sends empty tables as null, and whole numbers may be floats.
+    let from_lua: LuaValue = serde_json::from_str(
+        r#"{"pairs":[
+            {"key":"name","value":"turtle"},
+            {"key":"slots","value":null},
+            {"key":"shapes","value":{"pairs":[{"key":1,"value":{"pairs":[{"key":"Wrapped","value":7.0}]}}]}},
+            {"key":"nothing","value":null}
+        ]}"#,
+    )
+    .unwrap();
+    let thing: TestThing = from_lua_value(from_lua).unwrap();
+    assert!(thing.slots.is_empty());
+    assert_eq!(thing.shapes, vec![TestShape::Wrapped(7)]);
+
+    assert_eq!(
+        to_lua_value(&u64::MAX),
+        Err(LuaSerdeError::IntegerTooLarge(u64::MAX))
+    );
+}
+
+#[test]
+/// Json values should convert both ways, with non-string keys written out.
+fn lua_values_and_json() {
+    let json = serde_json::json!({"list": [1, 2.5, "three"], "flag": true, "none": null});
+    let lua = LuaValue::from(json.clone());
+    assert_eq!(lua["list"][2].as_f64(), Some(2.5));
+    assert_eq!(serde_json::Value::from(lua), json);
+
+    let lua = to_lua_value(&BTreeMap::from([(2, "b"), (5, "e")])).unwrap();
+    assert_eq!(
+        serde_json::Value::from(lua),
+        serde_json::json!({"2": "b", "5": "e"})
+    );
+}
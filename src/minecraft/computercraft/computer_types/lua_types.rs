@@ -1,12 +1,14 @@
 // Lua tables are really funky, and the json exporting methods built into CC:Tweaked aren't quite
 // enough. So we have our own custom format.
 
-use serde::{Deserialize, Serialize};
+use std::{fmt::Display, ops::Index};
+
+use serde::{Deserialize, Deserializer, Serialize};
 
 // To pull everything back out we need to first pre-cast from `value` to a
 // packed table (if it is one) or a primitive.
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged)] // Try to deserialize in the order that the enums are laid out.
 pub enum LuaValue {
     // Packed table format
@@ -24,15 +26,290 @@ pub enum LuaValue {
 
 /// All of the tables we export from minecraft will be in this `key, value`
 /// pair format. Thus tables just turn into an array of pairs.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct LuaPackedTable {
+    // Empty tables come across as null.
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub pairs: Vec<LuaKeyValuePair>,
 }
 
+fn null_as_empty<'de, D>(deserializer: D) -> Result<Vec<LuaKeyValuePair>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
+
 // For the key-value pairs seen in our table export format
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct LuaKeyValuePair {
     // These can be recursive via containing more tables, be careful.
     pub key: LuaValue,
     pub value: LuaValue,
 }
+
+// ======
+// Navigation
+// ======
+
+/// A key to look something up in a table with. Lua tables can be keyed by
+/// anything, but strings and integers are all we ever use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LuaKey<'a> {
+    String(&'a str),
+    Integer(i64),
+}
+
+impl LuaKey<'_> {
+    fn matches(&self, key: &LuaValue) -> bool {
+        match (self, key) {
+            (LuaKey::String(wanted), LuaValue::String(key)) => wanted == key,
+            (LuaKey::Integer(wanted), LuaValue::Integer(key)) => wanted == key,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for LuaKey<'a> {
+    fn from(value: &'a str) -> Self {
+        LuaKey::String(value)
+    }
+}
+
+impl<'a> From<&'a String> for LuaKey<'a> {
+    fn from(value: &'a String) -> Self {
+        LuaKey::String(value)
+    }
+}
+
+impl From<i64> for LuaKey<'_> {
+    fn from(value: i64) -> Self {
+        LuaKey::Integer(value)
+    }
+}
+
+/// Returned when indexing into something that is not there, same idea as
+/// `serde_json::Value`.
+static NULL: LuaValue = LuaValue::Null;
+
+impl LuaPackedTable {
+    /// Look up a key in this table.
+    pub fn get<'a>(&self, key: impl Into<LuaKey<'a>>) -> Option<&LuaValue> {
+        let key = key.into();
+        self.pairs
+            .iter()
+            .find(|pair| key.matches(&pair.key))
+            .map(|pair| &pair.value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+impl LuaValue {
+    /// Look up a key, if this is a table.
+    pub fn get<'a>(&self, key: impl Into<LuaKey<'a>>) -> Option<&LuaValue> {
+        self.as_table()?.get(key)
+    }
+
+    /// Follow a dotted path of keys down through nested tables, IE
+    /// `task.definition.task_data`. Segments that are numbers are tried as
+    /// integer keys first.
+    pub fn lookup(&self, path: &str) -> Option<&LuaValue> {
+        path.split('.')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |value, segment| match segment.parse::<i64>() {
+                Ok(index) => value.get(index).or_else(|| value.get(segment)),
+                Err(_) => value.get(segment),
+            })
+    }
+
+    pub fn as_table(&self) -> Option<&LuaPackedTable> {
+        match self {
+            LuaValue::Table(table) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LuaValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            LuaValue::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    /// Integers are numbers too.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LuaValue::Integer(integer) => Some(*integer as f64),
+            LuaValue::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LuaValue::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, LuaValue::Null)
+    }
+}
+
+impl<'a> Index<&'a str> for LuaValue {
+    type Output = LuaValue;
+
+    /// Missing keys, and indexing into things that are not tables, give
+    /// [LuaValue::Null].
+    fn index(&self, key: &'a str) -> &Self::Output {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+impl Index<i64> for LuaValue {
+    type Output = LuaValue;
+
+    /// Missing keys, and indexing into things that are not tables, give
+    /// [LuaValue::Null].
+    fn index(&self, key: i64) -> &Self::Output {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+// ======
+// Printing
+// ======
+
+/// Prints as lua would write it. Use `{:#}` to spread tables over multiple
+/// lines.
+impl Display for LuaValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+impl LuaValue {
+    fn write_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        match self {
+            LuaValue::Table(table) => table.write_indented(f, depth),
+            LuaValue::String(string) => write!(f, "{string:?}"),
+            LuaValue::Integer(integer) => write!(f, "{integer}"),
+            LuaValue::Float(float) => write!(f, "{float}"),
+            LuaValue::Bool(bool) => write!(f, "{bool}"),
+            LuaValue::Null => write!(f, "nil"),
+        }
+    }
+
+    /// Keys that are valid lua names can be written bare.
+    fn write_key(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LuaValue::String(name)
+                if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                write!(f, "{name}")
+            }
+            other => {
+                write!(f, "[")?;
+                other.write_indented(f, 0)?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Display for LuaPackedTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+impl LuaPackedTable {
+    fn write_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        if self.pairs.is_empty() {
+            return write!(f, "{{}}");
+        }
+        let pretty = f.alternate();
+        write!(f, "{{")?;
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if pretty {
+                write!(f, "\n{}", "    ".repeat(depth + 1))?;
+            } else if i == 0 {
+                write!(f, " ")?;
+            }
+            pair.key.write_key(f)?;
+            write!(f, " = ")?;
+            pair.value.write_indented(f, depth + 1)?;
+            if pretty {
+                write!(f, ",")?;
+            } else if i + 1 < self.pairs.len() {
+                write!(f, ", ")?;
+            }
+        }
+        if pretty {
+            write!(f, "\n{}}}", "    ".repeat(depth))
+        } else {
+            write!(f, " }}")
+        }
+    }
+}
+
+// ======
+// Tests
+// ======
+
+#[test]
+/// Nested tables should be reachable by key, path, and index.
+fn navigating_packed_tables() {
+    let json = r#"{"pairs":[
+        {"key":"task","value":{"pairs":[
+            {"key":"definition","value":{"pairs":[{"key":"fuel_buffer","value":10}]}},
+            {"key":"list","value":{"pairs":[{"key":1,"value":"first"}]}}
+        ]}},
+        {"key":"empty","value":{"pairs":null}},
+        {"key":"odd key","value":true}
+    ]}"#;
+    let locals: LuaValue = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        locals["task"]["definition"]["fuel_buffer"].as_i64(),
+        Some(10)
+    );
+    assert_eq!(locals["task"]["list"][1].as_str(), Some("first"));
+    assert!(locals["task"]["nope"]["deeper"].is_null());
+    assert_eq!(
+        locals.lookup("task.definition.fuel_buffer"),
+        Some(&LuaValue::Integer(10))
+    );
+    assert_eq!(
+        locals.lookup("task.list.1").unwrap().as_str(),
+        Some("first")
+    );
+    assert!(locals.lookup("task.missing").is_none());
+    assert!(locals["empty"].as_table().unwrap().is_empty());
+
+    assert_eq!(
+        locals["task"].to_string(),
+        r#"{ definition = { fuel_buffer = 10 }, list = { [1] = "first" } }"#
+    );
+    assert_eq!(
+        format!("{:#}", locals["task"]["definition"]),
+        "{\n    fuel_buffer = 10,\n}"
+    );
+    assert!(locals.to_string().contains(r#"["odd key"] = true"#));
+}
@@ -1,4 +1,11 @@
 pub mod cc_panic;
+pub mod compact;
+pub mod hello;
+pub mod lua_serde;
 pub mod lua_types;
 pub mod packet_types;
+pub mod rpc_types;
+pub mod server_message;
+pub mod tasks;
 pub mod walkback_type;
+pub mod world_delta;
@@ -1,26 +1,138 @@
 // The packet format received and sent to the turtle
 
-use std::str::FromStr;
+use std::{collections::HashMap, fmt::Display};
 
-use regex::Regex;
-use serde::{Deserialize, Serialize, de::Error};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::minecraft::computercraft::computer_types::{
-    cc_panic::LuaPanic, walkback_type::Walkback,
+use crate::minecraft::{
+    computercraft::computer_types::{
+        cc_panic::LuaPanic,
+        compact::{self, CompactError, Encoding},
+        hello::HelloWorld,
+        rpc_types::TurtleResponse,
+        tasks::TaskResult,
+        walkback_type::Walkback,
+        world_delta::WorldDelta,
+    },
+    peripherals::inventory::GenericInventory,
 };
-use crate::minecraft::types::CoordinatePosition;
-
-const INCORRECT_PACKET_TYPE_ERROR: &str = "wrong packet type";
 
 /// Types of packet
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum PacketType {
     Panic,
     Walkback,
     Unknown,
     Debugging,
+    #[serde(rename = "task_result")]
+    TaskResult,
+    Ack,
+    Response,
+    Hello,
+    Pong,
+    Inventory,
+    #[serde(rename = "world_delta")]
+    WorldDelta,
+    Auth,
+    Provision,
+}
+
+impl Display for PacketType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketType::Panic => write!(f, "panic"),
+            PacketType::Walkback => write!(f, "walkback"),
+            PacketType::Unknown => write!(f, "unknown"),
+            PacketType::Debugging => write!(f, "debugging"),
+            PacketType::TaskResult => write!(f, "task_result"),
+            PacketType::Ack => write!(f, "ack"),
+            PacketType::Response => write!(f, "response"),
+            PacketType::Hello => write!(f, "hello"),
+            PacketType::Pong => write!(f, "pong"),
+            PacketType::Inventory => write!(f, "inventory"),
+            PacketType::WorldDelta => write!(f, "world_delta"),
+            PacketType::Auth => write!(f, "auth"),
+            PacketType::Provision => write!(f, "provision"),
+        }
+    }
+}
+
+// ==================
+// Errors
+// ==================
+
+/// Everything that can go wrong when turning the text that came out of a
+/// websocket into a typed packet.
+#[derive(Debug)]
+pub enum PacketDecodeError {
+    /// The incoming text was not a packet at all. Either it was not json, or
+    /// the outer packet fields (`id`, `uuid`, `packet_type`, `data`) were
+    /// missing or the wrong type.
+    Malformed(serde_json::Error),
+    /// Tried to cast a packet into a type that it was not tagged as.
+    WrongPacketType {
+        /// The type we tried to cast into.
+        expected: PacketType,
+        /// The type the packet was actually tagged as.
+        found: PacketType,
+    },
+    /// The packet was tagged correctly, but the inner data did not match the
+    /// format of that packet type.
+    InvalidData {
+        /// The type of the packet that failed to decode.
+        packet_type: PacketType,
+        /// Why the inner data did not match.
+        source: serde_json::Error,
+    },
+    /// The packet was sent with the compact encoding, but did not decode. See
+    /// `compact.rs`.
+    InvalidCompactData {
+        /// The type of the packet that failed to decode.
+        packet_type: PacketType,
+        /// What was wrong with it.
+        source: CompactError,
+    },
+}
+
+impl Display for PacketDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketDecodeError::Malformed(error) => {
+                write!(f, "malformed packet: {error}")
+            }
+            PacketDecodeError::WrongPacketType { expected, found } => {
+                write!(
+                    f,
+                    "wrong packet type: expected a `{expected}` packet, but got a `{found}` packet"
+                )
+            }
+            PacketDecodeError::InvalidData {
+                packet_type,
+                source,
+            } => {
+                write!(f, "invalid `{packet_type}` packet data: {source}")
+            }
+            PacketDecodeError::InvalidCompactData {
+                packet_type,
+                source,
+            } => {
+                write!(f, "invalid compact `{packet_type}` packet data: {source}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PacketDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PacketDecodeError::Malformed(error) => Some(error),
+            PacketDecodeError::WrongPacketType { .. } => None,
+            PacketDecodeError::InvalidData { source, .. } => Some(source),
+            PacketDecodeError::InvalidCompactData { source, .. } => Some(source),
+        }
+    }
 }
 
 // ==================
@@ -31,7 +143,7 @@ pub enum PacketType {
 ///
 /// The Internal type of this packet is not immediately known, so you must
 /// cast this into one of the other known packet types before manipulating it
-/// further.
+/// further. The easiest way to do that is with [RawTurtlePacket::decode].
 #[derive(Debug, Deserialize)]
 pub struct RawTurtlePacket {
     /// The turtle that sent this packet. We rename it to `from` on the rust
@@ -43,6 +155,9 @@ pub struct RawTurtlePacket {
     /// The UUID of the packet
     uuid: String,
 
+    /// Milliseconds since the unix epoch, from `os.epoch("utc")`.
+    timestamp: u64,
+
     /// What kind of packet this is
     packet_type: PacketType,
 
@@ -58,6 +173,181 @@ impl RawTurtlePacket {
     pub fn packet_type(&self) -> &PacketType {
         &self.packet_type
     }
+
+    /// Get the ID of the turtle that sent this packet.
+    pub fn from(&self) -> u16 {
+        self.from
+    }
+
+    /// Get the UUID of this packet.
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// When the turtle sent this packet, in milliseconds since the unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Cast this packet into the matching [TurtlePacket] variant based on the
+    /// type it was tagged with.
+    pub fn decode(self) -> Result<TurtlePacket, PacketDecodeError> {
+        match self.packet_type {
+            PacketType::Panic => Ok(TurtlePacket::Panic(self.try_into()?)),
+            PacketType::Walkback => Ok(TurtlePacket::Walkback(self.try_into()?)),
+            PacketType::Unknown => Ok(TurtlePacket::Unknown(self.try_into()?)),
+            PacketType::Debugging => Ok(TurtlePacket::Debugging(self.try_into()?)),
+            PacketType::TaskResult => Ok(TurtlePacket::TaskResult(self.try_into()?)),
+            PacketType::Ack => Ok(TurtlePacket::Ack(self.try_into()?)),
+            PacketType::Response => Ok(TurtlePacket::Response(self.try_into()?)),
+            PacketType::Hello => Ok(TurtlePacket::Hello(self.try_into()?)),
+            PacketType::Pong => Ok(TurtlePacket::Pong(self.try_into()?)),
+            PacketType::Inventory => Ok(TurtlePacket::Inventory(self.try_into()?)),
+            PacketType::WorldDelta => Ok(TurtlePacket::WorldDelta(self.try_into()?)),
+            PacketType::Auth => Ok(TurtlePacket::Auth(self.try_into()?)),
+            PacketType::Provision => Ok(TurtlePacket::Provision(self.try_into()?)),
+        }
+    }
+
+    /// Make sure this packet is tagged as the type we are about to cast it into.
+    fn expect_type(&self, expected: PacketType) -> Result<(), PacketDecodeError> {
+        if self.packet_type != expected {
+            return Err(PacketDecodeError::WrongPacketType {
+                expected,
+                found: self.packet_type,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize the inner data of a packet, tagging any failures with the type
+/// of packet we were working on.
+fn decode_inner<T: serde::de::DeserializeOwned>(
+    packet_type: PacketType,
+    data: Value,
+) -> Result<T, PacketDecodeError> {
+    serde_json::from_value(data).map_err(|source| PacketDecodeError::InvalidData {
+        packet_type,
+        source,
+    })
+}
+
+/// Same as [decode_inner], but for packet types that may also be sent with the
+/// compact encoding.
+fn decode_inner_or_compact<T: serde::de::DeserializeOwned>(
+    packet_type: PacketType,
+    data: Value,
+    decode_compact: fn(&[u8]) -> Result<T, CompactError>,
+) -> Result<T, PacketDecodeError> {
+    match compact::from_packet_data(&data) {
+        Some(bytes) => bytes
+            .and_then(|bytes| decode_compact(&bytes))
+            .map_err(|source| PacketDecodeError::InvalidCompactData {
+                packet_type,
+                source,
+            }),
+        None => decode_inner(packet_type, data),
+    }
+}
+
+// ==================
+// Decoded packet
+// ==================
+
+/// A packet from a turtle that has already been cast into its inner type.
+///
+/// Match on this instead of manually checking the [PacketType] of a
+/// [RawTurtlePacket] and casting it yourself.
+#[derive(Debug)]
+pub enum TurtlePacket {
+    Panic(PanicPacket),
+    Walkback(WalkbackPacket),
+    Unknown(UnknownPacket),
+    Debugging(DebuggingPacket),
+    TaskResult(TaskResultPacket),
+    Ack(AckPacket),
+    Response(ResponsePacket),
+    Hello(HelloPacket),
+    Pong(PongPacket),
+    Inventory(InventoryPacket),
+    WorldDelta(WorldDeltaPacket),
+    Auth(AuthPacket),
+    Provision(ProvisionPacket),
+}
+
+impl TurtlePacket {
+    /// Decode a packet straight from the json text that came out of the websocket.
+    pub fn from_json(json: &str) -> Result<Self, PacketDecodeError> {
+        let raw: RawTurtlePacket =
+            serde_json::from_str(json).map_err(PacketDecodeError::Malformed)?;
+        raw.decode()
+    }
+
+    /// The type this packet was tagged with.
+    pub fn packet_type(&self) -> PacketType {
+        match self {
+            TurtlePacket::Panic(_) => PacketType::Panic,
+            TurtlePacket::Walkback(_) => PacketType::Walkback,
+            TurtlePacket::Unknown(_) => PacketType::Unknown,
+            TurtlePacket::Debugging(_) => PacketType::Debugging,
+            TurtlePacket::TaskResult(_) => PacketType::TaskResult,
+            TurtlePacket::Ack(_) => PacketType::Ack,
+            TurtlePacket::Response(_) => PacketType::Response,
+            TurtlePacket::Hello(_) => PacketType::Hello,
+            TurtlePacket::Pong(_) => PacketType::Pong,
+            TurtlePacket::Inventory(_) => PacketType::Inventory,
+            TurtlePacket::WorldDelta(_) => PacketType::WorldDelta,
+            TurtlePacket::Auth(_) => PacketType::Auth,
+            TurtlePacket::Provision(_) => PacketType::Provision,
+        }
+    }
+
+    /// The turtle that sent this packet.
+    pub fn from(&self) -> u16 {
+        match self {
+            TurtlePacket::Panic(packet) => packet.from,
+            TurtlePacket::Walkback(packet) => packet.from,
+            TurtlePacket::Unknown(packet) => packet.from,
+            TurtlePacket::Debugging(packet) => packet.from,
+            TurtlePacket::TaskResult(packet) => packet.from,
+            TurtlePacket::Ack(packet) => packet.from,
+            TurtlePacket::Response(packet) => packet.from,
+            TurtlePacket::Hello(packet) => packet.from,
+            TurtlePacket::Pong(packet) => packet.from,
+            TurtlePacket::Inventory(packet) => packet.from,
+            TurtlePacket::WorldDelta(packet) => packet.from,
+            TurtlePacket::Auth(packet) => packet.from,
+            TurtlePacket::Provision(packet) => packet.from,
+        }
+    }
+
+    /// The UUID of this packet.
+    pub fn uuid(&self) -> &str {
+        match self {
+            TurtlePacket::Panic(packet) => &packet.uuid,
+            TurtlePacket::Walkback(packet) => &packet.uuid,
+            TurtlePacket::Unknown(packet) => &packet.uuid,
+            TurtlePacket::Debugging(packet) => &packet.uuid,
+            TurtlePacket::TaskResult(packet) => &packet.uuid,
+            TurtlePacket::Ack(packet) => &packet.uuid,
+            TurtlePacket::Response(packet) => &packet.uuid,
+            TurtlePacket::Hello(packet) => &packet.uuid,
+            TurtlePacket::Pong(packet) => &packet.uuid,
+            TurtlePacket::Inventory(packet) => &packet.uuid,
+            TurtlePacket::WorldDelta(packet) => &packet.uuid,
+            TurtlePacket::Auth(packet) => &packet.uuid,
+            TurtlePacket::Provision(packet) => &packet.uuid,
+        }
+    }
+}
+
+impl TryFrom<RawTurtlePacket> for TurtlePacket {
+    type Error = PacketDecodeError;
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.decode()
+    }
 }
 
 // ==================
@@ -72,11 +362,14 @@ impl RawTurtlePacket {
 /// This packet type is mostly intended for troubleshooting and post-runtime
 /// inspection. Additionally, turtles that return this packet will reboot
 /// themselves, and may need to be re-initialized.
+#[derive(Debug)]
 pub struct PanicPacket {
     /// The turtle that sent this packet
     pub from: u16,
     /// The UUID of the packet
     pub uuid: String,
+    /// When the turtle panicked, in milliseconds since the unix epoch.
+    pub timestamp: u64,
     /// The panic data
     pub panic_data: LuaPanic,
 }
@@ -84,20 +377,17 @@ pub struct PanicPacket {
 impl TryFrom<RawTurtlePacket> for PanicPacket {
     // This should be able to grab all of the panic data always,
     // but we still can error if needed.
-    type Error = serde_json::error::Error;
+    type Error = PacketDecodeError;
 
     fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
-        if value.packet_type == PacketType::Panic {
-            return Err(serde_json::error::Error::custom(
-                INCORRECT_PACKET_TYPE_ERROR,
-            ));
-        }
+        value.expect_type(PacketType::Panic)?;
 
-        let panic_data: LuaPanic = serde_json::from_value(value.inner_data)?;
+        let panic_data: LuaPanic = decode_inner(PacketType::Panic, value.inner_data)?;
 
         Ok(PanicPacket {
             from: value.from,
             uuid: value.uuid,
+            timestamp: value.timestamp,
             panic_data,
         })
     }
@@ -109,32 +399,35 @@ impl TryFrom<RawTurtlePacket> for PanicPacket {
 
 /// Walkback dumps returned from turtles. May be very large, so avoid cloning
 /// and pass by reference only.
+#[derive(Debug)]
 pub struct WalkbackPacket {
     /// The turtle that sent this packet
     pub from: u16,
     /// The UUID of the packet
     pub uuid: String,
+    /// When the walkback was dumped, in milliseconds since the unix epoch.
+    pub timestamp: u64,
     /// The walkback data contained within this packet
     pub walkback: Walkback,
 }
 
 impl TryFrom<RawTurtlePacket> for WalkbackPacket {
-    type Error = serde_json::error::Error; // We either cast, or don't.
+    type Error = PacketDecodeError; // We either cast, or don't.
 
     fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
-        if value.packet_type == PacketType::Walkback {
-            return Err(serde_json::error::Error::custom(
-                INCORRECT_PACKET_TYPE_ERROR,
-            ));
-        }
+        value.expect_type(PacketType::Walkback)?;
 
         // Attempt to cast down into the walkback type.
-        let convert: Walkback = serde_json::from_value(value.inner_data)?;
+        let convert: Walkback = decode_inner_or_compact(
+            PacketType::Walkback,
+            value.inner_data,
+            compact::decode_walkback,
+        )?;
 
-        // Temporary.
         Ok(WalkbackPacket {
             from: value.from,
             uuid: value.uuid,
+            timestamp: value.timestamp,
             walkback: convert,
         })
     }
@@ -149,6 +442,7 @@ impl TryFrom<RawTurtlePacket> for WalkbackPacket {
 /// this packet may be completely nonsensical, even the from id could be wrong.
 ///
 /// No guarantees about the validity of the data are made.
+#[derive(Debug)]
 pub struct UnknownPacket {
     /// The turtle that sent this packet
     pub from: u16,
@@ -159,16 +453,11 @@ pub struct UnknownPacket {
 }
 
 impl TryFrom<RawTurtlePacket> for UnknownPacket {
-    type Error = serde_json::error::Error; // We either cast, or don't.
+    type Error = PacketDecodeError; // We either cast, or don't.
 
     fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
-        if value.packet_type == PacketType::Walkback {
-            return Err(serde_json::error::Error::custom(
-                INCORRECT_PACKET_TYPE_ERROR,
-            ));
-        }
+        value.expect_type(PacketType::Unknown)?;
 
-        // Temporary.
         Ok(UnknownPacket {
             from: value.from,
             uuid: value.uuid,
@@ -195,16 +484,11 @@ pub struct DebuggingPacket {
 }
 
 impl TryFrom<RawTurtlePacket> for DebuggingPacket {
-    type Error = serde_json::error::Error; // We either cast, or don't.
+    type Error = PacketDecodeError; // We either cast, or don't.
 
     fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
-        if value.packet_type == PacketType::Walkback {
-            return Err(serde_json::error::Error::custom(
-                INCORRECT_PACKET_TYPE_ERROR,
-            ));
-        }
+        value.expect_type(PacketType::Debugging)?;
 
-        // Temporary.
         Ok(DebuggingPacket {
             from: value.from,
             uuid: value.uuid,
@@ -212,3 +496,443 @@ impl TryFrom<RawTurtlePacket> for DebuggingPacket {
         })
     }
 }
+
+// =========
+// Task result
+// =========
+
+/// Sent when a task (not a sub-task) leaves the task queue, wether it finished
+/// or failed.
+#[derive(Debug)]
+pub struct TaskResultPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The `name` of the task that ended, IE `tree_chop`.
+    pub task_name: String,
+    /// How the task ended.
+    pub result: TaskResult,
+}
+
+/// The inner data of a task result packet, as sent by `finishTask`.
+#[derive(Deserialize)]
+struct TaskResultInner {
+    task_name: String,
+    result: TaskResult,
+}
+
+impl TryFrom<RawTurtlePacket> for TaskResultPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::TaskResult)?;
+
+        let inner: TaskResultInner = decode_inner(PacketType::TaskResult, value.inner_data)?;
+
+        Ok(TaskResultPacket {
+            from: value.from,
+            uuid: value.uuid,
+            task_name: inner.task_name,
+            result: inner.result,
+        })
+    }
+}
+
+// =========
+// Ack
+// =========
+
+/// Turtles send these back when they get a server packet that needs to be
+/// acknowledged. See [ServerMessage::needs_ack].
+///
+/// [ServerMessage::needs_ack]: crate::minecraft::computercraft::computer_types::server_message::ServerMessage::needs_ack
+#[derive(Debug)]
+pub struct AckPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The UUID of the server packet that is being acknowledged.
+    pub acked: String,
+}
+
+/// The inner data of an ack packet. Same format as `ServerMessage::Ack`.
+#[derive(Deserialize)]
+struct AckInner {
+    uuid: String,
+}
+
+impl TryFrom<RawTurtlePacket> for AckPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Ack)?;
+
+        let inner: AckInner = decode_inner(PacketType::Ack, value.inner_data)?;
+
+        Ok(AckPacket {
+            from: value.from,
+            uuid: value.uuid,
+            acked: inner.uuid,
+        })
+    }
+}
+
+// =========
+// Pong
+// =========
+
+/// The answer to a `ServerMessage::Ping`. Only used to tell that the turtle is
+/// still alive, so these are never acked.
+#[derive(Debug)]
+pub struct PongPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The UUID of the ping being answered.
+    pub ping: String,
+}
+
+impl TryFrom<RawTurtlePacket> for PongPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Pong)?;
+
+        // Same format as an ack.
+        let inner: AckInner = decode_inner(PacketType::Pong, value.inner_data)?;
+
+        Ok(PongPacket {
+            from: value.from,
+            uuid: value.uuid,
+            ping: inner.uuid,
+        })
+    }
+}
+
+// =========
+// Inventory
+// =========
+
+/// Everything a turtle is holding. Sent whenever its inventory changes.
+#[derive(Debug)]
+pub struct InventoryPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    pub inventory: GenericInventory,
+}
+
+impl TryFrom<RawTurtlePacket> for InventoryPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Inventory)?;
+
+        let inventory: GenericInventory = decode_inner(PacketType::Inventory, value.inner_data)?;
+
+        Ok(InventoryPacket {
+            from: value.from,
+            uuid: value.uuid,
+            inventory,
+        })
+    }
+}
+
+// =========
+// World delta
+// =========
+
+/// What a turtle has seen since its last world delta. See `world_delta.rs`.
+#[derive(Debug)]
+pub struct WorldDeltaPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// When the delta was taken, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub delta: WorldDelta,
+}
+
+impl TryFrom<RawTurtlePacket> for WorldDeltaPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::WorldDelta)?;
+
+        let delta: WorldDelta = decode_inner_or_compact(
+            PacketType::WorldDelta,
+            value.inner_data,
+            compact::decode_world_delta,
+        )?;
+
+        Ok(WorldDeltaPacket {
+            from: value.from,
+            uuid: value.uuid,
+            timestamp: value.timestamp,
+            delta,
+        })
+    }
+}
+
+// =========
+// Response
+// =========
+
+/// The turtle's answer to a `ServerMessage::Request`.
+#[derive(Debug)]
+pub struct ResponsePacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The id of the request this is answering.
+    pub id: String,
+    /// The response, or why the turtle could not answer.
+    pub result: Result<TurtleResponse, String>,
+}
+
+/// The inner data of a response packet, as sent by `NETWORKING.respond`.
+#[derive(Deserialize)]
+struct ResponseInner {
+    id: String,
+    #[serde(default)]
+    response: Option<TurtleResponse>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl TryFrom<RawTurtlePacket> for ResponsePacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Response)?;
+
+        let inner: ResponseInner = decode_inner(PacketType::Response, value.inner_data)?;
+        let result = match (inner.response, inner.error) {
+            (Some(response), None) => Ok(response),
+            (_, Some(error)) => Err(error),
+            (None, None) => Err("Turtle sent an empty response.".to_string()),
+        };
+
+        Ok(ResponsePacket {
+            from: value.from,
+            uuid: value.uuid,
+            id: inner.id,
+            result,
+        })
+    }
+}
+
+// =========
+// Hello
+// =========
+
+/// The first packet a turtle sends after connecting. See `hello.rs`.
+#[derive(Debug)]
+pub struct HelloPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The protocol version the turtle speaks.
+    pub protocol_version: u32,
+    /// The Adler-32 hash of every library the turtle has installed, keyed by
+    /// file name.
+    pub libraries: HashMap<String, u32>,
+    /// The contents of `hello_world.json`, if the turtle has one.
+    pub hello_world: Option<HelloWorld>,
+    /// The encodings the turtle can send its large packets in. Encodings we do
+    /// not know are left out.
+    pub encodings: Vec<Encoding>,
+}
+
+/// The inner data of a hello packet.
+#[derive(Deserialize)]
+struct HelloInner {
+    protocol_version: u32,
+    // Empty tables come across as null.
+    libraries: Option<HashMap<String, u32>>,
+    #[serde(default)]
+    hello_world: Option<HelloWorld>,
+    // Older turtles only speak json, and do not send this.
+    #[serde(default)]
+    encodings: Option<Vec<String>>,
+}
+
+impl TryFrom<RawTurtlePacket> for HelloPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Hello)?;
+
+        let inner: HelloInner = decode_inner(PacketType::Hello, value.inner_data)?;
+
+        Ok(HelloPacket {
+            from: value.from,
+            uuid: value.uuid,
+            protocol_version: inner.protocol_version,
+            libraries: inner.libraries.unwrap_or_default(),
+            hello_world: inner.hello_world,
+            encodings: inner
+                .encodings
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|name| Encoding::from_name(name))
+                .collect(),
+        })
+    }
+}
+
+// =========
+// Auth
+// =========
+
+/// The turtle's answer to a `ServerMessage::Challenge`. See `auth.rs`.
+#[derive(Debug)]
+pub struct AuthPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The HMAC of the challenge, as hex. None if the turtle has no token.
+    pub proof: Option<String>,
+}
+
+/// The inner data of an auth packet.
+#[derive(Deserialize)]
+struct AuthInner {
+    #[serde(default)]
+    proof: Option<String>,
+}
+
+impl TryFrom<RawTurtlePacket> for AuthPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Auth)?;
+
+        // Turtles without a token send no data at all.
+        let inner: Option<AuthInner> = decode_inner(PacketType::Auth, value.inner_data)?;
+
+        Ok(AuthPacket {
+            from: value.from,
+            uuid: value.uuid,
+            proof: inner.and_then(|inner| inner.proof),
+        })
+    }
+}
+
+// =========
+// Provision
+// =========
+
+/// Sent during mitosis, asking for a token for the turtle being built. Answered
+/// with a `ServerMessage::Provisioned`. See `auth.rs`.
+#[derive(Debug)]
+pub struct ProvisionPacket {
+    /// The turtle that sent this packet
+    pub from: u16,
+    /// The UUID of the packet
+    pub uuid: String,
+    /// The computer ID of the new turtle.
+    pub computer_id: u16,
+}
+
+/// The inner data of a provision packet.
+#[derive(Deserialize)]
+struct ProvisionInner {
+    computer_id: u16,
+}
+
+impl TryFrom<RawTurtlePacket> for ProvisionPacket {
+    type Error = PacketDecodeError; // We either cast, or don't.
+
+    fn try_from(value: RawTurtlePacket) -> Result<Self, Self::Error> {
+        value.expect_type(PacketType::Provision)?;
+
+        let inner: ProvisionInner = decode_inner(PacketType::Provision, value.inner_data)?;
+
+        Ok(ProvisionPacket {
+            from: value.from,
+            uuid: value.uuid,
+            computer_id: inner.computer_id,
+        })
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Packets should land in the variant matching their tag.
+fn decode_dispatches_on_packet_type() {
+    let json =
+        r#"{"id":12,"uuid":"ABCDEFGH","timestamp":0,"packet_type":"debugging","data":"ping"}"#;
+    let packet = TurtlePacket::from_json(json).unwrap();
+    assert_eq!(packet.packet_type(), PacketType::Debugging);
+    assert_eq!(packet.from(), 12);
+    assert_eq!(packet.uuid(), "ABCDEFGH");
+    let TurtlePacket::Debugging(debug) = packet else {
+        panic!("Not a debugging packet!")
+    };
+    assert_eq!(debug.inner_data, Value::String("ping".into()));
+
+    let json = r#"{"id":3,"uuid":"AAAAAAAA","timestamp":0,"packet_type":"walkback","data":{"cur_position":{"position":{"x":1,"y":2,"z":3},"facing":"n"}}}"#;
+    let TurtlePacket::Walkback(walkback) = TurtlePacket::from_json(json).unwrap() else {
+        panic!("Not a walkback packet!")
+    };
+    assert_eq!(walkback.walkback.cur_position.position.y, 2);
+}
+
+#[test]
+/// Casting into the wrong packet type must fail, and say why.
+fn wrong_packet_type_is_rejected() {
+    let json = r#"{"id":1,"uuid":"ZZZZZZZZ","timestamp":0,"packet_type":"debugging","data":"hi"}"#;
+    let raw: RawTurtlePacket = serde_json::from_str(json).unwrap();
+    let result: Result<WalkbackPacket, _> = raw.try_into();
+    match result {
+        Err(PacketDecodeError::WrongPacketType { expected, found }) => {
+            assert_eq!(expected, PacketType::Walkback);
+            assert_eq!(found, PacketType::Debugging);
+        }
+        other => panic!("Expected a wrong packet type error, got {other:?}"),
+    }
+}
+
+#[test]
+/// Bad inner data should report which packet type it was.
+fn invalid_data_reports_packet_type() {
+    let json = r#"{"id":1,"uuid":"ZZZZZZZZ","timestamp":0,"packet_type":"walkback","data":"not a walkback"}"#;
+    match TurtlePacket::from_json(json) {
+        Err(PacketDecodeError::InvalidData { packet_type, .. }) => {
+            assert_eq!(packet_type, PacketType::Walkback)
+        }
+        other => panic!("Expected invalid data, got {other:?}"),
+    }
+    assert!(matches!(
+        TurtlePacket::from_json("pong"),
+        Err(PacketDecodeError::Malformed(_))
+    ));
+}
+
+#[test]
+/// Task results should come out typed.
+fn task_result_packet_decodes() {
+    use crate::minecraft::computercraft::computer_types::tasks::TaskFailureReason;
+
+    let json = r#"{"id":7,"uuid":"QWERTYUI","timestamp":0,"packet_type":"task_result","data":{"task_name":"mine_to_level","result":{"kind":"fail","reason":"out of fuel","stacktrace":"stack traceback:"}}}"#;
+    let TurtlePacket::TaskResult(packet) = TurtlePacket::from_json(json).unwrap() else {
+        panic!("Not a task result packet!")
+    };
+    assert_eq!(packet.task_name, "mine_to_level");
+    let TaskResult::Failure(failure) = packet.result else {
+        panic!("Task should have failed!")
+    };
+    assert_eq!(failure.reason, TaskFailureReason::OutOfFuel);
+}
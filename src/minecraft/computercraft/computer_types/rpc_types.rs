@@ -0,0 +1,77 @@
+// Requests the server can make of a turtle, and what the turtle sends back.
+// See `request_handlers` in `mesh_os.lua`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::minecraft::{
+    computercraft::computer_types::walkback_type::Walkback,
+    peripherals::inventory::GenericInventory, types::MinecraftPosition,
+};
+
+/// Something we want to know about a turtle right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TurtleRequest {
+    /// How much fuel the turtle has.
+    Fuel,
+    /// Where the turtle thinks it is.
+    Position,
+    /// Everything the turtle is holding.
+    Inventory,
+    /// A full dump of the turtle's walkback.
+    Walkback,
+}
+
+/// The answer to a [TurtleRequest]. The variant always matches the request.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TurtleResponse {
+    Fuel {
+        /// `None` if fuel is disabled on the server.
+        #[serde(default)]
+        level: Option<u32>,
+        /// `None` if fuel is disabled on the server.
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    Position {
+        position: MinecraftPosition,
+    },
+    Inventory {
+        inventory: GenericInventory,
+    },
+    Walkback {
+        // Walkbacks are huge, don't make every response that big.
+        walkback: Box<Walkback>,
+    },
+}
+
+#[test]
+/// Requests go out as just their kind.
+fn request_serialization() {
+    let json = serde_json::to_value(TurtleRequest::Fuel).unwrap();
+    assert_eq!(json, serde_json::json!({ "kind": "fuel" }));
+
+    // Unlimited fuel comes back with no fields at all.
+    let response: TurtleResponse = serde_json::from_str(r#"{"kind":"fuel"}"#).unwrap();
+    assert!(matches!(
+        response,
+        TurtleResponse::Fuel {
+            level: None,
+            limit: None
+        }
+    ));
+
+    // Inventories come back in the same shape as the inventory packet.
+    let json = serde_json::to_value(TurtleRequest::Inventory).unwrap();
+    assert_eq!(json, serde_json::json!({ "kind": "inventory" }));
+    let response: TurtleResponse = serde_json::from_str(
+        r#"{"kind":"inventory","inventory":{"size":2,"slots":[null,{"item":"minecraft:coal","count":5}]}}"#,
+    )
+    .unwrap();
+    let TurtleResponse::Inventory { inventory } = response else {
+        panic!("Expected an inventory!")
+    };
+    assert!(inventory.slots[0].is_none());
+    assert_eq!(inventory.slots[1].unwrap().count, 5);
+}
@@ -0,0 +1,167 @@
+// The packet format sent from the server to the turtles.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::minecraft::computercraft::computer_types::{
+    compact::Encoding, hello::HelloResponse, rpc_types::TurtleRequest, tasks::TaskDefinition,
+};
+
+/// Messages the server can send to a turtle.
+///
+/// This is tagged the same way as the packets turtles send to us, so on the lua
+/// side the variant ends up in `packet_type`, and the inner data (if any) ends
+/// up in `data`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "packet_type", content = "data", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Add a task to the turtle's task queue.
+    AssignTask(TaskDefinition),
+    /// Stop working on the current task.
+    CancelTask,
+    /// Let the turtle know that we got one of its packets.
+    Ack {
+        /// The UUID of the packet we are acknowledging.
+        uuid: String,
+    },
+    /// Our answer to the turtle's hello packet.
+    HelloReply {
+        #[serde(flatten)]
+        response: HelloResponse,
+        /// How the turtle should send its large packets from now on. Picked
+        /// out of the encodings it offered.
+        encoding: Encoding,
+    },
+    /// Are you still there? Turtles answer with a `pong` packet.
+    Ping,
+    /// Ask the turtle something. It answers with a `response` packet carrying
+    /// the same id, so these do not need to be acked.
+    Request {
+        /// Matches the response to this request.
+        id: String,
+        request: TurtleRequest,
+    },
+    /// Prove you hold the token for your computer ID. Sent in answer to a hello
+    /// when the server requires authentication, the turtle answers with an
+    /// `auth` packet. See `auth.rs`.
+    Challenge {
+        /// Random, and never used twice.
+        nonce: String,
+    },
+    /// The answer to a `provision` packet.
+    Provisioned {
+        /// The computer the token is for.
+        computer_id: u16,
+        /// The new turtle's token. None if we do not require tokens, or that
+        /// computer already has one.
+        token: Option<String>,
+    },
+    /// We missed some of the turtle's world deltas. The turtle answers with a
+    /// full delta of everything it knows. See `world_delta.rs`.
+    ResyncWorld,
+    /// A reply to something the turtle sent with `debugSend`. Only used in
+    /// tests and debugging.
+    DebugReply(Value),
+}
+
+impl ServerMessage {
+    /// Wether the turtle must acknowledge this message. These are kept around
+    /// and re-sent until the turtle acks them.
+    ///
+    /// Keep this in sync with `ACKED_PACKET_TYPES` in `networking.lua`.
+    pub fn needs_ack(&self) -> bool {
+        matches!(
+            self,
+            ServerMessage::AssignTask(_)
+                | ServerMessage::CancelTask
+                | ServerMessage::Provisioned { .. }
+                | ServerMessage::ResyncWorld
+        )
+    }
+
+    /// Wrap this message in a packet with a brand new UUID and the current time.
+    pub fn into_packet(self) -> ServerPacket {
+        ServerPacket {
+            uuid: new_packet_uuid(),
+            timestamp: current_timestamp(),
+            message: self,
+        }
+    }
+}
+
+/// The envelope every outgoing message is wrapped in. Matches the `packet`
+/// class in `networking.lua`, minus the computer ID, since the turtle already
+/// knows who it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerPacket {
+    /// The UUID of this packet.
+    pub uuid: String,
+    /// Milliseconds since the unix epoch, same as `os.epoch("utc")`.
+    pub timestamp: u64,
+    /// The message itself. This provides the `packet_type` and `data` fields.
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
+impl ServerPacket {
+    /// Turn this packet into the json string that goes down the websocket.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Make a packet UUID in the same format the turtles use. See `getUUID` in
+/// `networking.lua`.
+pub fn new_packet_uuid() -> String {
+    let mut rng = rand::rng();
+    (0..8)
+        .map(|_| char::from(rng.random_range(b'A'..=b'Z')))
+        .collect()
+}
+
+/// Milliseconds since the unix epoch.
+pub fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// The envelope should always contain the uuid, timestamp, and packet type.
+fn server_packet_envelope() {
+    let packet = ServerMessage::DebugReply("pong".into()).into_packet();
+    let json: Value = serde_json::from_str(&packet.to_json().unwrap()).unwrap();
+    assert_eq!(json["uuid"].as_str().unwrap().len(), 8);
+    assert!(json["timestamp"].is_u64());
+    assert_eq!(json["packet_type"], "debug_reply");
+    assert_eq!(json["data"], "pong");
+
+    // Messages with no data have no data field.
+    let packet = ServerMessage::Ping.into_packet();
+    let json: Value = serde_json::from_str(&packet.to_json().unwrap()).unwrap();
+    assert_eq!(json["packet_type"], "ping");
+    assert!(json.get("data").is_none());
+
+    // And we can read our own packets back in.
+    let ack = ServerMessage::Ack {
+        uuid: "ABCDEFGH".into(),
+    }
+    .into_packet();
+    let round_trip: ServerPacket = serde_json::from_str(&ack.to_json().unwrap()).unwrap();
+    assert_eq!(round_trip, ack);
+    let provisioned = ServerMessage::Provisioned {
+        computer_id: 9,
+        token: None,
+    }
+    .into_packet();
+    let round_trip: ServerPacket = serde_json::from_str(&provisioned.to_json().unwrap()).unwrap();
+    assert_eq!(round_trip, provisioned);
+}
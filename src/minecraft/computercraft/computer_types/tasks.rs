@@ -0,0 +1,430 @@
+// Task definitions, mirroring `tasks/task_types.lua` and the task files that
+// define each payload.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::minecraft::types::CoordinatePosition;
+
+/// What the turtle should do, and how it should behave while doing it.
+///
+/// This serializes into exactly the table that `mesh_os.testAddTask` expects.
+/// See `TaskDefinition` in `task_types.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskDefinition {
+    /// Wether or not the task needs to end where it started.
+    pub return_to_start: bool,
+    /// Wether or not the task needs to face in the same direction it started in.
+    pub return_to_facing: bool,
+    /// Target amount of fuel to keep in the turtle. The task fails if it falls
+    /// below this number and the task does not refuel itself.
+    pub fuel_buffer: u32,
+    /// The inner configuration for the specific task.
+    pub task_data: TaskData,
+}
+
+/// The inner configuration for each kind of task. See `TaskDataType` in
+/// `task_types.lua`.
+///
+/// On the lua side these are all tables with a `name` field that decides which
+/// task runs, so we tag on that.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name")]
+pub enum TaskData {
+    #[serde(rename = "tree_chop")]
+    TreeChop(TreeChopTaskData),
+    #[serde(rename = "recursive_miner")]
+    RecursiveMiner(RecursiveMinerData),
+    #[serde(rename = "branch_miner")]
+    BranchMiner(BranchMinerData),
+    #[serde(rename = "craft_task")]
+    Crafting(CraftingData),
+    #[serde(rename = "smelt_task")]
+    Smelting(SmeltingData),
+    #[serde(rename = "block_search")]
+    BlockSearch(BlockSearchData),
+    /// Flies up to the build limit and back down to find out what height the
+    /// turtle is at. Has no configuration.
+    #[serde(rename = "normalize_height")]
+    NormalizeHeight,
+    #[serde(rename = "mine_to_level")]
+    MineToLevel(MineToLevelData),
+    /// Builds a brand new turtle. Has no configuration.
+    #[serde(rename = "mitosis_task")]
+    Mitosis,
+}
+
+// ==================
+// Task payloads
+// ==================
+
+/// See `tree_chop.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeChopTaskData {
+    /// Maximum number of seconds to spend in this task.
+    pub timeout: u64,
+    /// Number of logs to gather before stopping. Will harvest until the timeout
+    /// if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_logs: Option<u32>,
+    /// The maximum number of saplings to keep on hand. Defaults to 16.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_saplings: Option<u32>,
+    /// The minimum number of saplings to keep on hand. Defaults to 4.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_saplings: Option<u32>,
+}
+
+/// See `recursive_miner.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecursiveMinerData {
+    /// Maximum number of seconds to spend in this task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Maximum number of blocks to mine before exiting early.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks_mined_limit: Option<u32>,
+    /// Groups of blocks that can be mined, ordered by priority.
+    pub mineable_groups: Vec<BlockGroup>,
+    /// Patterns of items that are allowed to be burnt as fuel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel_patterns: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discardables: Option<DiscardableItems>,
+}
+
+/// See `branch_miner.lua`.
+///
+/// If neither `timeout` or `trunk_length` are set, the turtle will keep going
+/// until it has everything it wants, or runs out of fuel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchMinerData {
+    pub desired: DesiredBlocks,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incidental: Option<IncidentalBlocks>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discardables: Option<DiscardableItems>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel_items: Option<FuelItems>,
+    /// Maximum number of seconds to spend in this task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Maximum distance to mine forwards.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trunk_length: Option<u32>,
+}
+
+/// See `craft.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CraftingData {
+    pub recipe: CraftingRecipe,
+    /// How many of each ingredient to place per slot.
+    pub count: u32,
+}
+
+/// A 3x3 crafting grid, left to right, top to bottom.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CraftingRecipe {
+    /// Name patterns for each slot. Must contain exactly 9 entries, use
+    /// [CraftingRecipe::BLANK] for empty slots.
+    pub shape: Vec<String>,
+}
+
+impl CraftingRecipe {
+    /// The placeholder for an empty slot in the crafting grid.
+    pub const BLANK: &'static str = "BLANK";
+}
+
+/// See `smelt.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmeltingData {
+    pub to_smelt: Vec<SmeltItem>,
+    /// Patterns of item names that can be used as fuel.
+    pub fuels: Vec<String>,
+}
+
+/// An item to smelt. Without a limit, every matching item in the inventory is
+/// smelted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmeltItem {
+    pub name_pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// See `block_search.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockSearchData {
+    pub to_find: BlockGroup,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuel_items: Option<FuelItems>,
+}
+
+/// See `mine_to_level.lua`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MineToLevelData {
+    /// The desired Y level.
+    pub level: i32,
+}
+
+// ==================
+// Shared task types
+// ==================
+// These live in `aliases.lua`.
+
+/// A group of blocks, or a singular block.
+///
+/// Both fields must always be present on the lua side, so these are never
+/// skipped, even when empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockGroup {
+    /// Patterns that will match the names of blocks in the group.
+    pub names_patterns: Vec<String>,
+    /// Tags that blocks in this group may have.
+    pub tags: Vec<String>,
+}
+
+/// Blocks to mine, ordered by preference, paired with how many of each to mine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DesiredBlocks {
+    pub groups: Vec<DesiredGroup>,
+}
+
+/// One entry of [DesiredBlocks].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DesiredGroup {
+    pub group: BlockGroup,
+    pub desired_total: u32,
+    /// Must be zero or unset when handing out a task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mined: Option<u32>,
+}
+
+/// Blocks that may be mined through while looking for desired blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncidentalBlocks {
+    pub groups: Vec<BlockGroup>,
+}
+
+/// Patterns of item names the turtle is allowed to throw away.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscardableItems {
+    pub patterns: Vec<String>,
+}
+
+/// Patterns of item names the turtle is allowed to burn to refuel itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FuelItems {
+    pub patterns: Vec<String>,
+}
+
+// ==================
+// Task results
+// ==================
+
+/// How a task ended. See `TaskCompletion` and `TaskFailure` in
+/// `task_types.lua`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind")]
+pub enum TaskResult {
+    #[serde(rename = "success")]
+    Success(TaskCompletion),
+    #[serde(rename = "fail")]
+    Failure(TaskFailure),
+}
+
+/// The task finished everything it was asked to do.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TaskCompletion {
+    /// Data returned from the task, if any.
+    pub result: TaskResultData,
+}
+
+/// The task did some amount of work, but was unable to finish.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TaskFailure {
+    pub reason: TaskFailureReason,
+    /// Where in the task the failure was thrown from.
+    pub stacktrace: String,
+}
+
+/// Why a task failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum TaskFailureReason {
+    /// The task was handed the config for a different task.
+    #[serde(rename = "bad config")]
+    BadConfig,
+    /// The task was expecting some state, and did not get it.
+    #[serde(rename = "assumptions not met")]
+    AssumptionsNotMet,
+    #[serde(rename = "assertion failed")]
+    AssertionFailed,
+    #[serde(rename = "inventory full")]
+    InventoryFull,
+    #[serde(rename = "out of fuel")]
+    OutOfFuel,
+    /// The turtle was unable to walk back to where the task started.
+    #[serde(rename = "walkback rewind failure")]
+    WalkbackRewindFailure,
+    /// A task spawned by this task failed.
+    #[serde(rename = "sub-task died")]
+    SubTaskDied,
+}
+
+impl Display for TaskFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskFailureReason::BadConfig => write!(f, "bad config"),
+            TaskFailureReason::AssumptionsNotMet => write!(f, "assumptions not met"),
+            TaskFailureReason::AssertionFailed => write!(f, "assertion failed"),
+            TaskFailureReason::InventoryFull => write!(f, "inventory full"),
+            TaskFailureReason::OutOfFuel => write!(f, "out of fuel"),
+            TaskFailureReason::WalkbackRewindFailure => write!(f, "walkback rewind failure"),
+            TaskFailureReason::SubTaskDied => write!(f, "sub-task died"),
+        }
+    }
+}
+
+/// Data returned by a successful task. Tagged by `name` like [TaskData], see
+/// `TaskResultData` in `task_types.lua`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "name")]
+pub enum TaskResultData {
+    #[serde(rename = "recursive_miner_result")]
+    RecursiveMiner(RecursiveMinerResult),
+    #[serde(rename = "branch_miner_result")]
+    BranchMiner(BranchMinerResult),
+    #[serde(rename = "block_search_result")]
+    BlockSearch(BlockSearchResult),
+    /// The `NoneResult`, for tasks that have nothing to report.
+    #[serde(rename = "none")]
+    None,
+}
+
+/// See `recursive_miner.lua`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RecursiveMinerResult {
+    pub mined_blocks: MinedBlocks,
+    /// Total number of blocks mined during the task.
+    pub total_blocks_mined: u32,
+}
+
+/// See `branch_miner.lua`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BranchMinerResult {
+    pub mined_blocks: MinedBlocks,
+    /// Total number of blocks mined during the task, including incidentals.
+    pub total_blocks_mined: u32,
+}
+
+/// How many blocks were mined from each group. Parallel to the groups in the
+/// task config, IE `counts[0]` is the count for the first group.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MinedBlocks {
+    // Empty tables come across as null.
+    pub counts: Option<Vec<u32>>,
+}
+
+/// See `block_search.lua`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockSearchResult {
+    /// Where the block was found, if we found one.
+    #[serde(default)]
+    pub found: Option<CoordinatePosition>,
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Tasks must serialize to the same shape as the lua table literals.
+fn task_definition_matches_lua_shape() {
+    let task = TaskDefinition {
+        return_to_start: true,
+        return_to_facing: true,
+        fuel_buffer: 0,
+        task_data: TaskData::RecursiveMiner(RecursiveMinerData {
+            timeout: None,
+            blocks_mined_limit: None,
+            mineable_groups: vec![BlockGroup {
+                names_patterns: vec!["minecraft:stone".into()],
+                tags: vec![],
+            }],
+            fuel_patterns: Some(vec![]),
+            discardables: None,
+        }),
+    };
+    let expected = serde_json::json!({
+        "return_to_start": true,
+        "return_to_facing": true,
+        "fuel_buffer": 0,
+        "task_data": {
+            "name": "recursive_miner",
+            "mineable_groups": [
+                { "names_patterns": ["minecraft:stone"], "tags": [] }
+            ],
+            "fuel_patterns": [],
+        },
+    });
+    assert_eq!(serde_json::to_value(&task).unwrap(), expected);
+    assert_eq!(
+        serde_json::from_value::<TaskDefinition>(expected).unwrap(),
+        task
+    );
+
+    // Tasks without any config are just the name.
+    let json = serde_json::to_value(TaskData::Mitosis).unwrap();
+    assert_eq!(json, serde_json::json!({ "name": "mitosis_task" }));
+    let json = serde_json::to_value(TaskData::MineToLevel(MineToLevelData { level: -59 })).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "name": "mine_to_level", "level": -59 })
+    );
+}
+
+#[test]
+/// Results should decode from what `finishTask` is handed.
+fn task_results_decode() {
+    let json = serde_json::json!({
+        "kind": "success",
+        "result": {
+            "name": "recursive_miner_result",
+            "mined_blocks": { "counts": [27] },
+            "total_blocks_mined": 27,
+        },
+    });
+    let TaskResult::Success(completion) = serde_json::from_value(json).unwrap() else {
+        panic!("Should be a success!")
+    };
+    let TaskResultData::RecursiveMiner(result) = completion.result else {
+        panic!("Should be a recursive miner result!")
+    };
+    assert_eq!(result.total_blocks_mined, 27);
+    assert_eq!(result.mined_blocks.counts, Some(vec![27]));
+
+    // Missing `found` means nothing was found.
+    let json = serde_json::json!({
+        "kind": "success",
+        "result": { "name": "block_search_result" },
+    });
+    let result: TaskResult = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        result,
+        TaskResult::Success(TaskCompletion {
+            result: TaskResultData::BlockSearch(BlockSearchResult { found: None })
+        })
+    );
+
+    let json = serde_json::json!({
+        "kind": "fail",
+        "reason": "walkback rewind failure",
+        "stacktrace": "stack traceback:",
+    });
+    let TaskResult::Failure(failure) = serde_json::from_value(json).unwrap() else {
+        panic!("Should be a failure!")
+    };
+    assert_eq!(failure.reason, TaskFailureReason::WalkbackRewindFailure);
+    assert_eq!(failure.reason.to_string(), "walkback rewind failure");
+}
@@ -0,0 +1,230 @@
+// Incremental updates to what a turtle has seen of the world.
+//
+// Turtles used to send their entire walkback to keep us up to date, which gets
+// huge fast. Instead they send world deltas: only the blocks they have seen and
+// positions they have visited since their last delta. Every delta is numbered,
+// so if one goes missing we can tell, and ask the turtle for everything it
+// knows again. The blocks in a delta are merged into the world model either
+// way, since every block there keeps track of when it was seen.
+//
+// See `walkback:takeWorldDelta()` in `walkback.lua` for the other side.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::minecraft::{types::CoordinatePosition, vanilla::block_type::PositionedMinecraftBlock};
+
+/// Everything a turtle saw since its last delta.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "WorldDeltaInner")]
+pub struct WorldDelta {
+    /// Counts up by one every delta. Starts back at zero when the turtle
+    /// reboots.
+    pub sequence: u64,
+    /// Wether this is everything the turtle knows, instead of just what
+    /// changed. Turtles send one of these when they boot, and when we ask for a
+    /// resync.
+    pub full: bool,
+    /// Blocks the turtle has seen, newest observation only.
+    pub blocks: HashMap<CoordinatePosition, PositionedMinecraftBlock>,
+    /// Positions the turtle has been in.
+    pub positions: HashSet<CoordinatePosition>,
+}
+
+/// The delta as it comes off the wire.
+#[derive(Deserialize)]
+struct WorldDeltaInner {
+    sequence: u64,
+    full: bool,
+    // Empty tables come across as null.
+    blocks: Option<HashMap<CoordinatePosition, PositionedMinecraftBlock>>,
+    // Sets of positions on the lua side, thus the bools are always true.
+    positions: Option<HashMap<CoordinatePosition, bool>>,
+}
+
+impl From<WorldDeltaInner> for WorldDelta {
+    fn from(inner: WorldDeltaInner) -> Self {
+        Self {
+            sequence: inner.sequence,
+            full: inner.full,
+            blocks: inner.blocks.unwrap_or_default(),
+            positions: inner.positions.unwrap_or_default().into_keys().collect(),
+        }
+    }
+}
+
+/// What happened when a delta was applied. See [DeltaStream::apply].
+///
+/// This is only about where the stream is at. The blocks in the delta go into
+/// the world model no matter the outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaOutcome {
+    /// The delta was the next one, the stream moved past it.
+    Applied,
+    /// We already applied this delta, the stream stayed put.
+    Duplicate,
+    /// Some deltas went missing before this one, or the turtle started counting
+    /// over without sending everything first. The stream stayed put, and a
+    /// resync should be requested from the turtle.
+    Gap {
+        /// The sequence number we were waiting on.
+        expected: u64,
+        /// The sequence number we got instead.
+        got: u64,
+    },
+    /// There was already a gap, and we are still waiting on the turtle to send
+    /// everything. The stream stayed put, and there is no need to ask again.
+    AwaitingResync,
+}
+
+/// Where a turtle's stream of [WorldDelta]s is at. The blocks in them go
+/// straight into the world model, see `world/mod.rs`, so this is all we keep
+/// per turtle.
+///
+/// Starts over every time the turtle says hello, since it may have rebooted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeltaStream {
+    /// The sequence number of the next delta we expect.
+    next_sequence: u64,
+    /// Set after a gap, until a full delta comes in.
+    awaiting_resync: bool,
+}
+
+impl DeltaStream {
+    /// Apply a delta from the turtle.
+    ///
+    /// Full deltas are always applied, and pick the sequence back up from
+    /// there. Only the last delta we applied counts as a duplicate, anything
+    /// older means the turtle started counting over and we missed its full
+    /// delta.
+    pub fn apply(&mut self, delta: &WorldDelta) -> DeltaOutcome {
+        if !delta.full {
+            if delta.sequence + 1 == self.next_sequence {
+                return DeltaOutcome::Duplicate;
+            }
+            if self.awaiting_resync {
+                return DeltaOutcome::AwaitingResync;
+            }
+            if delta.sequence != self.next_sequence {
+                self.awaiting_resync = true;
+                return DeltaOutcome::Gap {
+                    expected: self.next_sequence,
+                    got: delta.sequence,
+                };
+            }
+        }
+
+        self.next_sequence = delta.sequence + 1;
+        self.awaiting_resync = false;
+        DeltaOutcome::Applied
+    }
+
+    /// The sequence number of the next delta we expect.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Wether we are waiting on the turtle to send everything again.
+    pub fn awaiting_resync(&self) -> bool {
+        self.awaiting_resync
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+/// A delta with only positions in it, at `x:n|y:0|z:0` for each n.
+fn positions_delta(sequence: u64, full: bool, xs: &[i64]) -> WorldDelta {
+    let positions: HashMap<String, bool> = xs
+        .iter()
+        .map(|x| (format!("x:{x}|y:0|z:0"), true))
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "sequence": sequence,
+        "full": full,
+        "blocks": null,
+        "positions": positions,
+    }))
+    .unwrap()
+}
+
+#[test]
+/// Deltas should apply in order, and gaps should be caught and recovered from.
+fn applying_world_deltas() {
+    let mut stream = DeltaStream::default();
+    assert_eq!(
+        stream.apply(&positions_delta(0, true, &[0])),
+        DeltaOutcome::Applied
+    );
+    assert_eq!(
+        stream.apply(&positions_delta(1, false, &[1])),
+        DeltaOutcome::Applied
+    );
+    assert_eq!(
+        stream.apply(&positions_delta(1, false, &[1])),
+        DeltaOutcome::Duplicate
+    );
+    assert_eq!(stream.next_sequence(), 2);
+
+    // Delta 2 went missing.
+    assert_eq!(
+        stream.apply(&positions_delta(3, false, &[3])),
+        DeltaOutcome::Gap {
+            expected: 2,
+            got: 3
+        }
+    );
+    assert!(stream.awaiting_resync());
+    assert_eq!(
+        stream.apply(&positions_delta(4, false, &[4])),
+        DeltaOutcome::AwaitingResync
+    );
+    assert_eq!(stream.next_sequence(), 2);
+
+    // The turtle reset its walkback in the meantime.
+    assert_eq!(
+        stream.apply(&positions_delta(5, true, &[2, 3, 4])),
+        DeltaOutcome::Applied
+    );
+    assert!(!stream.awaiting_resync());
+    assert_eq!(stream.next_sequence(), 6);
+    assert_eq!(
+        stream.apply(&positions_delta(6, false, &[])),
+        DeltaOutcome::Applied
+    );
+
+    // Rebooted turtles start over from zero.
+    assert_eq!(
+        stream.apply(&positions_delta(0, true, &[])),
+        DeltaOutcome::Applied
+    );
+    assert_eq!(
+        stream.apply(&positions_delta(1, false, &[7])),
+        DeltaOutcome::Applied
+    );
+    assert_eq!(stream.next_sequence(), 2);
+
+    // Rebooted again, but its full delta never made it.
+    assert_eq!(
+        stream.apply(&positions_delta(0, false, &[])),
+        DeltaOutcome::Gap {
+            expected: 2,
+            got: 0
+        }
+    );
+    assert!(stream.awaiting_resync());
+    assert_eq!(stream.next_sequence(), 2);
+
+    // A server that never saw the start of the stream asks for everything.
+    let mut fresh = DeltaStream::default();
+    assert_eq!(
+        fresh.apply(&positions_delta(12, false, &[])),
+        DeltaOutcome::Gap {
+            expected: 0,
+            got: 12
+        }
+    );
+}
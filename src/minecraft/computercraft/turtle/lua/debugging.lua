@@ -6,12 +6,21 @@ local constants = require("constants")
 local debugging = {}
 
 --- Step-locked syncing with the test harness.
-function debugging.waitStep()
-    NETWORKING.debugSend("wait")
+---
+--- Naming the step lets the harness check that we stopped where it expected
+--- us to, see `StepBarrier` in `test_websocket.rs`. Steps without a name match
+--- whatever the harness is waiting for.
+---@param name string|nil
+function debugging.waitStep(name)
+    if name then
+        NETWORKING.debugSend({ wait = name })
+    else
+        NETWORKING.debugSend("wait")
+    end
     if NETWORKING.waitForPacket(constants.WAIT_STEP_TIMEOUT) then
         return
     end
-    NETWORKING.debugSend("fail, no response from harness.")
+    NETWORKING.debugSend("fail, no response from harness at step " .. (name or "<unnamed>") .. ".")
     ---@diagnostic disable-next-line: undefined-field
     os.setComputerLabel("step failure")
 end
@@ -133,18 +133,6 @@ end
 -- === === ===
 -- === === ===
 
---- Cast a table into a string, no matter the internal data. Will NOT recurse into
---- sub tables. Meant for debugging only, as the ordering here is not stable.
----@param table table
----@return string
-local function forceStringTable(table)
-    local final_string = "Data of table (" .. tostring(table) .. "): "
-    for key, value in pairs(table) do
-        final_string = final_string .. "[key: (" .. tostring(key) .. "), value: (" .. tostring(value) .. ")]"
-    end
-    return final_string
-end
-
 -- Table types for linting to prevent returning the wrong things.
 
 --- @class SeenCleaned table
@@ -230,7 +218,8 @@ local function cleanForJSON(value, seen, seen_cleaned, skip_invalid_tables)
         -- If we end up here, that means we've hit a circular dependency. We need
         -- to break the cycle. Currently cant think of a clean way to resolve
         -- this situation so we just explode. Don't make self referencing tables!
-        local printable_version = forceStringTable(value)
+        ---@diagnostic disable-next-line: undefined-global
+        local printable_version = textutils.serializeJSON(helpers.packTable(value))
         panic.panic("Self referential table! " .. printable_version)
     end
 
@@ -272,7 +261,8 @@ local function cleanForJSON(value, seen, seen_cleaned, skip_invalid_tables)
             return xkcd_idk, seen_cleaned
         end
         -- I want to at least see what was in the table still
-        local printable_version = forceStringTable(value)
+        ---@diagnostic disable-next-line: undefined-global
+        local printable_version = textutils.serializeJSON(helpers.packTable(value))
         panic.panic("Mixed table! Not allowed! table: " .. printable_version)
     end
 
@@ -393,6 +383,57 @@ function helpers.deserializeJSON(json)
     return true, result_or_error
 end
 
+--- Pack a value into the `{pairs = {{key, value}, ...}}` format the control
+--- server uses for tables (See `LuaPackedTable`), since json can't key things
+--- by anything but strings, or mix arrays with maps. Things json can't hold at
+--- all are turned into strings.
+---
+--- Unlike `helpers.serializeJSON`, this never panics, so it is safe to call
+--- while panicking.
+---@param value any
+---@param seen table? Tables we are currently inside of, to not loop forever.
+---@return any
+function helpers.packTable(value, seen)
+    seen = seen or {}
+    local value_type = type(value)
+    if value_type == "string" or value_type == "number" or value_type == "boolean" then
+        return value
+    end
+    if value_type ~= "table" then
+        -- functions, threads, userdata.
+        return tostring(value)
+    end
+    if seen[value] then
+        return "recursive " .. tostring(value)
+    end
+    seen[value] = true
+    local packed = {}
+    for k, v in pairs(value) do
+        packed[#packed + 1] = { key = helpers.packTable(k, seen), value = helpers.packTable(v, seen) }
+    end
+    seen[value] = nil
+    return { pairs = packed }
+end
+
+--- Undo `helpers.packTable`, IE on data the control server packed with
+--- `to_lua_value`. Anything that is not a packed table is returned as is.
+---@param value any
+---@return any
+function helpers.unpackTable(value)
+    if type(value) ~= "table" or value.pairs == nil then
+        return value
+    end
+    local unpacked = {}
+    for _, pair in ipairs(value.pairs) do
+        local key = helpers.unpackTable(pair.key)
+        -- nil keys are not allowed, and nil values don't need setting.
+        if key ~= nil then
+            unpacked[key] = helpers.unpackTable(pair.value)
+        end
+    end
+    return unpacked
+end
+
 -- === === ===
 -- === === ===
 -- Generic table helpers
@@ -111,6 +111,17 @@ local max_event_timer_resolution = 10
 ---@type number
 local current_event_timer_resolution = default_event_timer_resolution
 
+--- How often to send world deltas to the control server, at most. Sending one
+--- for every single step would flood the server.
+---
+--- Expressed as real milliseconds.
+--- @type number
+local world_delta_interval_ms = 1000
+
+--- When we last sent a world delta, from `os.epoch("utc")`.
+--- @type number
+local last_world_delta_time = 0
+
 -- =========
 -- Types
 -- =========
@@ -433,7 +444,8 @@ end
 --- - TODO: More post task completion checks.
 ---
 --- Once we know it is safe to finish the task, we'll restore the walkback that
---- was popped off when the task was started.
+--- was popped off when the task was started, and let the control server know
+--- how the task went.
 --- TODO: This is also where we would return block information to the rust server.
 --- @param task TurtleTask
 --- @param result TaskCompletion|TaskFailure
@@ -488,6 +500,12 @@ local function finishTask(task, result)
     -- We actually do all of the cleanup before we even care if the task passed
     -- or failed, since it needs to happen regardless.
 
+    -- Sub-task results go to their parent task, not the control server.
+    -- Tests pull results out of the OS directly, so we don't send those either.
+    if not task.is_sub_task and not test_mode then
+        NETWORKING.taskResultSend(task.definition.task_data.name, result)
+    end
+
     -- Was this a pass or a fail?
     if result.kind == "success" then
         -- This is easy!
@@ -583,6 +601,62 @@ local function handleTimerEvent(timer_id)
     -- panic.panic("Unknown timer!")
 end
 
+-- =========
+-- Server packets
+-- =========
+
+--- Answers for each kind of "request" the control server can make. Each one
+--- returns the response table, minus the `kind`, which is filled in for you.
+---
+--- Keep these in sync with `TurtleRequest` on the rust side.
+---@type table<string, fun(): table>
+local request_handlers = {
+    fuel = function()
+        ---@diagnostic disable-next-line: undefined-global
+        local level = turtle.getFuelLevel()
+        -- Fuel is turned off, leave both fields out.
+        if level == "unlimited" then
+            return {}
+        end
+        ---@diagnostic disable-next-line: undefined-global
+        return { level = level, limit = turtle.getFuelLimit() }
+    end,
+    position = function()
+        return { position = walkback.cur_position }
+    end,
+    inventory = function()
+        return { inventory = walkback:inventoryJSON() }
+    end,
+    walkback = function()
+        return { walkback = walkback:dataJson() }
+    end,
+}
+
+--- Handle a packet from the control server that came in while the OS was
+--- pulling events.
+--- @param packet server_packet
+local function handleServerPacket(packet)
+    if packet.packet_type == "request" then
+        local id = packet.data.id
+        local kind = packet.data.request.kind
+        local handler = request_handlers[kind]
+        if handler == nil then
+            NETWORKING.respond(id, nil, "Unknown request kind: " .. tostring(kind))
+            return
+        end
+        local response = handler()
+        response.kind = kind
+        NETWORKING.respond(id, response)
+        return
+    end
+    if packet.packet_type == "resync_world" then
+        -- Goes out with the next world delta.
+        walkback:requestWorldResync()
+        return
+    end
+    -- TODO: Handle the rest of the server packets.
+end
+
 -- =========
 -- Event handling
 -- =========
@@ -652,15 +726,28 @@ local function handleEvents()
         handleTimerEvent(timer_id)
 
     elseif event_name == "turtle_inventory" then
-        -- TODO: Do something with this event
+        -- Keep the control server up to date on what we are holding. Tests
+        -- check inventories themselves, so these would just get in the way.
+        if not test_mode then
+            NETWORKING.inventorySend(walkback:inventoryJSON())
+        end
     elseif event_name == "websocket_closed" then
-        -- TODO: Do something with this event
+        -- The url is the 2nd item. We only have the one websocket, but check
+        -- anyways.
+        if event[2] == NETWORKING.SERVER_URL then
+            print("Lost connection to the server, reconnecting...")
+            NETWORKING.reconnect()
+        end
     elseif event_name == "task_complete" then
         -- When a coroutine finishes, it throws this. Tasks that start sub-tasks
         -- end up requesting these, so we have to put these back.
         queueEvent(table.unpack(event))
     elseif event_name == "websocket_message" then
-        -- TODO: Do something with this event
+        -- The message is the 3rd item, after the url.
+        local packet = NETWORKING.handleMessage(event[3])
+        if packet ~= nil then
+            handleServerPacket(packet)
+        end
     else
         -- This is an event we do not care (or know) about
         -- Thus we do nothing.
@@ -971,6 +1058,20 @@ function mesh_os.main()
         -- while it is eeping ever so peacefully.
         ::skip_task::
 
+        -- The server may have gone quiet without the socket closing.
+        if not NETWORKING.isHealthy() then
+            print("Server stopped pinging us, reconnecting...")
+            NETWORKING.reconnect()
+        end
+
+        -- Keep the control server up to date on what we have seen. Tests
+        -- check the world themselves, same as inventories.
+        if not test_mode and walkback:hasWorldDelta()
+            and os.epoch("utc") - last_world_delta_time >= world_delta_interval_ms then
+            NETWORKING.worldDeltaSend(walkback:takeWorldDelta())
+            last_world_delta_time = os.epoch("utc")
+        end
+
         -- Handle events
         handleEvents()
 
@@ -3,7 +3,7 @@
 use log::info;
 use rand::Rng;
 
-use crate::{minecraft::computercraft::computer_types::packet_types::{DebuggingPacket, RawTurtlePacket}, tests::prelude::*};
+use crate::{minecraft::computercraft::computer_types::packet_types::DebuggingPacket, tests::prelude::*};
 
 /// Spawns a tree_chop task test. This is it's own function to allow you to start
 /// hella tree tests at once for fun!
@@ -29,7 +29,7 @@ async fn run_tree_chop(
     }
 
     -- Sync yield
-    debugging.waitStep()
+    debugging.waitStep("booted")
 
     -- Equip the axe
     turtle.equipRight()
@@ -38,7 +38,7 @@ async fn run_tree_chop(
     turtle.back()
 
     -- Yield to let the test know we are ready to start
-    debugging.waitStep()
+    debugging.waitStep("equipped axe")
 
     -- setup the OS
     mesh_os.startup(start_position)
@@ -66,7 +66,7 @@ async fn run_tree_chop(
     local _, _ = pcall(mesh_os.main)
 
     -- Tell the os we are done.
-    debugging.waitStep()
+    debugging.waitStep("chopped tree")
     "#;
 
     let libraries = MeshpitLibraries {
@@ -95,12 +95,14 @@ async fn run_tree_chop(
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
-    socket.receive(5).await.expect("Should receive");
-    socket.send(str.clone(), 5).await.expect("Should send");
+    let mut steps = socket.steps();
+    steps.pass("booted", 5).await.expect("Should boot");
 
     // Wait for the turtle to move backwards
-    socket.receive(15).await.expect("Should receive");
+    steps
+        .await_step("equipped axe", 15)
+        .await
+        .expect("Should equip the axe");
 
     // Place the tree
     let t_p = position.with_offset(test.corner());
@@ -109,10 +111,13 @@ async fn run_tree_chop(
     test.command(TestCommand::RawCommand(&format!("/place feature minecraft:fancy_oak {} {} {}", t_p.position.x, t_p.position.y, t_p.position.z))).await;
 
     // Ready for the turtle to do the tree.
-    socket.send(str.clone(), 5).await.expect("Should send");
+    steps.release().await.expect("Should release");
 
     // This may take a while.
-    socket.receive(840).await.expect("Should receive");
+    steps
+        .await_step("chopped tree", 840)
+        .await
+        .expect("Should chop the tree");
 
     // Turtle is done.
     // Did it end up back at the start?
@@ -231,7 +236,7 @@ async fn recursive_miner_test() {
     }
 
     -- Sync yield
-    debugging.waitStep()
+    debugging.waitStep("booted")
 
     -- Equip the pickaxe
     turtle.equipRight()
@@ -240,32 +245,17 @@ async fn recursive_miner_test() {
     turtle.back()
     turtle.back()
 
-    -- Yield to let the test know we are ready to start
-    debugging.waitStep()
+    -- Let the test know we are ready to start, then wait for it to send us
+    -- the task.
+    NETWORKING.debugSend({ wait = "ready for task" })
+    local _, task_packet = NETWORKING.waitForPacket(60)
 
     -- setup the OS
     mesh_os.startup(start_position)
 
-    ---@type TaskDefinition
-    local miner_task = {
-        fuel_buffer = 0,
-        return_to_facing = true,
-        return_to_start = true,
-        ---@type RecursiveMinerData
-        task_data = {
-            name = "recursive_miner",
-            timeout = nil,
-            mineable_groups = {
-                {
-                    names_patterns = {"minecraft:stone"},
-                    tags = {}
-                }
-            },
-            fuel_patterns = {},
-        },
-    }
-
     -- Add the task to the queue
+    ---@type TaskDefinition
+    local miner_task = task_packet.data
     mesh_os.testAddTask(miner_task)
 
     -- Run the task. This will return the result of the task
@@ -309,12 +299,14 @@ async fn recursive_miner_test() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
-    socket.receive(5).await.expect("Should receive");
-    socket.send(str.clone(), 5).await.expect("Should send");
+    socket.steps().pass("booted", 5).await.expect("Should boot");
 
     // Wait for the turtle to move back twice
-    socket.receive(15).await.expect("Should receive");
+    socket
+        .steps()
+        .await_step("ready for task", 15)
+        .await
+        .expect("Should get ready for the task");
 
 
 
@@ -322,23 +314,34 @@ async fn recursive_miner_test() {
     let p1 = CoordinatePosition { x: 1, y: 1, z: 1 };
     let p2 = CoordinatePosition { x: 3, y: 3, z: 3 };
     let stone = MinecraftBlock::from_string("minecraft:stone").unwrap();
-    assert!(test.command(TestCommand::Fill(p1, p2, &stone)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(p1, p2), &stone)).await.success());
 
     // Son, im mine 😭
-    socket.send(str.clone(), 5).await.expect("Should send");
+    let miner_task = TaskDefinition {
+        return_to_start: true,
+        return_to_facing: true,
+        fuel_buffer: 0,
+        task_data: TaskData::RecursiveMiner(RecursiveMinerData {
+            timeout: None,
+            blocks_mined_limit: None,
+            mineable_groups: vec![BlockGroup {
+                names_patterns: vec!["minecraft:stone".into()],
+                tags: vec![],
+            }],
+            fuel_patterns: Some(vec![]),
+            discardables: None,
+        }),
+    };
+    socket.send(ServerMessage::AssignTask(miner_task), 5).await.expect("Should send");
 
     // Wait for the mining to finish.
-    let turtle_json = socket.receive(300).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(300).await.expect("Should receive");
     // This should just be a number
     let blocks_mined = debug_packet.inner_data.as_u64().unwrap();
     info!("Turtle claims to have mined {blocks_mined} blocks.");
 
     // Now get the count of the first group. It should be equal.
-    let turtle_json = socket.receive(5).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(5).await.expect("Should receive");
     let group_count = debug_packet.inner_data.as_u64().unwrap();
     info!("The group claims {group_count} blocks mined.");
 
@@ -393,13 +396,13 @@ async fn branch_miner_test() {
     }
 
     -- Sync yield
-    debugging.waitStep()
+    debugging.waitStep("booted")
 
     -- Equip the pickaxe
     turtle.equipRight()
 
     -- Yield to let the test know we are ready to start
-    debugging.waitStep()
+    debugging.waitStep("equipped pickaxe")
 
     -- setup the OS
     mesh_os.startup(start_position)
@@ -482,12 +485,14 @@ async fn branch_miner_test() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
-    socket.receive(5).await.expect("Should receive");
-    socket.send(str.clone(), 5).await.expect("Should send");
+    socket.steps().pass("booted", 5).await.expect("Should boot");
 
-    // Wait for the turtle to move back twice
-    socket.receive(15).await.expect("Should receive");
+    // Wait for the turtle to equip its pickaxe
+    socket
+        .steps()
+        .await_step("equipped pickaxe", 15)
+        .await
+        .expect("Should equip the pickaxe");
 
 
 
@@ -495,7 +500,7 @@ async fn branch_miner_test() {
     let p1 = CoordinatePosition { x: 1, y: 1, z: 1 };
     let p2 = CoordinatePosition { x: 17, y: 1, z: 17 };
     let stone = MinecraftBlock::from_string("minecraft:stone").unwrap();
-    assert!(test.command(TestCommand::Fill(p1, p2, &stone)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(p1, p2), &stone)).await.success());
 
     // Then disperse some ores in it
     // Minecraft has a command for this:
@@ -518,21 +523,17 @@ async fn branch_miner_test() {
     };
 
     // We can now start mining.
-    socket.send(str.clone(), 5).await.expect("Should send");
+    socket.steps().release().await.expect("Should release");
 
     // Wait for the mining to finish.
-    let turtle_json = socket.receive(300).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(300).await.expect("Should receive");
     // This should just be a number of ores mined.
     // This will panic if its -100, which is intended.
     let blocks_mined = debug_packet.inner_data.as_u64().unwrap();
     info!("Turtle claims to have mined {blocks_mined} total blocks.");
 
     // Now get the count of the first group. It should be equal.
-    let turtle_json = socket.receive(5).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(5).await.expect("Should receive");
     let copper_count = debug_packet.inner_data.as_u64().unwrap();
     info!("The turtle claims to have mined {copper_count} copper ore.");
 
@@ -736,7 +737,7 @@ async fn lectern_from_scratch_test() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     socket.receive(5).await.expect("Should receive");
     socket.send(go.clone(), 5).await.expect("Should send");
@@ -867,7 +868,7 @@ async fn crafting_test() {
 
     computer.turn_on(&mut test).await;
 
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
 
     // Initial handshake
     socket.receive(5).await.expect("Should receive");
@@ -998,7 +999,7 @@ async fn smelting_test() {
 
     computer.turn_on(&mut test).await;
 
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
 
     // Initial handshake
     socket.receive(5).await.expect("Should receive");
@@ -1008,9 +1009,7 @@ async fn smelting_test() {
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
-    let turtle_json = socket.receive(300).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(300).await.expect("Should receive");
     let ingots = debug_packet.inner_data.as_u64().unwrap();
     info!("Turtle claims to have smelt {ingots} iron ingots.");
 
@@ -1038,19 +1037,19 @@ async fn block_search_task_test() {
     let c_p1 = CoordinatePosition { x: 0, y: 1, z: 0 };
     let c_p2 = CoordinatePosition { x: 12, y: 3, z: 12 };
     let barrier = MinecraftBlock::from_string("barrier").unwrap();
-    assert!(test.command(TestCommand::Fill(c_p1, c_p2, &barrier)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(c_p1, c_p2), &barrier)).await.success());
 
     // Hollow it out
     let h_p1 = CoordinatePosition { x: 1, y: 1, z: 1 };
     let h_p2 = CoordinatePosition { x: 11, y: 2, z: 11 };
     let air = MinecraftBlock::from_string("air").unwrap();
-    assert!(test.command(TestCommand::Fill(h_p1, h_p2, &air)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(h_p1, h_p2), &air)).await.success());
 
     // Add some sand
     let s_p1 = CoordinatePosition { x: 9, y: 1, z: 9 };
     let s_p2 = CoordinatePosition { x: 11, y: 2, z: 11 };
     let sand = MinecraftBlock::from_string("minecraft:sand").unwrap();
-    assert!(test.command(TestCommand::Fill(s_p1, s_p2, &sand)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(s_p1, s_p2), &sand)).await.success());
 
     // Turt
     let position = MinecraftPosition {
@@ -1119,16 +1118,14 @@ async fn block_search_task_test() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     // Sync
     socket.receive(5).await.expect("Should receive");
     socket.send(go.clone(), 5).await.expect("Should send");
 
     // Wait for the search.
-    let turtle_json = socket.receive(300).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(300).await.expect("Should receive");
     let found_block = debug_packet.inner_data.as_bool().unwrap();
     info!("Turtle found sand: {found_block}");
 
@@ -1159,12 +1156,12 @@ async fn block_search_no_fly_test() {
     // Barriers
     let c_p1 = CoordinatePosition { x: 0, y: 1, z: 0 };
     let c_p2 = CoordinatePosition { x: 4, y: 5, z: 4 };
-    assert!(test.command(TestCommand::Fill(c_p1, c_p2, &barrier)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(c_p1, c_p2), &barrier)).await.success());
 
     // Hollow it out
     let h_p1 = CoordinatePosition { x: 1, y: 1, z: 1 };
     let h_p2 = CoordinatePosition { x: 3, y: 4, z: 3 };
-    assert!(test.command(TestCommand::Fill(h_p1, h_p2, &air)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(h_p1, h_p2), &air)).await.success());
 
     // Stone block in the way that needs to be avoided
     let stone_pos = CoordinatePosition { x: 2, y: 1, z: 2 };
@@ -1250,7 +1247,7 @@ async fn block_search_no_fly_test() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     // Sync
     socket.receive(5).await.expect("Should receive");
@@ -1258,9 +1255,7 @@ async fn block_search_no_fly_test() {
 
     // Wait for turtle to finish search'
     // Should be real fast
-    let turtle_json = socket.receive(10).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(10).await.expect("Should receive");
     let found_block = debug_packet.inner_data.as_bool().unwrap();
     info!("Turtle found sand: {found_block}");
 
@@ -1368,15 +1363,13 @@ async fn block_search_back_and_forth_test() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     socket.receive(5).await.expect("Should receive");
     socket.send(go.clone(), 5).await.expect("Should send");
 
     // Should find it fast
-    let turtle_json = socket.receive(30).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(30).await.expect("Should receive");
     let found_block = debug_packet.inner_data.as_bool().unwrap();
     info!("Turtle found sand: {found_block}");
 
@@ -1489,15 +1482,13 @@ async fn block_search_4_step_loop() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     socket.receive(5).await.expect("Should receive");
     socket.send(go.clone(), 5).await.expect("Should send");
 
     // Should find it fast
-    let turtle_json = socket.receive(30).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(30).await.expect("Should receive");
     let found_block = debug_packet.inner_data.as_bool().unwrap();
     info!("Turtle found sand: {found_block}");
 
@@ -1540,7 +1531,7 @@ async fn block_search_overhang() {
     // longer ceiling with a required downwards movement at the end
     let p1 = CoordinatePosition { x: 2, y: 3, z: 2 };
     let p2 = CoordinatePosition { x: 2, y: 3, z: 9 };
-    assert!(test.command(TestCommand::Fill(p1, p2, &stone)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(p1, p2), &stone)).await.success());
     assert!(test.command(TestCommand::SetBlock(MinecraftPosition {position:CoordinatePosition { x: 2, y: 2, z: 9 }, facing: None}, &stone)).await.success());
 
     // Its okay that we have to move forwards for the sand again
@@ -1611,15 +1602,13 @@ async fn block_search_overhang() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     socket.receive(5).await.expect("Should receive");
     socket.send(go.clone(), 5).await.expect("Should send");
 
     // Should find it fast
-    let turtle_json = socket.receive(120).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(120).await.expect("Should receive");
     let found_block = debug_packet.inner_data.as_bool().unwrap();
     info!("Turtle found sand: {found_block}");
 
@@ -1742,7 +1731,7 @@ async fn recursive_miner_falling_test() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
@@ -1755,21 +1744,19 @@ async fn recursive_miner_falling_test() {
     let p1 = CoordinatePosition { x: 1, y: 1, z: 1 };
     let p2 = CoordinatePosition { x: 3, y: 10, z: 3 };
     let sand = MinecraftBlock::from_string("minecraft:sand").unwrap();
-    assert!(test.command(TestCommand::Fill(p1, p2, &sand)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(p1, p2), &sand)).await.success());
 
     // Replace the bottom row with stone that we want to mine
     let p3 = CoordinatePosition { x: 3, y: 1, z: 3 };
     let stone = MinecraftBlock::from_string("minecraft:stone").unwrap();
-    assert!(test.command(TestCommand::Fill(p1, p3, &stone)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(p1, p3), &stone)).await.success());
 
     // Son, im mine 😭
     socket.send(str.clone(), 5).await.expect("Should send");
 
     // Wait for the mining to finish.
     // We should just see the 9 stone in the total.
-    let turtle_json = socket.receive(300).await.expect("Should receive");
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&turtle_json).unwrap();
-    let debug_packet: DebuggingPacket = raw_packet.try_into().unwrap();
+    let debug_packet: DebuggingPacket = socket.receive_packet(300).await.expect("Should receive");
     // This should just be a number
     let blocks_mined = debug_packet.inner_data.as_u64().unwrap();
     info!("Turtle claims to have mined {blocks_mined} blocks.");
@@ -1893,7 +1880,7 @@ async fn mitosis_basic() {
 
     computer.turn_on(&mut test).await;
 
-    let go = String::from("go");
+    let go = ServerMessage::DebugReply("go".into());
 
     socket.receive(10).await.expect("Should receive");
     socket.send(go.clone(), 10).await.expect("Should send");
@@ -20,13 +20,55 @@ NETWORKING = {}
 
 -- All compuers communicate over the same websocket, only differentiated by their computer ID
 -- via sending it in the initial handshake.
--- TODO: This is currently pinned to localhost. Should we do this another way?
-NETWORKING.SERVER_URL = "ws://localhost:4816/meshpit"
+-- Defaults to localhost, which is where the test server lives. Point a computer at a
+-- different server with `set meshpit.server_url ws://host:port/meshpit`.
+settings.define("meshpit.server_url", {
+    description = "The websocket to connect to the meshpit server on.",
+    default = "ws://localhost:4816/meshpit",
+    type = "string",
+})
+NETWORKING.SERVER_URL = settings.get("meshpit.server_url")
 NETWORKING.websocket = nil
 NETWORKING.HEADERS = {
     ["Computer-ID"] = tostring(os.getComputerID())
 }
 
+--- The version of the packet format we speak. Sent in our hello packet.
+---
+--- Must match `PROTOCOL_VERSION` on the rust side.
+NETWORKING.PROTOCOL_VERSION = 1
+
+--- The encodings we can send our large packets in. Sent in our hello packet,
+--- and the server picks one.
+local OFFERED_ENCODINGS = { "compact", "json" }
+
+--- The file our token lives in, in the root of the computer. See `auth.rs` on
+--- the rust side.
+NETWORKING.TOKEN_FILE = "turtle_token"
+
+--- Wether we proved who we are to the server during our hello. Servers that do
+--- not check (like the test harness) never ask.
+NETWORKING.authenticated = false
+
+--- Tokens the server sent us for turtles we are building, keyed by computer ID.
+--- False if the server would not give us one.
+---@type table<number, string|false>
+NETWORKING.provisioned_tokens = {}
+
+--- The encoding the server picked for our large packets. Everything is json
+--- until we hear otherwise.
+---@type "json"|"compact"
+NETWORKING.encoding = "json"
+
+--- When the server last pinged us, in milliseconds since the epoch. Nil until
+--- the first ping, since not every server pings us (the test harness does not).
+---@type number|nil
+NETWORKING.last_ping = nil
+
+--- How long the server can go without pinging us before we give up on the
+--- connection. The server pings every 10 seconds by default.
+local PING_TIMEOUT_MS = 60 * 1000
+
 --- To prevent issues when multiple messages are sent in the same second, each packet
 --- gets a UUID to differentiate it. Do note that re-transmitting a packet on failure
 --- should continue to use the same UUID, just in case the control computer got it.
@@ -87,16 +129,79 @@ print("Done!")
 ---| "walkback" A popped walkback.
 ---| "unknown" Format is not specified.
 ---| "debugging" Self explanatory
+---| "task_result" A task left the queue. Data is {task_name: string, result: TaskCompletion|TaskFailure}.
+---| "ack" We got one of the server's packets. Data is {uuid: string}.
+---| "response" The answer to a "request". Data is {id: string, response: table|nil, error: string|nil}.
+---| "hello" The first packet we send after connecting. Data is {protocol_version: number, libraries: {[string]: number}, hello_world: HelloWorld|nil, encodings: string[]}.
+---| "pong" The answer to a "ping". Data is {uuid: string}, the UUID of the ping.
+---| "inventory" Everything we are holding. Data is the output of `walkback:inventoryJSON()`.
+---| "world_delta" What we have seen since the last delta. Data is the output of `walkback:takeWorldDelta()`.
+---| "auth" The answer to a "challenge". Data is {proof: string}, or nil if we have no token.
+---| "provision" Ask for a token for a turtle we are building. Data is {computer_id: number}.
 
 --- The packet format used to communicate outwards and inwards from the turtle.
 ---@class packet
 ---@field id number The computer's ID
 ---@field uuid string A unique identifier for this packet
----@field timestamp number Seconds since unix epoch
+---@field timestamp number Milliseconds since unix epoch
 ---@field packet_type PacketType The kind of packet this is.
 ---@field data any The inner data contained within this table.
 
 
+--- The different kinds of packets the control server sends to turtles.
+---@alias ServerPacketType
+---| "assign_task" Add a task to the queue. Data is a TaskDefinition.
+---| "cancel_task" Stop working on the current task. Has no data.
+---| "ack" The server got one of our packets. Data is {uuid: string}.
+---| "ping" Are we still alive? Has no data. Answered with a "pong".
+---| "request" The server wants to know something. Data is {id: string, request: {kind: string}}. Answer with `respond`.
+---| "resync_world" The server missed some of our world deltas, send everything. Has no data.
+---| "debug_reply" Reply to a debugSend. Only used in testing.
+---| "challenge" Prove we are who we say we are. Data is {nonce: string}. Answered with an "auth".
+---| "provisioned" The answer to a "provision". Data is {computer_id: number, token: string|nil}.
+---| "hello_reply" What the server thinks of our hello. Data is {status: "accepted"|"outdated"|"rejected", stale_libraries: string[]|nil, reason: string|nil, encoding: "json"|"compact"}.
+
+--- The packet format the control server sends to the turtle. This is the same
+--- as `packet`, minus the ID, since we already know who we are.
+---@class server_packet
+---@field uuid string A unique identifier for this packet
+---@field timestamp number Milliseconds since unix epoch
+---@field packet_type ServerPacketType The kind of packet this is.
+---@field data any|nil The inner data, not all packet types have data.
+
+--- Server packet types that we must acknowledge. The server keeps re-sending
+--- these until it gets an ack.
+---
+--- Keep this in sync with `ServerMessage::needs_ack` on the rust side.
+local ACKED_PACKET_TYPES = {
+    assign_task = true,
+    cancel_task = true,
+    provisioned = true,
+    resync_world = true,
+}
+
+--- The UUIDs of the most recent acked server packets, so we can skip any
+--- re-sends of packets we already handled.
+---@type string[]
+local recent_server_uuids = {}
+local RECENT_SERVER_UUID_LIMIT = 32
+
+--- Remember a server packet UUID. Returns false if we have already seen it.
+---@param UUID string
+---@return boolean
+local function rememberServerUUID(UUID)
+    for _, seen in ipairs(recent_server_uuids) do
+        if seen == UUID then
+            return false
+        end
+    end
+    table.insert(recent_server_uuids, UUID)
+    if #recent_server_uuids > RECENT_SERVER_UUID_LIMIT then
+        table.remove(recent_server_uuids, 1)
+    end
+    return true
+end
+
 --- Constructs a packet in a the set format.
 ---
 --- Requires a UUID.
@@ -160,8 +265,10 @@ local function send(message, type, UUID)
 end
 
 --- Waits for any message to come into the websocket. Calling this with zero timeout will not block.
+---
+--- Also returns the raw message, in case it needs to be put back.
 ---@param timeout number|nil
----@returns boolean, any
+---@returns boolean, any, string|nil
 local function receive(timeout)
     -- Skip if networking is disabled
     if NETWORKING_DISABLED then
@@ -207,12 +314,306 @@ local function receive(timeout)
         panic.forceReboot("Failed to unpack received packet! : " .. tostring(result_or_failure))
     end
 
-    return true, result_or_failure
+    return true, result_or_failure, message_or_pcall_error
+end
+
+--- Check if our connection to the server still looks alive. If this returns
+--- false, call `NETWORKING.reconnect`.
+---@return boolean
+function NETWORKING.isHealthy()
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return true
+    end
+    if not NETWORKING.websocket then
+        return false
+    end
+    -- Servers that never ping us get the benefit of the doubt.
+    if NETWORKING.last_ping == nil then
+        return true
+    end
+    return os.epoch("utc") - NETWORKING.last_ping < PING_TIMEOUT_MS
+end
+
+
+--- How long to wait for the server to ack one of our packets before sending it
+--- again, in seconds.
+local ACK_TIMEOUT_SECONDS = 2
+
+--- How many times we send a packet before giving up on the server.
+local SEND_ATTEMPTS = 5
+
+--- Wait for the server to ack the packet with this UUID. Returns false if the
+--- ack never came.
+---
+--- Anything else that comes in while we wait is put back on the event queue
+--- once we are done, so whoever is reading packets still gets it. Acks for
+--- other packets are the server re-acking copies it already acked, so those
+--- are dropped.
+---@param UUID string
+---@return boolean
+local function waitForAck(UUID)
+    local deadline = os.clock() + ACK_TIMEOUT_SECONDS
+    local passed_over = {}
+    local acked = false
+    while os.clock() < deadline do
+        local ok, result, raw = receive(deadline - os.clock())
+        if not ok then
+            break
+        end
+        local is_ack = type(result) == "table" and result.packet_type == "ack"
+        if is_ack and type(result.data) == "table" and result.data.uuid == UUID then
+            acked = true
+            break
+        end
+        if not is_ack then
+            passed_over[#passed_over + 1] = raw
+        end
+    end
+
+    -- Same layout as the event the websocket queues: url, message, binary.
+    for _, message in ipairs(passed_over) do
+        os.queueEvent("websocket_message", NETWORKING.SERVER_URL, message, false)
+    end
+    return acked
 end
 
--- TODO: Check if the socket is healthy, automatically re-connects if needed.
+--- Send a packet, and keep re-sending it until the server acks it. Every
+--- attempt re-uses the same UUID, so the server can drop the copies it
+--- already handled.
+---
+--- Reboots the computer if the server never acks it.
+---@param message table|string
+---@param type PacketType
+local function sendWithRetries(message, type)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    -- Create a UUID for this message
+    local UUID = getUUID()
 
+    for i = 1, SEND_ATTEMPTS do
+        if send(message, type, UUID) and waitForAck(UUID) then
+            return
+        end
+    end
+
+    -- The server never got it, or we never got its ack. Either way, something
+    -- is very wrong with the connection.
+    panic.forceReboot("Server never acked a message after " .. SEND_ATTEMPTS .. " attempts!")
+end
 
+--- Send a packet that the server answers directly instead of acking, like our
+--- hello. Only retries when sending fails on our end, since the answer is
+--- what we wait on.
+---
+--- Reboots the computer if every attempt fails.
+---@param message table|string
+---@param type PacketType
+local function sendUnacked(message, type)
+    -- Create a UUID for this message
+    local UUID = getUUID()
+
+    for i = 1, SEND_ATTEMPTS do
+        if send(message, type, UUID) then
+            return
+        end
+    end
+
+    -- Failed to send every time. This should not happen.
+    panic.forceReboot("Failed to send a message after " .. SEND_ATTEMPTS .. " attempts!")
+end
+
+-- =========
+-- Compact encoding
+-- =========
+
+-- Walkbacks and world deltas are mostly positions and blocks, which are huge
+-- as json. If the server agreed to it in our hello, we pack those into bytes
+-- instead. See `compact.rs` on the rust side for the layout.
+
+--- The version of the compact layout.
+---
+--- Must match `COMPACT_VERSION` on the rust side.
+local COMPACT_VERSION = 1
+
+--- The block states we keep, in the order of their bits in the palette bitmask.
+---
+--- Must match `STATE_FIELDS` on the rust side.
+local STATE_FIELDS = { "age", "eye", "honey_level", "level", "lit", "stage" }
+local STATE_BITS = { 1, 2, 4, 8, 16, 32 }
+
+--- Facings in the order they are numbered. Zero is no facing.
+local FACING_NUMBERS = { n = 1, e = 2, s = 3, w = 4, u = 5, d = 6 }
+
+local BASE64_ALPHABET = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+
+--- Add a varint to the end of a list of byte strings. Only takes whole numbers
+--- that are not negative.
+---@param out string[]
+---@param value number
+local function writeVarint(out, value)
+    while value >= 128 do
+        out[#out + 1] = string.char(value % 128 + 128)
+        value = math.floor(value / 128)
+    end
+    out[#out + 1] = string.char(value)
+end
+
+--- Zigzag a whole number first, so small negative numbers stay small.
+---@param out string[]
+---@param value number
+local function writeSigned(out, value)
+    if value >= 0 then
+        writeVarint(out, value * 2)
+    else
+        writeVarint(out, -value * 2 - 1)
+    end
+end
+
+--- Write a list of positions, each as the difference from the one before it.
+---@param out string[]
+---@param positions CoordPosition[]
+local function writePositions(out, positions)
+    writeVarint(out, #positions)
+    local x, y, z = 0, 0, 0
+    for _, position in ipairs(positions) do
+        writeSigned(out, position.x - x)
+        writeSigned(out, position.y - y)
+        writeSigned(out, position.z - z)
+        x, y, z = position.x, position.y, position.z
+    end
+end
+
+--- Pull the positions back out of a table keyed by `helpers.keyFromTable`.
+---@param keyed table<string, any>|nil
+---@return CoordPosition[]
+local function keysToPositions(keyed)
+    local positions = {}
+    for key, _ in pairs(keyed or {}) do
+        local x, y, z = string.match(key, "^x:(-?%d+)|y:(-?%d+)|z:(-?%d+)$")
+        positions[#positions + 1] = { x = tonumber(x), y = tonumber(y), z = tonumber(z) }
+    end
+    return positions
+end
+
+--- Write every block, with a palette of every distinct block first.
+---@param out string[]
+---@param blocks table<string, Block>|nil
+local function writeBlocks(out, blocks)
+    local palette = {}
+    local palette_size = 0
+    local palette_indexes = {}
+    local positions = {}
+    local indexes = {}
+    for _, block in pairs(blocks or {}) do
+        local state = block.state or {}
+        local mask = 0
+        local values = {}
+        local key_parts = { block.name }
+        for bit, field in ipairs(STATE_FIELDS) do
+            local value = state[field]
+            if value ~= nil then
+                if type(value) == "boolean" then
+                    value = value and 1 or 0
+                end
+                mask = mask + STATE_BITS[bit]
+                values[#values + 1] = value
+                key_parts[#key_parts + 1] = field .. "=" .. value
+            end
+        end
+
+        local key = table.concat(key_parts, "|")
+        local index = palette_indexes[key]
+        if index == nil then
+            index = palette_size
+            palette_size = palette_size + 1
+            palette_indexes[key] = index
+            writeVarint(palette, #block.name)
+            palette[#palette + 1] = block.name
+            writeVarint(palette, mask)
+            for _, value in ipairs(values) do
+                writeVarint(palette, value)
+            end
+        end
+        positions[#positions + 1] = block.pos
+        indexes[#indexes + 1] = index
+    end
+
+    writeVarint(out, palette_size)
+    out[#out + 1] = table.concat(palette)
+    writeVarint(out, #positions)
+    local x, y, z = 0, 0, 0
+    for i, position in ipairs(positions) do
+        writeSigned(out, position.x - x)
+        writeSigned(out, position.y - y)
+        writeSigned(out, position.z - z)
+        x, y, z = position.x, position.y, position.z
+        writeVarint(out, indexes[i])
+    end
+end
+
+--- Standard base64 with padding, so the bytes survive being put in json.
+---
+--- Must match `to_base64` on the rust side.
+---@param bytes string
+---@return string
+local function toBase64(bytes)
+    local out = {}
+    for i = 1, #bytes, 3 do
+        local a, b, c = string.byte(bytes, i, i + 2)
+        local group = a * 65536 + (b or 0) * 256 + (c or 0)
+        local chars = {}
+        for j = 1, 4 do
+            local sextet = math.floor(group / 64 ^ (4 - j)) % 64
+            chars[j] = BASE64_ALPHABET:sub(sextet + 1, sextet + 1)
+        end
+        if b == nil then
+            chars[3] = "="
+        end
+        if c == nil then
+            chars[4] = "="
+        end
+        out[#out + 1] = table.concat(chars)
+    end
+    return table.concat(out)
+end
+
+--- Pack a world delta from `walkback:takeWorldDelta()` into the compact
+--- encoding. Returns the data to send in place of the delta.
+---@param delta table
+---@return table
+local function compactWorldDelta(delta)
+    local out = {}
+    writeVarint(out, COMPACT_VERSION)
+    writeVarint(out, delta.sequence)
+    writeVarint(out, delta.full and 1 or 0)
+    writeBlocks(out, delta.blocks)
+    writePositions(out, keysToPositions(delta.positions))
+    return { compact = toBase64(table.concat(out)) }
+end
+
+--- Pack a walkback dump from `walkback:dataJson()` into the compact encoding.
+--- Returns the data to send in place of the dump.
+---
+--- `chain_seen_positions` is left out, the server rebuilds it from the chain.
+---@param walkback_data table
+---@return table
+local function compactWalkback(walkback_data)
+    local out = {}
+    local cur_position = walkback_data.cur_position
+    writeVarint(out, COMPACT_VERSION)
+    writeSigned(out, cur_position.position.x)
+    writeSigned(out, cur_position.position.y)
+    writeSigned(out, cur_position.position.z)
+    writeVarint(out, FACING_NUMBERS[cur_position.facing] or 0)
+    writePositions(out, walkback_data.walkback_chain or {})
+    writePositions(out, keysToPositions(walkback_data.all_seen_positions))
+    writeBlocks(out, walkback_data.all_seen_blocks)
+    return { compact = toBase64(table.concat(out)) }
+end
 
 --- One way message to the control server, does not expect a response. Should
 --- only be used for debugging.
@@ -230,30 +631,474 @@ function NETWORKING.debugSend(message)
         return
     end
 
-    -- Create a UUID for this message
-    local UUID = getUUID()
+    sendWithRetries(message, "debugging")
+end
 
-    -- send it!
-    -- We try at most 5 times before completely giving up.
-    for i = 1, 5 do
-        if send(message, "debugging", UUID) then
-            return
+--- Send panic data to the control server. Takes the table made in `panic.lua`.
+--- Does not expect a response.
+---@param panic_data PanicData
+function NETWORKING.panicSend(panic_data)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    sendWithRetries(panic_data, "panic")
+end
+
+--- Send a walkback dump to the control server. Takes the table returned from
+--- `walkback:dataJson()`. Does not expect a response.
+---@param walkback_data table
+function NETWORKING.walkbackSend(walkback_data)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    if NETWORKING.encoding == "compact" then
+        walkback_data = compactWalkback(walkback_data)
+    end
+    sendWithRetries(walkback_data, "walkback")
+end
+
+--- Let the control server know a task has ended, and how it went. Does not
+--- expect a response.
+---@param task_name string The `name` of the task that ended.
+---@param result TaskCompletion|TaskFailure
+function NETWORKING.taskResultSend(task_name, result)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    sendWithRetries({ task_name = task_name, result = result }, "task_result")
+end
+
+--- Let the control server know what we are holding. Takes the table returned
+--- from `walkback:inventoryJSON()`. Does not expect a response.
+---@param inventory table
+function NETWORKING.inventorySend(inventory)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    sendWithRetries(inventory, "inventory")
+end
+
+--- Let the control server know what we have seen. Takes the table returned
+--- from `walkback:takeWorldDelta()`. Does not expect a response.
+---@param delta table
+function NETWORKING.worldDeltaSend(delta)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    if NETWORKING.encoding == "compact" then
+        delta = compactWorldDelta(delta)
+    end
+    sendWithRetries(delta, "world_delta")
+end
+
+--- Ask the control server for a token for a turtle we are building. The answer
+--- shows up in `NETWORKING.takeProvisionedToken`.
+---@param computer_id number The computer ID of the new turtle.
+function NETWORKING.provisionSend(computer_id)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    NETWORKING.provisioned_tokens[computer_id] = nil
+    sendWithRetries({ computer_id = computer_id }, "provision")
+end
+
+--- Pick up the answer to a `provisionSend`. Returns nil if the server has not
+--- answered yet, and false if it answered without a token.
+---@param computer_id number
+---@return string|false|nil
+function NETWORKING.takeProvisionedToken(computer_id)
+    local token = NETWORKING.provisioned_tokens[computer_id]
+    if token ~= nil then
+        NETWORKING.provisioned_tokens[computer_id] = nil
+    end
+    return token
+end
+
+--- Answer a "request" packet from the control server. Does not expect a
+--- response.
+---
+--- Pass in a response table if the request worked, or an error message if
+--- it did not.
+---@param id string The id of the request we are answering.
+---@param response table|nil
+---@param error_message string|nil
+function NETWORKING.respond(id, response, error_message)
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    sendWithRetries({ id = id, response = response, error = error_message }, "response")
+end
+
+--- Check that a message from the server is actually a server packet, then
+--- deal with acks and pings.
+---
+--- The server always sends a `server_packet`, anything else means the server
+--- and the turtle disagree on the packet format, which we cannot recover from.
+---
+--- Data the server packed into the `pairs` format (See `lua_serde.rs`), IE to
+--- send us non-string keys, is unpacked back into a normal table here.
+---
+--- Returns false if this packet should be skipped, since it is an ack, a ping,
+--- or a re-send of a packet we already handled.
+---@param result any
+---@return boolean
+local function acceptPacket(result)
+    -- Make sure this is actually a packet.
+    local is_packet = type(result) == "table"
+        and type(result.uuid) == "string"
+        and type(result.timestamp) == "number"
+        and type(result.packet_type) == "string"
+    if not is_packet then
+        panic.panic("Received a malformed packet from the server! " .. tostring(result), true)
+    end
+    result.data = helpers.unpackTable(result.data)
+
+    -- Acks we are waiting on are picked up in `sendWithRetries`, any others
+    -- showed up too late to matter.
+    if result.packet_type == "ack" then
+        return false
+    end
+
+    if result.packet_type == "ping" then
+        NETWORKING.last_ping = os.epoch("utc")
+        -- No retries, there will be another ping soon enough.
+        send({ uuid = result.uuid }, "pong", getUUID())
+        return false
+    end
+
+    if ACKED_PACKET_TYPES[result.packet_type] then
+        -- Always ack, even on a re-send, since the server obviously never
+        -- got our last ack. No retries, the server will send it again.
+        send({ uuid = result.uuid }, "ack", getUUID())
+        if not rememberServerUUID(result.uuid) then
+            return false
         end
     end
 
-    -- Failed to send all 5 times. This should not happen.
-    panic.forceReboot("Failed to send a message after 5 attempts!")
+    if result.packet_type == "provisioned" then
+        -- Picked up with `takeProvisionedToken`. False means the server had
+        -- no token for us.
+        NETWORKING.provisioned_tokens[result.data.computer_id] = result.data.token or false
+        return false
+    end
+
+    return true
 end
 
 --- Block and wait for any incoming message.
 ---
 --- Takes in a timeout. Returns a boolean on wether we got anything before the timeout ended.
+---
+--- Acks and pings from the server, and re-sends of packets we have already
+--- handled, are skipped over. Each skipped packet restarts the timeout.
 ---@param timeout number
----@return boolean, any
+---@return boolean, server_packet|string
 function NETWORKING.waitForPacket(timeout)
-    -- very hollow wrapper at the moment lol.
-    local bool, result = receive(timeout)
-    return bool, result
+    while true do
+        local bool, result = receive(timeout)
+        if not bool then
+            return bool, result
+        end
+
+        if acceptPacket(result) then
+            return bool, result
+        end
+    end
+end
+
+--- Handle a message pulled from a `websocket_message` event, for when we are
+--- pulling events ourselves instead of calling `waitForPacket`.
+---
+--- Returns nil if there is nothing else to do with this message.
+---@param message string The raw message from the event.
+---@return server_packet|nil
+function NETWORKING.handleMessage(message)
+    local ok, result = helpers.deserializeJSON(message)
+    if not ok then
+        -- Same as `receive`.
+        panic.forceReboot("Failed to unpack received packet! : " .. tostring(result))
+    end
+
+    if acceptPacket(result) then
+        return result
+    end
+    return nil
+end
+
+--- Adler-32 hash of a file. Not secure in the slightest, but we only need to
+--- tell if our libraries match the server's, and this needs no bit operations.
+---
+--- Must match `adler32` on the rust side.
+---@param path string
+---@return number
+local function hashFile(path)
+    local file = fs.open(path, "rb")
+    if not file then
+        return 0
+    end
+    local contents = file.readAll() or ""
+    file.close()
+
+    local a, b = 1, 0
+    for i = 1, #contents do
+        a = (a + string.byte(contents, i)) % 65521
+        b = (b + a) % 65521
+    end
+    return b * 65536 + a
+end
+
+-- =========
+-- Authentication
+-- =========
+
+-- When the server asks, we prove we hold the token issued to our computer ID
+-- by sending back the HMAC-SHA256 of `<computer id>:<nonce>`, keyed with the
+-- token. See `auth.rs` on the rust side.
+
+--- The round constants of SHA-256.
+local SHA256_CONSTANTS = {
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+}
+
+--- Numbers are doubles, so additions are kept to 32 bits by hand.
+local WORD = 2 ^ 32
+
+--- SHA-256 of a string of bytes. Returns the digest as a string of bytes.
+---
+--- Must match `sha256` on the rust side.
+---@param message string
+---@return string
+local function sha256(message)
+    local band, bnot, bxor = bit32.band, bit32.bnot, bit32.bxor
+    local rrotate, rshift = bit32.rrotate, bit32.rshift
+
+    -- Pad out to a whole number of blocks, ending in the length in bits.
+    local length = #message
+    local bit_length = {}
+    for i = 7, 0, -1 do
+        bit_length[#bit_length + 1] = string.char(math.floor(length * 8 / 256 ^ i) % 256)
+    end
+    message = message .. "\128" .. string.rep("\0", (55 - length) % 64) .. table.concat(bit_length)
+
+    local state = {
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    }
+    for block = 1, #message, 64 do
+        local w = {}
+        for i = 1, 16 do
+            local a, b, c, d = string.byte(message, block + (i - 1) * 4, block + (i - 1) * 4 + 3)
+            w[i] = ((a * 256 + b) * 256 + c) * 256 + d
+        end
+        for i = 17, 64 do
+            local s0 = bxor(rrotate(w[i - 15], 7), rrotate(w[i - 15], 18), rshift(w[i - 15], 3))
+            local s1 = bxor(rrotate(w[i - 2], 17), rrotate(w[i - 2], 19), rshift(w[i - 2], 10))
+            w[i] = (w[i - 16] + s0 + w[i - 7] + s1) % WORD
+        end
+
+        local a, b, c, d, e, f, g, h = table.unpack(state)
+        for i = 1, 64 do
+            local s1 = bxor(rrotate(e, 6), rrotate(e, 11), rrotate(e, 25))
+            local choice = bxor(band(e, f), band(bnot(e), g))
+            local temp1 = (h + s1 + choice + SHA256_CONSTANTS[i] + w[i]) % WORD
+            local s0 = bxor(rrotate(a, 2), rrotate(a, 13), rrotate(a, 22))
+            local majority = bxor(band(a, b), band(a, c), band(b, c))
+            local temp2 = (s0 + majority) % WORD
+            h, g, f, e = g, f, e, (d + temp1) % WORD
+            d, c, b, a = c, b, a, (temp1 + temp2) % WORD
+        end
+        local results = { a, b, c, d, e, f, g, h }
+        for i = 1, 8 do
+            state[i] = (state[i] + results[i]) % WORD
+        end
+    end
+
+    local digest = {}
+    for i = 1, 8 do
+        local word = state[i]
+        digest[i] = string.char(
+            math.floor(word / 16777216) % 256,
+            math.floor(word / 65536) % 256,
+            math.floor(word / 256) % 256,
+            word % 256
+        )
+    end
+    return table.concat(digest)
+end
+
+--- HMAC-SHA256 of a message. Returns the digest as a string of bytes.
+---
+--- Must match `hmac_sha256` on the rust side.
+---@param key string
+---@param message string
+---@return string
+local function hmacSha256(key, message)
+    if #key > 64 then
+        key = sha256(key)
+    end
+    key = key .. string.rep("\0", 64 - #key)
+    local inner, outer = {}, {}
+    for i = 1, 64 do
+        local byte = string.byte(key, i)
+        inner[i] = string.char(bit32.bxor(byte, 0x36))
+        outer[i] = string.char(bit32.bxor(byte, 0x5c))
+    end
+    return sha256(table.concat(outer) .. sha256(table.concat(inner) .. message))
+end
+
+--- Lowercase hex of a string of bytes.
+---@param bytes string
+---@return string
+local function toHex(bytes)
+    return (bytes:gsub(".", function(char)
+        return string.format("%02x", string.byte(char))
+    end))
+end
+
+--- Our token, if we have one.
+---@return string|nil
+local function readToken()
+    if not fs.exists(NETWORKING.TOKEN_FILE) then
+        return nil
+    end
+    local file = fs.open(NETWORKING.TOKEN_FILE, "r")
+    if not file then
+        return nil
+    end
+    local token = file.readAll()
+    file.close()
+    -- Hand edited files tend to pick up a trailing newline.
+    return token and token:match("^%s*(.-)%s*$")
+end
+
+--- Answer a "challenge" from the server. Turtles without a token still answer,
+--- so the server can tell them apart from turtles that never answered.
+---@param nonce string
+local function answerChallenge(nonce)
+    local token = readToken()
+    local proof = nil
+    if token ~= nil then
+        proof = toHex(hmacSha256(token, os.getComputerID() .. ":" .. nonce))
+    end
+    sendUnacked({ proof = proof }, "auth")
+end
+
+--- Introduce ourselves to the server. Sends our protocol version, the hashes of
+--- all of our libraries, and our `hello_world.json` if we have one.
+---
+--- Reboots if the server does not answer, or turns us away.
+local function hello()
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    -- Libraries are all installed flat in the root. `startup.lua` is different
+    -- on every computer, so it is not a library.
+    local libraries = {}
+    for _, name in ipairs(fs.list("/")) do
+        if name:sub(-4) == ".lua" and name ~= "startup.lua" and not fs.isDir(name) then
+            libraries[name] = hashFile(name)
+        end
+    end
+
+    local hello_world = nil
+    if fs.exists("hello_world.json") then
+        local file = fs.open("hello_world.json", "r")
+        if file then
+            local ok, result = helpers.deserializeJSON(file.readAll())
+            file.close()
+            if ok then
+                hello_world = result
+            end
+        end
+    end
+
+    sendUnacked({
+        protocol_version = NETWORKING.PROTOCOL_VERSION,
+        libraries = libraries,
+        hello_world = hello_world,
+        encodings = OFFERED_ENCODINGS,
+    }, "hello")
+
+    local ok, reply = NETWORKING.waitForPacket(10)
+    if not ok then
+        panic.forceReboot("Server never answered our hello! " .. tostring(reply))
+    end
+    ---@cast reply server_packet
+
+    -- Servers that check who we are want proof before they answer.
+    NETWORKING.authenticated = false
+    if reply.packet_type == "challenge" then
+        answerChallenge(reply.data.nonce)
+        ok, reply = NETWORKING.waitForPacket(10)
+        if not ok then
+            panic.forceReboot("Server never answered our proof! " .. tostring(reply))
+        end
+        ---@cast reply server_packet
+        NETWORKING.authenticated = true
+    end
+
+    if reply.packet_type ~= "hello_reply" then
+        panic.forceReboot("Expected a hello_reply, got: " .. tostring(reply.packet_type))
+    end
+
+    -- Older servers do not pick an encoding.
+    NETWORKING.encoding = reply.data.encoding or "json"
+
+    local status = reply.data.status
+    if status == "rejected" then
+        panic.forceReboot("Server rejected our hello! " .. tostring(reply.data.reason))
+    elseif status == "outdated" then
+        -- We can keep going, but the server should update us soon.
+        print("Server says these libraries are outdated:")
+        for _, name in ipairs(reply.data.stale_libraries or {}) do
+            print(name)
+        end
+    end
+end
+
+print("Saying hello...")
+hello()
+
+--- Throw away our websocket and connect again from scratch, saying hello again.
+--- The server re-sends anything we never acked.
+---
+--- Reboots if we cannot get back in.
+function NETWORKING.reconnect()
+    -- Skip if networking is disabled
+    if NETWORKING_DISABLED then
+        return
+    end
+
+    if NETWORKING.websocket then
+        -- Might already be closed, which is fine.
+        pcall(NETWORKING.websocket.close)
+    end
+    NETWORKING.websocket = nil
+    NETWORKING.last_ping = nil
+    connect()
+    hello()
 end
 
 print("Done setting up networking!")
\ No newline at end of file
@@ -1,6 +1,15 @@
 // Turtle networking related tests.
 
-use crate::tests::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::{
+    minecraft::computercraft::computer_types::{
+        lua_serde::{from_lua_value, to_lua_value},
+        lua_types::LuaValue,
+        packet_types::DebuggingPacket,
+    },
+    tests::prelude::*,
+};
 use log::info;
 
 #[tokio::test]
@@ -62,24 +71,24 @@ async fn websocket_ping_pong() {
     // Turn on the computer, and wait for the ping message
     computer.turn_on(&mut test).await;
 
-    let ping = socket.receive(1).await.expect("Turtle should yap");
+    let ping: DebuggingPacket = socket.receive_packet(1).await.expect("Turtle should yap");
     info!("Got ping!");
-    info!("{ping}");
-    assert!(ping.contains("ping"));
+    info!("{}", ping.inner_data);
+    assert_eq!(ping.inner_data, "ping");
 
     // send back
     info!("Sending pong...");
     socket
-        .send("\"pong\"".to_string(), 1)
+        .send(ServerMessage::DebugReply("pong".into()), 1)
         .await
         .expect("Should send");
     info!("Sent.");
 
     // Wait for the next response
     info!("Awaiting response...");
-    let response = socket.receive(1).await.expect("Turtle should yap");
-    let pass_fail = response.contains("pass");
-    info!("Got it! {response}");
+    let response: DebuggingPacket = socket.receive_packet(1).await.expect("Turtle should yap");
+    let pass_fail = response.inner_data == "pass";
+    info!("Got it! {}", response.inner_data);
     info!("Pass fail? {pass_fail}");
     if pass_fail {
         info!("Pass!");
@@ -94,3 +103,70 @@ async fn websocket_ping_pong() {
     test.stop(pass_fail).await;
     assert!(pass_fail);
 }
+
+#[tokio::test]
+/// Packed tables from the server should unpack into normal tables on the
+/// turtle, and come back the same after packing them again.
+async fn packed_tables_round_trip() {
+    let area = TestArea {
+        size_x: 3,
+        size_z: 3,
+    };
+    let mut test = MinecraftTestHandle::new(area, "Packed tables round trip").await;
+    let position = MinecraftPosition {
+        position: CoordinatePosition { x: 1, y: 1, z: 1 },
+        facing: None,
+    };
+
+    // Json can't key things by numbers, so this only makes it over packed.
+    let test_script = r#"
+    require("networking")
+    local helpers = require("helpers")
+    NETWORKING.debugSend("ready")
+    local ok, result = NETWORKING.waitForPacket(60)
+    if not ok then
+        NETWORKING.debugSend("fail")
+        return
+    end
+    local slots = result.data
+    if slots[1] ~= "minecraft:coal" or slots[16] ~= "minecraft:stone" then
+        NETWORKING.debugSend("fail")
+        return
+    end
+    NETWORKING.debugSend(helpers.packTable(slots))
+    "#;
+
+    let libraries = MeshpitLibraries {
+        networking: Some(true),
+        panic: Some(true),
+        helpers: Some(true),
+        ..Default::default()
+    };
+    let config = ComputerConfigs::StartupIncludingLibraries(test_script.to_string(), libraries);
+    let setup = ComputerSetup::new(ComputerKind::Basic, config);
+    let computer = test.build_computer(&position, setup).await;
+
+    let mut socket = TestWebsocket::new(computer.id())
+        .await
+        .expect("Should be able to get a socket.");
+    computer.turn_on(&mut test).await;
+
+    let ready: DebuggingPacket = socket.receive_packet(5).await.expect("Turtle should yap");
+    assert_eq!(ready.inner_data, "ready");
+
+    let slots: BTreeMap<u32, String> =
+        BTreeMap::from([(1, "minecraft:coal".into()), (16, "minecraft:stone".into())]);
+    let packed = serde_json::to_value(to_lua_value(&slots).unwrap()).unwrap();
+    socket
+        .send(ServerMessage::DebugReply(packed), 5)
+        .await
+        .expect("Should send");
+
+    let echoed: DebuggingPacket = socket.receive_packet(5).await.expect("Turtle should yap");
+    let back = serde_json::from_value::<LuaValue>(echoed.inner_data)
+        .ok()
+        .and_then(|value| from_lua_value::<BTreeMap<u32, String>>(value).ok());
+    let passed = back.as_ref() == Some(&slots);
+    test.stop(passed).await;
+    assert_eq!(back, Some(slots));
+}
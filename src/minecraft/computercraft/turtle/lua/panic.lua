@@ -56,6 +56,9 @@ local function makePanicData(message, message_only)
         the_up_values = panicUpValues()
     end
 
+    -- Can't require this at the top, helpers needs us.
+    local helpers = require("helpers")
+
     ---@alias PanicData {stack_trace: string, locals: table, up_values: table}
     ---@type PanicData
     local panic_data = {
@@ -64,13 +67,13 @@ local function makePanicData(message, message_only)
 
         -- Every local variable.
         -- In the format of an array of pairs.
-        locals = local_vars,
+        locals = helpers.packTable(local_vars),
 
         -- Every external variable we are referencing. This table has no overlap with locals.
         -- In the format of an array of pairs.
         -- up_values = the_up_values,\
         -- TODO: this is for debugging
-        up_values = {}
+        up_values = helpers.packTable({})
     }
 
     return panic_data
@@ -129,7 +132,7 @@ function panic.panic(message, message_only)
 
     -- Transmit that table to control.
     -- This will automatically turn the table into json.
-    NETWORKING.debugSend(panic_data)
+    NETWORKING.panicSend(panic_data)
 
     -- Done panicking.
     CURRENTLY_PANICKING = false
@@ -29,6 +29,9 @@ local helpers = require("helpers")
 ---
 --- Notes:
 --- - No timeout.
+--- - If the control server checks who turtles are, the new turtle gets its
+---   own token from the server, see `auth.rs`. Waits at most
+---   `TOKEN_WAIT_SECONDS` for it.
 --- - Returns NoneResult.
 --- @class MitosisData
 --- @field name "mitosis_task"
@@ -63,6 +66,12 @@ local valid_fuels = {
     },
 }
 
+--- How long to wait on the control server for the new turtle's token.
+local TOKEN_WAIT_SECONDS = 10
+
+--- How often to ask the control server for the token again while waiting.
+local TOKEN_ASK_EVERY_SECONDS = 3
+
 --- Mine through the world till a desired Y level is hit.
 --- @param config TurtleTask
 --- @return TaskCompletion|TaskFailure
@@ -134,6 +143,14 @@ local function mitosis(config)
     -- Wait a moment for it to start up.
     task_helpers.taskSleep(10)
 
+    -- It has an ID now, so the server can make it a token while we work. Only
+    -- servers that checked who we are hand out tokens.
+    local new_id = a.getID()
+    local wants_token = NETWORKING.authenticated
+    if wants_token then
+        NETWORKING.provisionSend(new_id)
+    end
+
     -- Now we can pick it up. Unsafe needs to be set since we're breaking a turtle.
     a = nil
     task_helpers.assert(wb:dig(nil, true))
@@ -176,7 +193,8 @@ local function mitosis(config)
 
     for _, path in ipairs(paths) do
         local worked = false
-        if path == mount_point or path == "rom" or path == "hello_world.json" then
+        if path == mount_point or path == "rom" or path == "hello_world.json"
+            or path == NETWORKING.TOKEN_FILE then
             goto continue
         end
 
@@ -209,6 +227,33 @@ local function mitosis(config)
     file.flush()
     file.close()
 
+    -- And its token, so it can prove who it is.
+    if wants_token then
+        local token = NETWORKING.takeProvisionedToken(new_id)
+        local waited = 0
+        while token == nil and waited < TOKEN_WAIT_SECONDS do
+            task_helpers.taskSleep(0.5)
+            waited = waited + 0.5
+            token = NETWORKING.takeProvisionedToken(new_id)
+            -- Either packet may have been lost, and the server hands us the
+            -- same token again until the new turtle uses it.
+            if token == nil and waited % TOKEN_ASK_EVERY_SECONDS == 0 then
+                NETWORKING.provisionSend(new_id)
+            end
+        end
+        -- Without a token the server will turn it away, but that is the
+        -- server's call to make, so keep going.
+        if token then
+            ---@diagnostic disable-next-line: undefined-global
+            local token_file = fs.open(mount_point .. "/" .. NETWORKING.TOKEN_FILE, "w")
+            token_file.write(token)
+            token_file.flush()
+            token_file.close()
+        else
+            print("Never got a token for turtle " .. tostring(new_id) .. "!")
+        end
+    end
+
     -- Done putting on files, digging will pick up the drive and the contents.
     -- Unsafe since its a disk drive.
     task_helpers.assert(wb:dig(nil, true))
@@ -89,7 +89,27 @@ local walkback = {
     all_seen_positions = {};
 
 	---@type AllSeenBlocks
-	all_seen_blocks = {}
+	all_seen_blocks = {};
+
+	--- Blocks seen since the last world delta, in the same format as
+	--- all_seen_blocks. See `walkback:takeWorldDelta()`.
+	---@type AllSeenBlocks
+	pending_blocks = {};
+
+	--- Positions visited since the last world delta, in the same format as
+	--- all_seen_positions.
+	---@type AllSeenPositions
+	pending_positions = {};
+
+	--- The sequence number of the next world delta.
+	---@type number
+	world_sequence = 0;
+
+	--- Wether the next world delta should contain everything we have seen,
+	--- instead of only what changed. Starts set, so the control server gets
+	--- everything after we boot.
+	---@type boolean
+	world_resync = true
 
 	--- In the future, it may be work storing all of the items the turtle is currently holding.
 	--- This would allow fast inventory lookups to see if we have an item, but would require
@@ -220,6 +240,7 @@ function walkback:addStep()
 	-- Add it to the all seen positions hashset.
 	-- This is fine if it overwrites an already seen value, since that has no effect.
 	self.all_seen_positions[current_pos_key] = true
+	self.pending_positions[current_pos_key] = true
 end
 
 --- Perform a turtle movement based off of a MovementDirection.
@@ -256,7 +277,7 @@ end
 ---
 --- Does not clone the block. Thus callers MUST NOT modify it after storing.
 ---
---- Modifies global state. (Updates all_seen_blocks)
+--- Modifies global state. (Updates all_seen_blocks, pending_blocks)
 ---@param position CoordPosition
 ---@param block Block
 --- @private
@@ -266,6 +287,7 @@ function walkback:saveBlock(position, block)
 	local key = helpers.keyFromTable(position)
 	---@cast key string
 	self.all_seen_blocks[key] = block
+	self.pending_blocks[key] = block
 end
 
 -- ========================
@@ -606,6 +628,49 @@ function walkback:dataJson()
 	return needed
 end
 
+--- Wether there is anything to put in a world delta.
+---@return boolean
+function walkback:hasWorldDelta()
+	return self.world_resync
+		or next(self.pending_blocks) ~= nil
+		or next(self.pending_positions) ~= nil
+end
+
+--- Make the next world delta to send to the control server, containing every
+--- block seen and position visited since the last one. If a resync was asked
+--- for, it contains everything we have seen instead.
+---
+--- Every delta gets the next sequence number, so the control server can tell
+--- if one went missing.
+---
+--- Modifies global state. (Clears pending_blocks, pending_positions)
+---
+--- Returns a table. Will be serialized on the way out.
+---@return table -- {sequence: number, full: boolean, blocks: AllSeenBlocks, positions: AllSeenPositions}
+function walkback:takeWorldDelta()
+	local delta = {
+		sequence = self.world_sequence,
+		full = self.world_resync,
+		blocks = self.pending_blocks,
+		positions = self.pending_positions,
+	}
+	if self.world_resync then
+		delta.blocks = self.all_seen_blocks
+		delta.positions = self.all_seen_positions
+	end
+	self.pending_blocks = {}
+	self.pending_positions = {}
+	self.world_sequence = self.world_sequence + 1
+	self.world_resync = false
+	return delta
+end
+
+--- The control server missed some of our world deltas, so the next one will
+--- contain everything we have seen.
+function walkback:requestWorldResync()
+	self.world_resync = true
+end
+
 --- Returns all of the items the turtle currently has in its inventory as an array.
 --- Empty slots are null.
 ---
@@ -626,9 +691,10 @@ function walkback:inventoryJSON()
 				["count"] = found.count
 			}
 			slots[#slots+1] = array_value
+		else
+			-- No item
+			slots[#slots+1] = json_null
 		end
-		-- No item
-		slots[#slots+1] = json_null
 	end
 	local inv = {
 		["size"] = 16,
@@ -7,7 +7,7 @@ use serde::Deserialize;
 use crate::{
     minecraft::{
         computercraft::computer_types::{
-            packet_types::{DebuggingPacket, RawTurtlePacket, WalkbackPacket},
+            packet_types::{DebuggingPacket, WalkbackPacket},
             walkback_type::Walkback,
         },
         peripherals::inventory::GenericInventory,
@@ -90,7 +90,7 @@ async fn basic_movement_test() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     let wanted_data = String::from("id");
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
@@ -233,7 +233,7 @@ async fn basic_walkback_tests() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
@@ -368,20 +368,16 @@ async fn basic_block_memorization() {
     );
 
     // do the thing mr turtle pls
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
-    let front = socket.receive(5).await.unwrap();
-    let up = socket.receive(5).await.unwrap();
+    let front: DebuggingPacket = socket.receive_packet(5).await.unwrap();
+    let up: DebuggingPacket = socket.receive_packet(5).await.unwrap();
 
     // make those blocks
-    let front: RawTurtlePacket = serde_json::from_str(&front).unwrap();
-    let front: DebuggingPacket = front.try_into().unwrap();
     let front: PositionedMinecraftBlock =
         PositionedMinecraftBlock::deserialize(&front.inner_data).unwrap();
-    let up: RawTurtlePacket = serde_json::from_str(&up).unwrap();
-    let up: DebuggingPacket = up.try_into().unwrap();
     let up: PositionedMinecraftBlock =
         PositionedMinecraftBlock::deserialize(&up.inner_data).unwrap();
 
@@ -424,7 +420,7 @@ async fn walkback_serialization() {
     walkback:setup(1,1,1,"n")
 
     debugging.waitStep()
-    NETWORKING.debugSend(walkback:dataJson())
+    NETWORKING.walkbackSend(walkback:dataJson())
     "#;
 
     let libraries = MeshpitLibraries {
@@ -449,19 +445,14 @@ async fn walkback_serialization() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
-    // Store the json
-    let json: String = socket
-        .receive(5)
+    let walkback_packet: WalkbackPacket = socket
+        .receive_packet(5)
         .await
         .expect("Should get walkback message");
-
-    // Deserialize it!
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&json).unwrap();
-    let walkback_packet: WalkbackPacket = raw_packet.try_into().unwrap();
     let walkback: Walkback = walkback_packet.walkback;
 
     // The position should have not changed.
@@ -520,21 +511,16 @@ async fn empty_inventory_serialization() {
     computer.turn_on(&mut test).await;
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
-    // Store the json
-    let json: String = socket
-        .receive(5)
+    let inventory_packet: DebuggingPacket = socket
+        .receive_packet(5)
         .await
         .expect("Should get a inventory message");
-
-    // Deserialize it!
-    let raw_packet: RawTurtlePacket = serde_json::from_str(&json).unwrap();
-    let walkback_packet: DebuggingPacket = raw_packet.try_into().unwrap();
     let inventory: GenericInventory =
-        GenericInventory::deserialize(&walkback_packet.inner_data).unwrap();
+        GenericInventory::deserialize(&inventory_packet.inner_data).unwrap();
 
     // Should have 16 slots
     assert_eq!(inventory.size, 16);
@@ -567,8 +553,7 @@ async fn recognize_all_blocks() {
 
     assert!(
         test.command(TestCommand::Fill(
-            c1,
-            c2,
+            Cuboid::new(c1, c2),
             &MinecraftBlock::from_string("barrier").unwrap()
         ))
         .await
@@ -583,8 +568,7 @@ async fn recognize_all_blocks() {
     c2.z -= 1;
     assert!(
         test.command(TestCommand::Fill(
-            c1,
-            c2,
+            Cuboid::new(c1, c2),
             &MinecraftBlock::from_string("air").unwrap()
         ))
         .await
@@ -657,7 +641,7 @@ async fn recognize_all_blocks() {
     // can start the loop
 
     // Initial handshake
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
@@ -708,15 +692,10 @@ async fn recognize_all_blocks() {
             .send(str.clone(), 1)
             .await
             .expect("Should be able to send message");
-        let turtle_saw = socket
-            .receive(1)
+        let debug_packet: DebuggingPacket = socket
+            .receive_packet(1)
             .await
             .expect("Turtle should send a block.");
-        let raw_packet: RawTurtlePacket =
-            serde_json::from_str(&turtle_saw).expect("Turtle should return a packet.");
-        let debug_packet: DebuggingPacket = raw_packet
-            .try_into()
-            .expect("This should be a debugging packet.");
 
         // Now the inside of that packet is just a MinecraftBlock that needs to be deserialized.
         let returned_block: PositionedMinecraftBlock =
@@ -991,7 +970,7 @@ async fn rotation_breaking() {
     let c2 = CoordinatePosition { x: 3, y: 1, z: 3 };
     let cobblestone = MinecraftBlock::from_string("cobblestone").unwrap();
 
-    assert!(test.command(TestCommand::Fill(c1, c2, &cobblestone)).await.success());
+    assert!(test.command(TestCommand::Fill(Cuboid::new(c1, c2), &cobblestone)).await.success());
 
     // The two blocks that should not be broken
     let mut dont_touch_1 = turtle_position;
@@ -1019,7 +998,7 @@ async fn rotation_breaking() {
     computer.turn_on(&mut test).await;
 
     // Let it do it's thing. This should take at most 5 minutes
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
     socket.receive(5).await.expect("Should receive");
     socket.send(str.clone(), 5).await.expect("Should send");
 
@@ -1116,7 +1095,7 @@ async fn dig_unsafe_block_test() {
 
     computer.turn_on(&mut test).await;
 
-    let str = String::from("go");
+    let str = ServerMessage::DebugReply("go".into());
 
     // Initial handshake
     socket.receive(5).await.unwrap();
@@ -1,12 +1,11 @@
 // Generic inventories.
 
-use std::collections::HashMap;
-
 use serde::Deserialize;
 
 use crate::minecraft::vanilla::item_type::MinecraftItem;
 
 // TODO: if we implement the methods as a trait, then it would be easier to add new things.
+/// Matches the output of `walkback:inventoryJSON()`.
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct GenericInventory {
     /// The size of the inventory, IE how many slots it has.
@@ -22,3 +21,24 @@ pub struct GenericInventorySlot {
     /// How many of that item are in the slot.
     pub count: u8,
 }
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Empty slots come across as null, and items by their full names.
+fn inventory_matches_lua_shape() {
+    let json = r#"{"size":3,"slots":[{"item":"minecraft:coal","count":12},null,{"item":"computercraft:disk_drive","count":1}]}"#;
+    let inventory: GenericInventory = serde_json::from_str(json).unwrap();
+    assert_eq!(inventory.size, 3);
+    assert_eq!(inventory.slots.len(), 3);
+    let first = inventory.slots[0].unwrap();
+    assert_eq!(first.item, MinecraftItem::from_string("coal").unwrap());
+    assert_eq!(first.count, 12);
+    assert!(inventory.slots[1].is_none());
+    assert_eq!(
+        inventory.slots[2].unwrap().item,
+        MinecraftItem::from_string("disk_drive").unwrap()
+    );
+}
@@ -6,8 +6,9 @@
 
 use std::{fmt::Display, str::FromStr};
 
+use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
 /// The world position of something in Minecraft.
@@ -153,6 +154,11 @@ impl CoordinatePosition {
     pub fn as_command_string(&self) -> String {
         format!("{} {} {}", self.x, self.y, self.z)
     }
+    /// How many moves it takes to get from one position to another, ignoring
+    /// anything in the way. Same as `helpers.taxicabDistance`.
+    pub const fn taxicab_distance(&self, other: CoordinatePosition) -> u64 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y) + self.z.abs_diff(other.z)
+    }
 }
 
 // ==
@@ -179,18 +185,21 @@ impl TryFrom<CoordinatePositionVariant> for CoordinatePosition {
     }
 }
 
+/// Capture groups for xyz, anchored on start and end so must match all 3.
+///
+/// Every key of every walkback goes through this, so it is only compiled once.
+static COORDINATE_KEY_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^x:(-?\d+)\|y:(-?\d+)\|z:(-?\d+)$").expect("This is valid regex."));
+
 /// Attempt to pull a coordinate position from a string. Errors with unit type if
 /// this is not a coordinate.
 impl std::str::FromStr for CoordinatePosition {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Capture groups for xyz, anchored on start and end so must match all 3
-        let regex = Regex::new(r"^x:(-?\d+)\|y:(-?\d+)\|z:(-?\d+)$").expect("This is valid regex.");
-
         // Captured?
         // If not, return early
-        let captures = regex.captures(s).ok_or("no capture")?;
+        let captures = COORDINATE_KEY_REGEX.captures(s).ok_or("no capture")?;
 
         // Got it, captures
         Ok(Self {
@@ -208,3 +217,743 @@ impl TryFrom<String> for CoordinatePosition {
         s.parse()
     }
 }
+
+// ==
+// Chunks, sections and regions
+// ==
+
+// Minecraft splits the world up a few ways:
+// - Chunks are 16x16 columns of blocks, going all the way up and down. They are
+//   what gets loaded and unloaded, see `forceload`.
+// - Sections are 16x16x16 cubes of blocks, stacked up to make a chunk.
+// - Regions are 32x32 chunks, and are what the world is saved in, one file each.
+//
+// Negative coordinates round towards negative infinity, so block -1 is in
+// chunk -1, not chunk 0.
+
+/// How many blocks wide a chunk is on the x and z axis. Sections are this many
+/// blocks tall too.
+pub const CHUNK_SIZE: i64 = 16;
+
+/// How many chunks wide a region is on the x and z axis.
+pub const REGION_SIZE: i64 = 32;
+
+/// How many chunks `forceload add` will load in one go.
+pub const FORCELOAD_LIMIT: usize = 256;
+
+/// Which chunk a block is in, counted in chunks instead of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChunkPosition {
+    pub x: i64,
+    pub z: i64,
+}
+
+impl ChunkPosition {
+    /// The chunk a block is in.
+    pub const fn containing(position: CoordinatePosition) -> Self {
+        Self {
+            x: position.x.div_euclid(CHUNK_SIZE),
+            z: position.z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    /// The block in this chunk with the lowest x and z, at some height.
+    pub const fn min_block(&self, y: i64) -> CoordinatePosition {
+        CoordinatePosition {
+            x: self.x * CHUNK_SIZE,
+            y,
+            z: self.z * CHUNK_SIZE,
+        }
+    }
+
+    /// The block in this chunk with the highest x and z, at some height.
+    pub const fn max_block(&self, y: i64) -> CoordinatePosition {
+        CoordinatePosition {
+            x: self.x * CHUNK_SIZE + CHUNK_SIZE - 1,
+            y,
+            z: self.z * CHUNK_SIZE + CHUNK_SIZE - 1,
+        }
+    }
+
+    /// The region this chunk is saved in.
+    pub const fn region(&self) -> RegionPosition {
+        RegionPosition {
+            x: self.x.div_euclid(REGION_SIZE),
+            z: self.z.div_euclid(REGION_SIZE),
+        }
+    }
+
+    /// Every chunk an area touches, one row of x at a time. Height doesn't
+    /// matter, chunks go all the way up.
+    pub fn touched_by(area: Cuboid) -> impl Iterator<Item = ChunkPosition> {
+        let low = Self::containing(area.min());
+        let high = Self::containing(area.max());
+        (low.z..=high.z).flat_map(move |z| (low.x..=high.x).map(move |x| Self { x, z }))
+    }
+
+    /// Every section in this chunk between two heights, inclusive, bottom up.
+    pub fn sections(&self, min_y: i64, max_y: i64) -> impl Iterator<Item = SectionPosition> {
+        let (x, z) = (self.x, self.z);
+        let low = min_y.min(max_y).div_euclid(CHUNK_SIZE);
+        let high = min_y.max(max_y).div_euclid(CHUNK_SIZE);
+        (low..=high).map(move |y| SectionPosition { x, y, z })
+    }
+}
+
+/// Which section a block is in, counted in sections instead of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SectionPosition {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl SectionPosition {
+    /// The section a block is in.
+    pub const fn containing(position: CoordinatePosition) -> Self {
+        Self {
+            x: position.x.div_euclid(CHUNK_SIZE),
+            y: position.y.div_euclid(CHUNK_SIZE),
+            z: position.z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    /// Where a block is in its section, as an index going along x, then z,
+    /// then y. The same order Minecraft stores blocks in.
+    pub const fn index_of(position: CoordinatePosition) -> usize {
+        let x = position.x.rem_euclid(CHUNK_SIZE);
+        let y = position.y.rem_euclid(CHUNK_SIZE);
+        let z = position.z.rem_euclid(CHUNK_SIZE);
+        ((y * CHUNK_SIZE + z) * CHUNK_SIZE + x) as usize
+    }
+
+    /// The block at an index in this section. Opposite of
+    /// [SectionPosition::index_of].
+    pub const fn block_at(&self, index: usize) -> CoordinatePosition {
+        let index = index as i64;
+        CoordinatePosition {
+            x: self.x * CHUNK_SIZE + index % CHUNK_SIZE,
+            y: self.y * CHUNK_SIZE + index / (CHUNK_SIZE * CHUNK_SIZE),
+            z: self.z * CHUNK_SIZE + (index / CHUNK_SIZE) % CHUNK_SIZE,
+        }
+    }
+
+    /// The block in this section with the lowest x, y and z.
+    pub const fn min_block(&self) -> CoordinatePosition {
+        self.block_at(0)
+    }
+
+    /// The block in this section with the highest x, y and z.
+    pub const fn max_block(&self) -> CoordinatePosition {
+        self.block_at((CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize - 1)
+    }
+
+    /// The chunk this section is a part of.
+    pub const fn chunk(&self) -> ChunkPosition {
+        ChunkPosition {
+            x: self.x,
+            z: self.z,
+        }
+    }
+
+    /// Every section an area touches, one row of x at a time, then one layer
+    /// of y at a time.
+    pub fn touched_by(area: Cuboid) -> impl Iterator<Item = SectionPosition> {
+        let low = area.min().y.div_euclid(CHUNK_SIZE);
+        let high = area.max().y.div_euclid(CHUNK_SIZE);
+        (low..=high).flat_map(move |y| {
+            ChunkPosition::touched_by(area).map(move |chunk| SectionPosition {
+                x: chunk.x,
+                y,
+                z: chunk.z,
+            })
+        })
+    }
+}
+
+/// Which region a block is in, counted in regions instead of blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RegionPosition {
+    pub x: i64,
+    pub z: i64,
+}
+
+impl RegionPosition {
+    /// The region a block is in.
+    pub const fn containing(position: CoordinatePosition) -> Self {
+        ChunkPosition::containing(position).region()
+    }
+
+    /// The chunk in this region with the lowest x and z.
+    pub const fn min_chunk(&self) -> ChunkPosition {
+        ChunkPosition {
+            x: self.x * REGION_SIZE,
+            z: self.z * REGION_SIZE,
+        }
+    }
+
+    /// Every chunk in this region, one row of x at a time.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkPosition> {
+        let low = self.min_chunk();
+        (low.z..low.z + REGION_SIZE)
+            .flat_map(move |z| (low.x..low.x + REGION_SIZE).map(move |x| ChunkPosition { x, z }))
+    }
+
+    /// The name of the file Minecraft saves this region in, IE `r.-1.0.mca`.
+    pub fn file_name(&self) -> String {
+        format!("r.{}.{}.mca", self.x, self.z)
+    }
+}
+
+impl From<ChunkPosition> for RegionPosition {
+    fn from(chunk: ChunkPosition) -> Self {
+        chunk.region()
+    }
+}
+
+impl From<SectionPosition> for ChunkPosition {
+    fn from(section: SectionPosition) -> Self {
+        section.chunk()
+    }
+}
+
+// ==
+// Cuboids
+// ==
+
+/// How many blocks a single `fill` command can change.
+pub const FILL_LIMIT: u64 = 32768;
+
+/// The order to go over the blocks of a [Cuboid] in. The first axis changes
+/// the fastest, IE [AxisOrder::Xzy] goes along x, then steps along z, then
+/// steps up to the next layer of y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl AxisOrder {
+    /// Which axis (0 for x, 1 for y, 2 for z) is walked at each level, fastest
+    /// first.
+    const fn axes(self) -> [usize; 3] {
+        match self {
+            AxisOrder::Xyz => [0, 1, 2],
+            AxisOrder::Xzy => [0, 2, 1],
+            AxisOrder::Yxz => [1, 0, 2],
+            AxisOrder::Yzx => [1, 2, 0],
+            AxisOrder::Zxy => [2, 0, 1],
+            AxisOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
+
+/// A box of blocks, including both of its corners. Always at least one block
+/// big.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cuboid {
+    /// The corner with the lowest x, y and z.
+    min: CoordinatePosition,
+    /// The corner with the highest x, y and z.
+    max: CoordinatePosition,
+}
+
+impl Cuboid {
+    /// The box between two opposite corners, given in any order.
+    pub const fn new(corner: CoordinatePosition, other: CoordinatePosition) -> Self {
+        Self {
+            min: CoordinatePosition {
+                x: lowest(corner.x, other.x),
+                y: lowest(corner.y, other.y),
+                z: lowest(corner.z, other.z),
+            },
+            max: CoordinatePosition {
+                x: highest(corner.x, other.x),
+                y: highest(corner.y, other.y),
+                z: highest(corner.z, other.z),
+            },
+        }
+    }
+
+    /// Just one block.
+    pub const fn single(position: CoordinatePosition) -> Self {
+        Self {
+            min: position,
+            max: position,
+        }
+    }
+
+    /// A box growing from a corner towards positive x, y and z, this many
+    /// blocks long on each axis. A size of zero is taken as one.
+    pub const fn from_size(corner: CoordinatePosition, x: u64, y: u64, z: u64) -> Self {
+        Self {
+            min: corner,
+            max: CoordinatePosition {
+                x: corner.x.saturating_add_unsigned(x.saturating_sub(1)),
+                y: corner.y.saturating_add_unsigned(y.saturating_sub(1)),
+                z: corner.z.saturating_add_unsigned(z.saturating_sub(1)),
+            },
+        }
+    }
+
+    /// The corner with the lowest x, y and z.
+    pub const fn min(&self) -> CoordinatePosition {
+        self.min
+    }
+
+    /// The corner with the highest x, y and z.
+    pub const fn max(&self) -> CoordinatePosition {
+        self.max
+    }
+
+    /// How many blocks long the box is on the x, y and z axis. Saturates
+    /// instead of overflowing for ridiculously big boxes.
+    pub const fn size(&self) -> [u64; 3] {
+        [
+            self.max.x.abs_diff(self.min.x).saturating_add(1),
+            self.max.y.abs_diff(self.min.y).saturating_add(1),
+            self.max.z.abs_diff(self.min.z).saturating_add(1),
+        ]
+    }
+
+    /// How many blocks are in the box. Saturates like [Cuboid::size].
+    pub const fn volume(&self) -> u64 {
+        let [x, y, z] = self.size();
+        x.saturating_mul(y).saturating_mul(z)
+    }
+
+    /// Wether a block is inside the box.
+    pub const fn contains(&self, position: CoordinatePosition) -> bool {
+        self.min.x <= position.x
+            && position.x <= self.max.x
+            && self.min.y <= position.y
+            && position.y <= self.max.y
+            && self.min.z <= position.z
+            && position.z <= self.max.z
+    }
+
+    /// Wether the boxes share at least one block.
+    pub const fn intersects(&self, other: &Cuboid) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+            && self.min.z <= other.max.z
+            && other.min.z <= self.max.z
+    }
+
+    /// The blocks both boxes share, if any.
+    pub const fn intersection(&self, other: &Cuboid) -> Option<Cuboid> {
+        if !self.intersects(other) {
+            return None;
+        }
+        Some(Self {
+            min: CoordinatePosition {
+                x: highest(self.min.x, other.min.x),
+                y: highest(self.min.y, other.min.y),
+                z: highest(self.min.z, other.min.z),
+            },
+            max: CoordinatePosition {
+                x: lowest(self.max.x, other.max.x),
+                y: lowest(self.max.y, other.max.y),
+                z: lowest(self.max.z, other.max.z),
+            },
+        })
+    }
+
+    /// The smallest box holding both boxes.
+    pub const fn union(&self, other: &Cuboid) -> Cuboid {
+        Self {
+            min: CoordinatePosition {
+                x: lowest(self.min.x, other.min.x),
+                y: lowest(self.min.y, other.min.y),
+                z: lowest(self.min.z, other.min.z),
+            },
+            max: CoordinatePosition {
+                x: highest(self.max.x, other.max.x),
+                y: highest(self.max.y, other.max.y),
+                z: highest(self.max.z, other.max.z),
+            },
+        }
+    }
+
+    /// Grow the box by this many blocks on every side.
+    pub const fn expand(&self, by: u64) -> Cuboid {
+        Self {
+            min: CoordinatePosition {
+                x: self.min.x.saturating_sub_unsigned(by),
+                y: self.min.y.saturating_sub_unsigned(by),
+                z: self.min.z.saturating_sub_unsigned(by),
+            },
+            max: CoordinatePosition {
+                x: self.max.x.saturating_add_unsigned(by),
+                y: self.max.y.saturating_add_unsigned(by),
+                z: self.max.z.saturating_add_unsigned(by),
+            },
+        }
+    }
+
+    /// Move the whole box.
+    pub const fn with_offset(&self, offset: CoordinatePosition) -> Cuboid {
+        Self {
+            min: self.min.with_offset(offset),
+            max: self.max.with_offset(offset),
+        }
+    }
+
+    /// Every block in the box, in some order.
+    pub fn blocks(&self, order: AxisOrder) -> impl Iterator<Item = CoordinatePosition> + use<> {
+        let [first, second, third] = order.axes();
+        let (low, high) = (axes_of(self.min), axes_of(self.max));
+        (low[third]..=high[third]).flat_map(move |c| {
+            (low[second]..=high[second]).flat_map(move |b| {
+                (low[first]..=high[first]).map(move |a| {
+                    let mut position = [0; 3];
+                    position[first] = a;
+                    position[second] = b;
+                    position[third] = c;
+                    let [x, y, z] = position;
+                    CoordinatePosition { x, y, z }
+                })
+            })
+        })
+    }
+
+    /// Split the box into pieces small enough for a single `fill` command,
+    /// see [FILL_LIMIT]. The pieces cover the whole box without overlapping.
+    pub fn fill_pieces(&self) -> impl Iterator<Item = Cuboid> + use<> {
+        // Pieces take up whole rows of x if they can, then whole layers of xz.
+        let [size_x, size_y, size_z] = self.size();
+        let piece_x = size_x.min(FILL_LIMIT);
+        let piece_z = size_z.min(FILL_LIMIT / piece_x);
+        let piece_y = size_y.min(FILL_LIMIT / (piece_x * piece_z));
+        let (min, max) = (self.min, self.max);
+        let step = |low: i64, high: i64, length: u64| {
+            (low..=high)
+                .step_by(length as usize)
+                .map(move |start| (start, start.saturating_add_unsigned(length - 1).min(high)))
+        };
+        step(min.y, max.y, piece_y).flat_map(move |(low_y, high_y)| {
+            step(min.z, max.z, piece_z).flat_map(move |(low_z, high_z)| {
+                step(min.x, max.x, piece_x).map(move |(low_x, high_x)| Cuboid {
+                    min: CoordinatePosition {
+                        x: low_x,
+                        y: low_y,
+                        z: low_z,
+                    },
+                    max: CoordinatePosition {
+                        x: high_x,
+                        y: high_y,
+                        z: high_z,
+                    },
+                })
+            })
+        })
+    }
+
+    /// Both corners as a string, used in commands (no commas).
+    pub fn as_command_string(&self) -> String {
+        format!(
+            "{} {}",
+            self.min.as_command_string(),
+            self.max.as_command_string()
+        )
+    }
+}
+
+/// Positions as arrays, so axes can be picked by index.
+const fn axes_of(position: CoordinatePosition) -> [i64; 3] {
+    [position.x, position.y, position.z]
+}
+
+/// `Ord::min` and `Ord::max` aren't const.
+const fn lowest(a: i64, b: i64) -> i64 {
+    if a < b { a } else { b }
+}
+
+const fn highest(a: i64, b: i64) -> i64 {
+    if a > b { a } else { b }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Negative blocks should land in negative chunks, sections and regions.
+fn negative_floor_division() {
+    let position = CoordinatePosition {
+        x: -1,
+        y: -64,
+        z: 17,
+    };
+    assert_eq!(
+        ChunkPosition::containing(position),
+        ChunkPosition { x: -1, z: 1 }
+    );
+    let section = SectionPosition::containing(position);
+    assert_eq!(section, SectionPosition { x: -1, y: -4, z: 1 });
+    assert_eq!(section.chunk(), ChunkPosition::containing(position));
+    assert_eq!(
+        RegionPosition::containing(position),
+        RegionPosition { x: -1, z: 0 }
+    );
+    assert_eq!(
+        RegionPosition::containing(position).file_name(),
+        "r.-1.0.mca"
+    );
+
+    // Right on the edges.
+    let edge = CoordinatePosition {
+        x: -16,
+        y: 0,
+        z: -17,
+    };
+    assert_eq!(
+        ChunkPosition::containing(edge),
+        ChunkPosition { x: -1, z: -2 }
+    );
+    let far = CoordinatePosition {
+        x: -512,
+        y: 0,
+        z: -513,
+    };
+    assert_eq!(
+        RegionPosition::containing(far),
+        RegionPosition { x: -1, z: -2 }
+    );
+    let chunk = ChunkPosition { x: -1, z: 0 };
+    assert_eq!(
+        chunk.min_block(5),
+        CoordinatePosition { x: -16, y: 5, z: 0 }
+    );
+    assert_eq!(
+        chunk.max_block(5),
+        CoordinatePosition { x: -1, y: 5, z: 15 }
+    );
+    assert!(
+        RegionPosition { x: -1, z: 0 }
+            .chunks()
+            .all(|chunk| chunk.region() == RegionPosition { x: -1, z: 0 })
+    );
+    assert_eq!(RegionPosition { x: 0, z: 0 }.chunks().count(), 1024);
+}
+
+#[test]
+/// Indexes should go back to the same block.
+fn section_positions() {
+    let position = CoordinatePosition { x: -1, y: 0, z: 17 };
+    let section = SectionPosition::containing(position);
+    assert_eq!(section, SectionPosition { x: -1, y: 0, z: 1 });
+    let index = SectionPosition::index_of(position);
+    assert_eq!(section.block_at(index), position);
+    assert_eq!(
+        section.min_block(),
+        CoordinatePosition {
+            x: -16,
+            y: 0,
+            z: 16
+        }
+    );
+    assert_eq!(
+        section.max_block(),
+        CoordinatePosition {
+            x: -1,
+            y: 15,
+            z: 31
+        }
+    );
+
+    for index in [0, 1, 15, 16, 255, 256, 4095] {
+        let block = section.block_at(index);
+        assert_eq!(SectionPosition::containing(block), section);
+        assert_eq!(SectionPosition::index_of(block), index);
+    }
+}
+
+#[test]
+/// An area should touch every chunk and section it overlaps, and nothing else.
+fn chunks_touched_by_an_area() {
+    let corner = CoordinatePosition { x: 5, y: 20, z: -1 };
+    let other = CoordinatePosition {
+        x: -17,
+        y: -1,
+        z: 0,
+    };
+    let area = Cuboid::new(corner, other);
+    let chunks: Vec<ChunkPosition> = ChunkPosition::touched_by(area).collect();
+    assert_eq!(
+        chunks,
+        [
+            ChunkPosition { x: -2, z: -1 },
+            ChunkPosition { x: -1, z: -1 },
+            ChunkPosition { x: 0, z: -1 },
+            ChunkPosition { x: -2, z: 0 },
+            ChunkPosition { x: -1, z: 0 },
+            ChunkPosition { x: 0, z: 0 },
+        ]
+    );
+    // A single block.
+    assert_eq!(ChunkPosition::touched_by(Cuboid::single(corner)).count(), 1);
+
+    let sections: Vec<SectionPosition> = SectionPosition::touched_by(area).collect();
+    // Three layers, -1, 0 and 1.
+    assert_eq!(sections.len(), chunks.len() * 3);
+    assert_eq!(
+        sections[0],
+        SectionPosition {
+            x: -2,
+            y: -1,
+            z: -1
+        }
+    );
+    assert!(
+        sections
+            .iter()
+            .all(|section| chunks.contains(&section.chunk()))
+    );
+    assert_eq!(
+        ChunkPosition { x: 0, z: 0 }
+            .sections(-1, 16)
+            .collect::<Vec<_>>(),
+        [
+            SectionPosition { x: 0, y: -1, z: 0 },
+            SectionPosition { x: 0, y: 0, z: 0 },
+            SectionPosition { x: 0, y: 1, z: 0 },
+        ]
+    );
+}
+
+#[test]
+/// Cuboids should be the same no matter which corners they are made from.
+fn cuboid_shapes() {
+    let area = Cuboid::new(
+        CoordinatePosition { x: 3, y: -2, z: 0 },
+        CoordinatePosition { x: -1, y: 2, z: 1 },
+    );
+    assert_eq!(area.min(), CoordinatePosition { x: -1, y: -2, z: 0 });
+    assert_eq!(area.max(), CoordinatePosition { x: 3, y: 2, z: 1 });
+    assert_eq!(area, Cuboid::new(area.max(), area.min()));
+    assert_eq!(area, Cuboid::from_size(area.min(), 5, 5, 2));
+    assert_eq!(area.size(), [5, 5, 2]);
+    assert_eq!(area.volume(), 50);
+    assert_eq!(Cuboid::single(area.min()).volume(), 1);
+    assert_eq!(Cuboid::from_size(area.min(), 0, 0, 0).volume(), 1);
+
+    assert!(area.contains(CoordinatePosition { x: 3, y: -2, z: 1 }));
+    assert!(!area.contains(CoordinatePosition { x: 4, y: 0, z: 0 }));
+
+    let other = Cuboid::new(
+        CoordinatePosition { x: 3, y: 2, z: 1 },
+        CoordinatePosition {
+            x: 10,
+            y: 10,
+            z: 10,
+        },
+    );
+    assert!(area.intersects(&other));
+    assert_eq!(area.intersection(&other), Some(Cuboid::single(area.max())));
+    let away = other.with_offset(CoordinatePosition { x: 1, y: 0, z: 0 });
+    assert!(!area.intersects(&away));
+    assert_eq!(area.intersection(&away), None);
+    let both = area.union(&away);
+    assert_eq!(both.min(), area.min());
+    assert_eq!(both.max(), away.max());
+
+    let grown = area.expand(1);
+    assert_eq!(grown.size(), [7, 7, 4]);
+    assert!(grown.contains(CoordinatePosition {
+        x: -2,
+        y: -3,
+        z: -1
+    }));
+    assert_eq!(area.as_command_string(), "-1 -2 0 3 2 1");
+
+    // Nothing overflows at the edges of the world.
+    let everything = Cuboid::new(
+        CoordinatePosition {
+            x: i64::MIN,
+            y: i64::MIN,
+            z: i64::MIN,
+        },
+        CoordinatePosition {
+            x: i64::MAX,
+            y: i64::MAX,
+            z: i64::MAX,
+        },
+    );
+    assert_eq!(everything.volume(), u64::MAX);
+    assert_eq!(everything.expand(5), everything);
+}
+
+#[test]
+/// Blocks should come out in the asked for order, and fill pieces should cover
+/// the whole cuboid without going over the limit.
+fn cuboid_iteration() {
+    let area = Cuboid::from_size(CoordinatePosition { x: 0, y: 0, z: 0 }, 2, 2, 2);
+    let position = |x, y, z| CoordinatePosition { x, y, z };
+    let blocks: Vec<CoordinatePosition> = area.blocks(AxisOrder::Xzy).collect();
+    assert_eq!(
+        blocks[..4],
+        [
+            position(0, 0, 0),
+            position(1, 0, 0),
+            position(0, 0, 1),
+            position(1, 0, 1),
+        ]
+    );
+    // Same order as section indexes.
+    let section = SectionPosition { x: 0, y: 0, z: 0 };
+    let whole = Cuboid::new(section.min_block(), section.max_block());
+    assert!(
+        whole
+            .blocks(AxisOrder::Xzy)
+            .enumerate()
+            .all(|(index, block)| SectionPosition::index_of(block) == index)
+    );
+    let blocks: Vec<CoordinatePosition> = area.blocks(AxisOrder::Zyx).collect();
+    assert_eq!(
+        blocks[..3],
+        [position(0, 0, 0), position(0, 0, 1), position(0, 1, 0)]
+    );
+    for order in [
+        AxisOrder::Xyz,
+        AxisOrder::Xzy,
+        AxisOrder::Yxz,
+        AxisOrder::Yzx,
+        AxisOrder::Zxy,
+        AxisOrder::Zyx,
+    ] {
+        let blocks: std::collections::HashSet<CoordinatePosition> = area.blocks(order).collect();
+        assert_eq!(blocks.len(), 8);
+        assert!(blocks.iter().all(|block| area.contains(*block)));
+    }
+
+    // Small enough already.
+    assert_eq!(area.fill_pieces().collect::<Vec<_>>(), [area]);
+    for big in [
+        Cuboid::from_size(position(-5, -64, 7), 200, 100, 200),
+        Cuboid::from_size(position(0, 0, 0), 40_000, 1, 1),
+        Cuboid::from_size(position(0, 0, 0), 33, 33, 33),
+    ] {
+        let pieces: Vec<Cuboid> = big.fill_pieces().collect();
+        assert!(pieces.iter().all(|piece| piece.volume() <= FILL_LIMIT));
+        assert!(
+            pieces
+                .iter()
+                .all(|piece| big.intersection(piece) == Some(*piece))
+        );
+        assert_eq!(pieces.iter().map(Cuboid::volume).sum::<u64>(), big.volume());
+        for (index, piece) in pieces.iter().enumerate() {
+            assert!(
+                pieces[index + 1..]
+                    .iter()
+                    .all(|other| !piece.intersects(other))
+            );
+        }
+    }
+}
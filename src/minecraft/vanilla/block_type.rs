@@ -2,7 +2,7 @@
 use std::borrow::Cow;
 
 use mcdata_rs::{Block, BlockStateDefinition};
-use serde::{Deserialize, de::IgnoredAny}; // Import the trait!
+use serde::{Deserialize, Deserializer, Serialize}; // Import the trait!
 
 use crate::minecraft::types::CoordinatePosition;
 use crate::minecraft::{
@@ -41,6 +41,9 @@ pub struct PositionedMinecraftBlock {
     block: MinecraftBlock,
     /// The position that this block is at.
     position: CoordinatePosition,
+    /// The tags the lua side thought were worth sending, see `block.lua`.
+    /// Always empty for blocks sent in the compact encoding.
+    tags: Vec<String>,
 }
 
 impl HasMinecraftBlock for PositionedMinecraftBlock {
@@ -132,18 +135,25 @@ pub struct LuaBlock {
     name: String,
     pos: CoordinatePosition,
     state: Option<LuaMinecraftBlockState>,
-    // We can completely ignore tags, as they are static and aren't dynamically
-    // added to blocks
-    #[serde(skip)]
-    #[serde(rename = "tag")]
-    _tags: IgnoredAny,
+    // Tags are static and aren't dynamically added to blocks, so these are only
+    // kept around for the world model to learn which blocks have which tags.
+    #[serde(default, rename = "tag", deserialize_with = "null_as_empty")]
+    tags: Vec<String>,
+}
+
+fn null_as_empty<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // Empty tables come across as null.
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
 }
 
 /// We need to be able to convert back and forth between the basic lua block
 /// states that we store on the lua side, and the cool rust one.
 /// These are the only states we really care about anyways.
-#[derive(Debug, Deserialize)]
-struct LuaMinecraftBlockState {
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct LuaMinecraftBlockState {
     pub age: Option<u32>,
     pub eye: Option<bool>,
     pub honey_level: Option<u32>,
@@ -230,21 +240,82 @@ impl TryFrom<LuaBlock> for PositionedMinecraftBlock {
     type Error = String;
 
     fn try_from(value: LuaBlock) -> Result<Self, Self::Error> {
-        let mut unconfigured = MinecraftBlock::from_string(&value.name).ok_or("Unknown block")?;
+        let block =
+            MinecraftBlock::from_lua_state(&value.name, value.state).ok_or("Unknown block")?;
+        Ok(PositionedMinecraftBlock::new(block, value.pos).with_tags(value.tags))
+    }
+}
+
+/// Pull the states we care about back out of a block.
+impl From<&[BlockStateDefinition]> for LuaMinecraftBlockState {
+    fn from(definitions: &[BlockStateDefinition]) -> Self {
+        let mut state = LuaMinecraftBlockState::default();
+        for definition in definitions {
+            let Some(value) = definition.num_values else {
+                continue;
+            };
+            match definition.name.as_str() {
+                "age" => state.age = Some(value),
+                "eye" => state.eye = Some(value != 0),
+                "honey_level" => state.honey_level = Some(value),
+                "level" => state.level = Some(value),
+                "lit" => state.lit = Some(value != 0),
+                "stage" => state.stage = Some(value),
+                _ => {}
+            }
+        }
+        state
+    }
+}
+
+impl MinecraftBlock {
+    /// Look up a block by name, and give it the states from the lua side.
+    pub(crate) fn from_lua_state(
+        name: &str,
+        state: Option<LuaMinecraftBlockState>,
+    ) -> Option<Self> {
+        let mut unconfigured = MinecraftBlock::from_string(name)?;
         // TODO: I have no idea if this is gonna work lmao.
-        let new_states: Vec<BlockStateDefinition> = if let Some(lua_states) = value.state {
-            lua_states.into()
-        } else {
-            Vec::new()
-        };
+        unconfigured.block.states = state.map(Into::into).unwrap_or_default();
+        Some(unconfigured)
+    }
 
-        unconfigured.block.states = new_states;
-        let done = PositionedMinecraftBlock {
-            block: unconfigured,
-            position: value.pos,
-        };
+    /// The states this block has, in the format the lua side uses.
+    pub(crate) fn lua_state(&self) -> LuaMinecraftBlockState {
+        LuaMinecraftBlockState::from(self.block.states.as_slice())
+    }
+}
+
+impl PositionedMinecraftBlock {
+    pub fn new(block: MinecraftBlock, position: CoordinatePosition) -> Self {
+        Self {
+            block,
+            position,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Attach the tags the lua side sent along with this block.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// The block, without its position.
+    pub fn block(&self) -> &MinecraftBlock {
+        &self.block
+    }
+
+    /// Where this block is.
+    pub fn position(&self) -> CoordinatePosition {
+        self.position
+    }
 
-        Ok(done)
+    /// The tags sent along with this block, IE `minecraft:logs`. Only the ones
+    /// `block.lua` thinks are important are ever sent, and none are sent in
+    /// the compact encoding.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
     }
 }
 
@@ -2,7 +2,8 @@
 // This is our base type for every kind of item in Minecraft, this can also be cast to MinecraftBlock (TODO: if i ever do that or need it)
 
 use mcdata_rs::Item;
-use std::borrow::Cow;
+use serde::de::Error;
+use std::{borrow::Cow, hash::Hash};
 
 use crate::minecraft::{
     computercraft::modded_data::get_modded_data, vanilla::data_globals::get_mc_data,
@@ -20,9 +21,20 @@ pub struct MinecraftItem {
 }
 
 // Implementation of equality
+// Item IDs are unique, even across vanilla and modded items, since modded IDs
+// have the top bit set. See `modded_items.rs`.
 impl PartialEq for MinecraftItem {
-    fn eq(&self, _other: &Self) -> bool {
-        todo!("Item equality checks.")
+    fn eq(&self, other: &Self) -> bool {
+        self.item.id == other.item.id
+    }
+}
+
+impl Eq for MinecraftItem {}
+
+// Must agree with `PartialEq`.
+impl Hash for MinecraftItem {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.item.id.hash(state);
     }
 }
 
@@ -63,44 +75,79 @@ impl MinecraftItem {
     }
     /// Attempt to get an item from a item name.
     ///
-    /// This does not work with modded items, such as computercraft floppies.
-    /// //TODO: can this be fixed?
-    pub fn from_string<T: AsRef<str> + std::hash::Hash + std::cmp::Eq + ?Sized>(
-        name: &T,
-    ) -> Option<Self>
-    where
-        std::string::String: std::borrow::Borrow<T>,
-    {
-        // this function has a lot of trait bounds to let us more easily pass in &str when making items.
+    /// Names may be namespaced, IE `minecraft:coal` or `computercraft:disk_drive`,
+    /// in which case only that namespace is searched. Un-namespaced names try
+    /// vanilla first, then modded.
+    pub fn from_string<T: AsRef<str> + ?Sized>(name: &T) -> Option<Self> {
+        // this function is generic to let us more easily pass in &str when making items.
         // this lets us do
         // MinecraftItem::from_string("gold_block").unwrap()
         // instead of
         // MinecraftItem::from_string(&"gold_block".into()).unwrap(),
+        let name = name.as_ref();
+        let vanilla = || get_mc_data().items_by_name.get(name_only(name));
+        let modded = || get_modded_data().items_by_name.get(name_only(name));
 
-        // first we try normal minecraft data, then we try modded data, since minecraft
-        // data will be WAY more common.
-
-        if let Some(item) = get_mc_data().items_by_name.get(name) {
-            Some(Self { item })
-        } else {
-            // try getting the modded one, if its still not there, just return none.
-            get_modded_data()
-                .items_by_name
-                .get(name)
-                .map(|item| Self { item })
-        }
+        let item = match name.split_once(':') {
+            Some(("minecraft", _)) => vanilla(),
+            // we assume the only mod is computercraft for obvious reasons.
+            Some(("computercraft", _)) => modded(),
+            Some(_) => None,
+            // first we try normal minecraft data, then we try modded data, since minecraft
+            // data will be WAY more common.
+            None => vanilla().or_else(modded),
+        }?;
+        Some(Self { item })
     }
 }
 
+/// Strip the namespace off of a name, if it has one.
+fn name_only(name: &str) -> &str {
+    name.split_once(':').map_or(name, |(_, name)| name)
+}
+
 // ======
 // Deserialization
 // ======
 
+// Items come in as their full names, IE `minecraft:coal`.
 impl<'de> serde::de::Deserialize<'de> for MinecraftItem {
-    fn deserialize<D>(_deserializer: D) -> Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        todo!("Implement minecraft item deserializer.")
+        let name: Cow<'de, str> = Cow::deserialize(deserializer)?;
+        MinecraftItem::from_string(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown item `{name}`")))
     }
 }
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Names should resolve in the right namespace, and equal items should hash
+/// the same.
+fn item_lookup() {
+    use std::collections::HashSet;
+
+    let coal = MinecraftItem::from_string("minecraft:coal").unwrap();
+    assert_eq!(coal, MinecraftItem::from_string("coal").unwrap());
+    assert_ne!(coal, MinecraftItem::from_string("stone").unwrap());
+
+    let drive = MinecraftItem::from_string("computercraft:disk_drive").unwrap();
+    assert!(drive.is_modded());
+    assert_eq!(drive.get_full_name(), "computercraft:disk_drive");
+    assert_eq!(drive, MinecraftItem::from_string("disk_drive").unwrap());
+    assert!(MinecraftItem::from_string("minecraft:disk_drive").is_none());
+    assert!(MinecraftItem::from_string("othermod:coal").is_none());
+
+    let unique: HashSet<MinecraftItem> = [coal, drive, coal].into_iter().collect();
+    assert_eq!(unique.len(), 2);
+
+    let parsed: Vec<MinecraftItem> =
+        serde_json::from_str(r#"["minecraft:coal", "computercraft:disk_drive"]"#).unwrap();
+    assert_eq!(parsed, vec![coal, drive]);
+    assert!(serde_json::from_str::<MinecraftItem>(r#""minecraft:not_an_item""#).is_err());
+}
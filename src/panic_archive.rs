@@ -0,0 +1,387 @@
+// Every panic any turtle has ever sent us, kept on disk.
+//
+// Panics are appended to a single json lines file as they come in, and read
+// back in when the archive is opened. Panics that happened in the same place
+// are grouped together by their signature, so after a long run with a lot of
+// turtles you get a short list of distinct crashes instead of a wall of them.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::minecraft::computercraft::computer_types::{
+    cc_panic::{LuaPanic, PanicReport, parse_traceback},
+    packet_types::PanicPacket,
+};
+
+/// The file panics are kept in, inside of the archive's folder.
+const ARCHIVE_FILE_NAME: &str = "panics.jsonl";
+
+/// Panics with the same signature happened in the same place, for the same
+/// reason. See [PanicSignature::of].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PanicSignature(u64);
+
+impl PanicSignature {
+    /// Work out the signature of a stack trace.
+    pub fn of(stack_trace: &str) -> Self {
+        Self(fnv1a(normalize_trace(stack_trace).as_bytes()))
+    }
+}
+
+impl Display for PanicSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Numbers and table addresses that change from panic to panic.
+static VOLATILE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"0x[0-9a-fA-F]+|-?\d+(?:\.\d+)?").expect("Valid regex"));
+
+/// Strip everything out of a stack trace that differs between two panics that
+/// are really the same, IE positions and table addresses in the message.
+///
+/// Line numbers in the frames are kept, since that is where the panic came from.
+pub fn normalize_trace(stack_trace: &str) -> String {
+    let (message, frames) = parse_traceback(stack_trace);
+    let first_line = message.lines().next().unwrap_or_default();
+    let mut normalized = VOLATILE_REGEX.replace_all(first_line, "#").into_owned();
+    for frame in frames {
+        normalized.push('\n');
+        normalized.push_str(&frame.to_string());
+    }
+    normalized
+}
+
+/// 64 bit FNV-1a. The signatures need to stay the same between runs, which the
+/// std hashers don't promise.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A single panic, as written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanicRecord {
+    /// The computer that panicked.
+    pub computer_id: u16,
+    /// When it panicked, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    /// The UUID of the panic packet.
+    pub uuid: String,
+    pub panic: LuaPanic,
+}
+
+impl PanicRecord {
+    pub fn signature(&self) -> PanicSignature {
+        PanicSignature::of(&self.panic.stack_trace)
+    }
+
+    /// Make sense of the panic.
+    pub fn report(&self) -> PanicReport {
+        self.panic.clone().report()
+    }
+}
+
+impl From<&PanicPacket> for PanicRecord {
+    fn from(packet: &PanicPacket) -> Self {
+        Self {
+            computer_id: packet.from,
+            timestamp: packet.timestamp,
+            uuid: packet.uuid.clone(),
+            panic: packet.panic_data.clone(),
+        }
+    }
+}
+
+/// Every panic that shares a signature, rolled up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicSummary {
+    pub signature: PanicSignature,
+    /// The message of the most recent panic.
+    pub message: String,
+    /// How many times it happened.
+    pub count: usize,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Every computer it happened on.
+    pub computers: BTreeSet<u16>,
+}
+
+/// Every panic we know of. See the top of this file.
+#[derive(Debug)]
+pub struct PanicArchive {
+    path: PathBuf,
+    /// In the order they were recorded.
+    records: Vec<(PanicSignature, PanicRecord)>,
+    /// `(computer, packet uuid)`, so retried packets are only kept once.
+    seen: HashSet<(u16, String)>,
+}
+
+impl PanicArchive {
+    /// Open the archive in a folder, creating it if needed.
+    ///
+    /// Lines that can't be read back are skipped with a warning, instead of
+    /// losing the whole archive.
+    pub fn open(folder: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&folder)?;
+        let path = folder.as_ref().join(ARCHIVE_FILE_NAME);
+        let mut archive = Self {
+            path,
+            records: Vec::new(),
+            seen: HashSet::new(),
+        };
+
+        let file = match File::open(&archive.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(archive),
+            Err(err) => return Err(err),
+        };
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<PanicRecord>(&line) {
+                Ok(record) => archive.insert(record),
+                Err(err) => warn!(
+                    "Skipping line {} of {}, it is not a panic! {err}",
+                    index + 1,
+                    archive.path.display()
+                ),
+            };
+        }
+        Ok(archive)
+    }
+
+    /// Where the panics are written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep a panic, writing it to disk. Returns its signature, or None if we
+    /// already had this exact packet.
+    pub fn record(
+        &mut self,
+        record: impl Into<PanicRecord>,
+    ) -> std::io::Result<Option<PanicSignature>> {
+        let record = record.into();
+        if self
+            .seen
+            .contains(&(record.computer_id, record.uuid.clone()))
+        {
+            return Ok(None);
+        }
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+
+        let signature = record.signature();
+        self.insert(record);
+        Ok(Some(signature))
+    }
+
+    fn insert(&mut self, record: PanicRecord) {
+        if self.seen.insert((record.computer_id, record.uuid.clone())) {
+            self.records.push((record.signature(), record));
+        }
+    }
+
+    /// How many panics there are, duplicates and all.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Every panic, in the order they came in.
+    pub fn records(&self) -> impl Iterator<Item = &PanicRecord> {
+        self.records.iter().map(|(_, record)| record)
+    }
+
+    /// Every panic a computer has had, oldest first.
+    pub fn for_computer(&self, computer_id: u16) -> Vec<&PanicRecord> {
+        let mut records: Vec<&PanicRecord> = self
+            .records()
+            .filter(|record| record.computer_id == computer_id)
+            .collect();
+        records.sort_by_key(|record| record.timestamp);
+        records
+    }
+
+    /// Every time a panic happened, oldest first.
+    pub fn occurrences(&self, signature: PanicSignature) -> Vec<&PanicRecord> {
+        let mut records: Vec<&PanicRecord> = self
+            .records
+            .iter()
+            .filter(|(other, _)| *other == signature)
+            .map(|(_, record)| record)
+            .collect();
+        records.sort_by_key(|record| record.timestamp);
+        records
+    }
+
+    /// Every distinct panic, most common first.
+    pub fn summaries(&self) -> Vec<PanicSummary> {
+        self.top(0, usize::MAX)
+    }
+
+    /// The most common panics since a point in time (in milliseconds since the
+    /// unix epoch), IE `top(current_timestamp() - WEEK, 10)`. Ties go to
+    /// whichever happened most recently.
+    pub fn top(&self, since: u64, limit: usize) -> Vec<PanicSummary> {
+        let mut summaries: HashMap<PanicSignature, PanicSummary> = HashMap::new();
+        for (signature, record) in &self.records {
+            if record.timestamp < since {
+                continue;
+            }
+            let summary = summaries.entry(*signature).or_insert_with(|| PanicSummary {
+                signature: *signature,
+                message: String::new(),
+                count: 0,
+                first_seen: record.timestamp,
+                last_seen: 0,
+                computers: BTreeSet::new(),
+            });
+            summary.count += 1;
+            summary.first_seen = summary.first_seen.min(record.timestamp);
+            if record.timestamp >= summary.last_seen {
+                summary.last_seen = record.timestamp;
+                summary.message = parse_traceback(&record.panic.stack_trace).0;
+            }
+            summary.computers.insert(record.computer_id);
+        }
+
+        let mut summaries: Vec<PanicSummary> = summaries.into_values().collect();
+        summaries.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then(a.signature.cmp(&b.signature))
+        });
+        summaries.truncate(limit);
+        summaries
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::computercraft::computer_types::packet_types::{
+    RawTurtlePacket, TurtlePacket,
+};
+
+#[cfg(test)]
+fn test_panic(
+    computer_id: u16,
+    uuid: &str,
+    timestamp: u64,
+    message: &str,
+    line: u32,
+) -> PanicPacket {
+    let trace = format!(
+        "{message}\nstack traceback:\n\t[C]: in function 'error'\n\tmesh_os.lua:{line}: in function 'step'"
+    );
+    let json = serde_json::json!({
+        "id": computer_id,
+        "uuid": uuid,
+        "timestamp": timestamp,
+        "packet_type": "panic",
+        "data": {"stack_trace": trace, "locals": {"pairs": null}, "up_values": {"pairs": null}},
+    });
+    let raw: RawTurtlePacket = serde_json::from_value(json).unwrap();
+    match raw.decode().unwrap() {
+        TurtlePacket::Panic(packet) => packet,
+        _ => panic!("Not a panic packet!"),
+    }
+}
+
+#[test]
+/// Panics should be grouped, counted, queried, and survive a reopen.
+fn archiving_panics() {
+    let folder = std::env::temp_dir().join(format!("meshpit_panics_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    let mut archive = PanicArchive::open(&folder).unwrap();
+
+    // Same place, different positions in the message.
+    let stuck = archive
+        .record(&test_panic(12, "AAAAAAAA", 1_000, "Stuck at 1, 2, 3", 40))
+        .unwrap()
+        .unwrap();
+    let again = archive
+        .record(&test_panic(7, "BBBBBBBB", 2_000, "Stuck at 4, -5, 6", 40))
+        .unwrap();
+    assert_eq!(again, Some(stuck));
+    assert_eq!(
+        archive
+            .record(&test_panic(12, "CCCCCCCC", 3_000, "Stuck at 7, 8, 9", 40))
+            .unwrap(),
+        Some(stuck)
+    );
+    // A retried packet is only kept once.
+    assert_eq!(
+        archive
+            .record(&test_panic(12, "CCCCCCCC", 3_000, "Stuck at 7, 8, 9", 40))
+            .unwrap(),
+        None
+    );
+    // Somewhere else.
+    let fuel = archive
+        .record(&test_panic(12, "DDDDDDDD", 4_000, "Out of fuel", 99))
+        .unwrap()
+        .unwrap();
+    assert_ne!(fuel, stuck);
+
+    let check = |archive: &PanicArchive| {
+        assert_eq!(archive.len(), 4);
+        let top = archive.summaries();
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].signature, stuck);
+        assert_eq!(top[0].count, 3);
+        assert_eq!(top[0].message, "Stuck at 7, 8, 9");
+        assert_eq!((top[0].first_seen, top[0].last_seen), (1_000, 3_000));
+        assert_eq!(top[0].computers, BTreeSet::from([7, 12]));
+
+        // Only the recent ones.
+        let recent = archive.top(2_500, 10);
+        assert_eq!(recent.len(), 2);
+        assert!(recent.iter().all(|summary| summary.count == 1));
+        // Tie goes to the newest.
+        assert_eq!(recent[0].signature, fuel);
+        assert_eq!(archive.top(0, 1).len(), 1);
+
+        let turtle_12: Vec<&str> = archive
+            .for_computer(12)
+            .iter()
+            .map(|record| record.uuid.as_str())
+            .collect();
+        assert_eq!(turtle_12, ["AAAAAAAA", "CCCCCCCC", "DDDDDDDD"]);
+        assert_eq!(archive.occurrences(fuel).len(), 1);
+        assert_eq!(archive.occurrences(fuel)[0].report().frames.len(), 2);
+    };
+    check(&archive);
+    check(&PanicArchive::open(&folder).unwrap());
+
+    std::fs::remove_dir_all(&folder).unwrap();
+}
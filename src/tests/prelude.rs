@@ -2,6 +2,7 @@
 
 // Global minecraft types
 pub use crate::minecraft::{
+    computercraft::computer_types::{server_message::ServerMessage, tasks::*},
     types::*,
     vanilla::{block_type::MinecraftBlock, data_globals::get_mc_data, item_type::MinecraftItem},
 };
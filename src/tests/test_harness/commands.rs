@@ -4,7 +4,7 @@ use log::info;
 
 use crate::{
     minecraft::{
-        types::MinecraftPosition,
+        types::{Cuboid, MinecraftPosition},
         vanilla::{
             block_type::{HasMinecraftBlock, MinecraftBlock},
             item_type::MinecraftItem,
@@ -27,10 +27,10 @@ pub enum TestCommand<'a> {
     /// Returns a pass or fail.
     SetBlock(MinecraftPosition, &'a MinecraftBlock),
 
-    /// Fill some blocks. Facing direction cannot be set.
+    /// Fill some blocks. Facing direction cannot be set. Areas too big for a single fill are split up.
     ///
     /// Returns a pass or fail.
-    Fill(CoordinatePosition, CoordinatePosition, &'a MinecraftBlock),
+    Fill(Cuboid, &'a MinecraftBlock),
 
     /// Test for a block at some position.
     ///
@@ -86,17 +86,19 @@ impl TestCommand<'_> {
                 // If we placed the block, we will get this text.
                 TestCommandResult::Success(result.contains("Changed the block"))
             }
-            TestCommand::Fill(pos1, pos2, minecraft_block) => {
-                let position1 = corner.with_offset(*pos1).as_command_string();
-                let position2 = corner.with_offset(*pos2).as_command_string();
+            TestCommand::Fill(area, minecraft_block) => {
                 let block_string = minecraft_block.get_full_name();
 
-                let command = format!("fill {position1} {position2} {block_string}");
-                info!("{command}");
+                let mut filled = true;
+                for piece in area.with_offset(corner).fill_pieces() {
+                    let command = format!("fill {} {block_string}", piece.as_command_string());
+                    info!("{command}");
 
-                let result = env.run_command(command).await;
-                // Should fill
-                TestCommandResult::Success(result.contains("Successfully filled"))
+                    let result = env.run_command(command).await;
+                    // Should fill
+                    filled &= result.contains("Successfully filled");
+                }
+                TestCommandResult::Success(filled)
             }
             TestCommand::TestForBlock(minecraft_position, minecraft_block) => {
                 // This command is less trivial.
@@ -15,8 +15,10 @@ async fn basic_setblock_and_fill_test() {
 
     // gold base
     let base = TestCommand::Fill(
-        CoordinatePosition { x: 1, y: 1, z: 1 },
-        CoordinatePosition { x: 3, y: 1, z: 3 },
+        Cuboid::new(
+            CoordinatePosition { x: 1, y: 1, z: 1 },
+            CoordinatePosition { x: 3, y: 1, z: 3 },
+        ),
         &MinecraftBlock::from_string("gold_block").unwrap(),
     );
 
@@ -109,10 +111,13 @@ async fn place_every_block() {
     let mut c2 = CoordinatePosition { x: 4, y: 3, z: 4 };
 
     assert!(
-        TestCommand::Fill(c1, c2, &MinecraftBlock::from_string("barrier").unwrap())
-            .invoke(&mut test)
-            .await
-            .success()
+        TestCommand::Fill(
+            Cuboid::new(c1, c2),
+            &MinecraftBlock::from_string("barrier").unwrap()
+        )
+        .invoke(&mut test)
+        .await
+        .success()
     );
 
     // Make it hollow, otherwise items would still fly.
@@ -122,10 +127,13 @@ async fn place_every_block() {
     c2.y -= 1;
     c2.z -= 1;
     assert!(
-        TestCommand::Fill(c1, c2, &MinecraftBlock::from_string("air").unwrap())
-            .invoke(&mut test)
-            .await
-            .success()
+        TestCommand::Fill(
+            Cuboid::new(c1, c2),
+            &MinecraftBlock::from_string("air").unwrap()
+        )
+        .invoke(&mut test)
+        .await
+        .success()
     );
 
     let block_pos = MinecraftPosition {
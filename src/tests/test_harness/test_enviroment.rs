@@ -1,7 +1,10 @@
 // Testing galore
 
 use crate::{
-    minecraft::vanilla::block_type::HasMinecraftBlock,
+    minecraft::{
+        types::{ChunkPosition, Cuboid, FORCELOAD_LIMIT},
+        vanilla::block_type::HasMinecraftBlock,
+    },
     tests::{prelude::*, test_harness::computer_builder::COMPUTER_STATE_CHANGE_TIME},
 };
 use std::{cmp::max, fmt::Display, path::PathBuf, sync::Arc};
@@ -56,17 +59,13 @@ impl MinecraftTestEnvironment {
 
 /// A handle of a running test.
 pub struct MinecraftTestHandle {
-    /// The area that this test occupies, and the corner that the test is based on.
+    /// The floor of this test. Its lowest corner is the south-east corner of the test, which all coordinates used in
+    /// tests are relative to.
     ///
     /// Test areas start at the southeast corner position and grow from there. (towards positive x/z)
     ///
-    /// This cannot be modified once the test has started, since we cannot re-allocate the plot we are running on.
-    area: TestArea,
-
-    /// The south-east corner of the test area. All coordinates used in tests are relative to this position.
-    ///
-    /// This cannot be modified once the test has started, since we cannot move our testing location.
-    corner: CoordinatePosition,
+    /// This cannot be modified once the test has started, since we cannot re-allocate or move the plot we are running on.
+    plot: Cuboid,
 
     /// This is set once the test has been marked as pass or fail.
     done: bool,
@@ -78,41 +77,35 @@ impl MinecraftTestHandle {
     /// Requires a test area, since we need to know how much space this test will use,
     /// and a name to display.
     pub async fn new<S: ToString + Display>(area: TestArea, test_name: S) -> Self {
-        // Account for the area starting at "0" thus being 1 block bigger than expected.
-        let area = TestArea {
-            size_x: area.size_x - 1,
-            size_z: area.size_x - 1,
-        };
-
         // Get a copy of the environment
         let mut env = MINECRAFT_TESTING_ENV.lock().await;
 
         // Find our plot position
         let corner = env.get_test_position(area);
+        let plot = area.floor(corner);
 
-        // Force load the plot
-        let c1 = corner;
-        let offset = CoordinatePosition {
-            x: area.size_x.into(),
-            y: 0,
-            z: area.size_z.into(),
-        };
-
-        let c2 = corner.with_offset(offset);
-
-        let command: String = format!("forceload add {} {} {} {}", c1.x, c1.z, c2.x, c2.z,);
+        // Force load every chunk the plot touches
+        assert!(
+            ChunkPosition::touched_by(plot).count() <= FORCELOAD_LIMIT,
+            "Test area is too big to force load!"
+        );
+        let first = ChunkPosition::containing(plot.min()).min_block(corner.y);
+        let last = ChunkPosition::containing(plot.max()).max_block(corner.y);
+        let command: String = format!(
+            "forceload add {} {} {} {}",
+            first.x, first.z, last.x, last.z
+        );
         let _ = env.run_command(command).await; // we assume this works, also i dont wanna parse the output rn
 
         // Update the floor
         env.update_floor(
-            corner,
-            area,
+            plot,
             MinecraftBlock::from_string("yellow_concrete").unwrap(),
         )
         .await;
 
         // Offset corner for the armorstand, cant be -1 -1 or it will be in the way of the bedrock test
-        let armor = c1.with_offset(CoordinatePosition { x: 1, y: 0, z: -1 });
+        let armor = corner.with_offset(CoordinatePosition { x: 1, y: 0, z: -1 });
         let a_x = armor.x;
         let a_z = armor.z;
 
@@ -127,11 +120,7 @@ impl MinecraftTestHandle {
         );
         let _ = env.run_command(command).await; // non-critical.
 
-        Self {
-            area,
-            corner,
-            done: false,
-        }
+        Self { plot, done: false }
     }
 
     /// Run a test command.
@@ -143,14 +132,14 @@ impl MinecraftTestHandle {
         command.invoke(self).await
     }
 
-    /// Get the area of the test
-    pub fn area(&self) -> TestArea {
-        self.area
+    /// Get the floor of the test
+    pub fn plot(&self) -> Cuboid {
+        self.plot
     }
 
     /// Get the corner position of the test
     pub fn corner(&self) -> CoordinatePosition {
-        self.corner
+        self.plot.min()
     }
 
     /// Finish the test and clean up. Requires a pass or fail status to update the plot floor.
@@ -160,7 +149,7 @@ impl MinecraftTestHandle {
             return;
         }
         let mut env = MINECRAFT_TESTING_ENV.lock().await;
-        let corner_string = self.corner.as_command_string();
+        let corner_string = self.corner().as_command_string();
         // Update floor
         let block = if passed {
             // pass sound
@@ -178,7 +167,7 @@ impl MinecraftTestHandle {
             MinecraftBlock::from_string("red_concrete").unwrap()
         };
 
-        env.update_floor(self.corner, self.area, block).await;
+        env.update_floor(self.plot, block).await;
         self.done = true;
         // We do not stop force-loading the chunks, since another test could be contained within it.
         // If we had something else to clean here, we would. but we dont.
@@ -354,30 +343,17 @@ impl MinecraftTestEnvironment {
     }
 
     /// Change the floor of a test
-    async fn update_floor(
-        &mut self,
-        corner: CoordinatePosition,
-        area: TestArea,
-        floor_block: MinecraftBlock,
-    ) {
-        let p1 = corner.as_command_string();
-        let p2 = CoordinatePosition {
-            x: corner.x + i64::from(area.size_x),
-            y: corner.y,
-            z: corner.z + i64::from(area.size_z),
-        }
-        .as_command_string();
+    async fn update_floor(&mut self, floor: Cuboid, floor_block: MinecraftBlock) {
         let block = floor_block.get_full_name();
-        // run the fill command
-        let command = format!("fill {p1} {p2} {block}");
-        let result = self.environment.send_rcon(&command).await;
-        if let Some(feedback) = result {
-            if feedback.contains("is not loaded") {
+        // Big floors take more than one fill.
+        for piece in floor.fill_pieces() {
+            let command = format!("fill {} {block}", piece.as_command_string());
+            let result = self.environment.send_rcon(&command).await;
+            if let Some(feedback) = result
+                && feedback.contains("is not loaded")
+            {
                 panic!("Tried to fill outside of loaded chunks!");
             }
-            if feedback.contains("Too many blocks") {
-                panic!("Floor for test is WAY too big!");
-            }
         }
     }
 
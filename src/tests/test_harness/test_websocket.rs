@@ -1,9 +1,12 @@
 // We need to run tests on the computers in ways that need to be fed or return some data.
 
-// TODO: This implementation might just end up being what we do for the actual server, and
-// thus will need to be moved out of here.
+// The real server lives in `websocket::server`. This one hands raw frames straight to
+// whichever test asked for that computer.
 
 use std::{
+    collections::VecDeque,
+    fmt::Display,
+    path::PathBuf,
     sync::{Arc, OnceLock},
     time::Duration,
 };
@@ -11,13 +14,30 @@ use std::{
 use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
 use log::{info, warn};
+use serde_json::Value;
 use tokio::{
     net::TcpListener,
     sync::{OnceCell, mpsc},
 };
-use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
 
-use crate::tests::prelude::MINECRAFT_TESTING_ENV;
+use crate::{
+    minecraft::computercraft::computer_types::{
+        compact::Encoding,
+        hello::{HelloResponse, LibraryManifest},
+        packet_types::{
+            DebuggingPacket, PacketDecodeError, PacketType, RawTurtlePacket, TurtlePacket,
+        },
+    },
+    tests::prelude::{MINECRAFT_TESTING_ENV, ServerMessage},
+    websocket::{
+        capture::{CaptureRecorder, ComputerCapture, Direction},
+        server::{DEFAULT_BIND_ADDRESS, UpgradeCheck},
+    },
+};
+
+/// Set this to a folder to record every test websocket to
+/// `<folder>/computer_<id>.jsonl`. See `websocket::capture` for replaying them.
+pub const CAPTURE_DIR_VARIABLE: &str = "MESHPIT_CAPTURE_DIR";
 
 // We force move the websocket to another thread, otherwise it would close between tests.
 static WEBSOCKET_RUNNING: OnceCell<()> = OnceCell::const_new();
@@ -58,6 +78,14 @@ pub struct TestWebsocket {
     id: u16,
     sender: mpsc::Sender<String>,
     receiver: mpsc::Receiver<String>,
+    /// Where to record every frame, see [CAPTURE_DIR_VARIABLE]. The hello is
+    /// answered before the test gets the websocket, so it is never recorded.
+    capture: Option<ComputerCapture>,
+    /// The step the turtle is stopped at, if any. See [StepBarrier].
+    stopped_at: Option<Step>,
+    /// Messages that [TestWebsocket::receive_packet] passed over, oldest first.
+    /// These come out before anything new.
+    pending: VecDeque<String>,
 }
 
 #[derive(Debug)]
@@ -65,20 +93,83 @@ pub enum TestWebsocketError {
     AlreadyTaken,
     TimedOut,
     Closed,
+    /// The outgoing message could not be serialized.
+    Serialization,
 }
 
+impl Display for TestWebsocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestWebsocketError::AlreadyTaken => {
+                write!(f, "someone already has a websocket for this computer")
+            }
+            TestWebsocketError::TimedOut => write!(f, "timed out"),
+            TestWebsocketError::Closed => write!(f, "the websocket closed"),
+            TestWebsocketError::Serialization => write!(f, "could not serialize the message"),
+        }
+    }
+}
+
+impl std::error::Error for TestWebsocketError {}
+
+/// Something went wrong in [TestWebsocket::receive_packet].
+#[derive(Debug)]
+pub enum ReceivePacketError {
+    /// No packet of the right type came in.
+    Missing {
+        error: TestWebsocketError,
+        /// Every packet of some other type that was passed over while waiting.
+        passed_over: Vec<String>,
+    },
+    /// A packet came in, but it would not decode.
+    Decode {
+        error: PacketDecodeError,
+        /// The message exactly as the turtle sent it.
+        raw: String,
+    },
+}
+
+impl Display for ReceivePacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceivePacketError::Missing { error, passed_over } => {
+                write!(f, "packet never came: {error}")?;
+                if !passed_over.is_empty() {
+                    write!(f, ", passed over: {}", passed_over.join(", "))?;
+                }
+                Ok(())
+            }
+            ReceivePacketError::Decode { error, raw } => {
+                write!(f, "failed to decode packet: {error}, raw: {raw}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReceivePacketError {}
+
 // Tests need to eventually time out, thus recv() and send() must have a timeout.
 impl TestWebsocket {
-    /// Sent a message down the websocket
+    /// Sent a message down the websocket. The message is wrapped in a packet
+    /// the same way the real server does it.
     #[must_use = "You really should check if that worked."]
     pub async fn send(
         &mut self,
-        message: String,
+        message: ServerMessage,
         sec_timeout: u64,
     ) -> Result<(), TestWebsocketError> {
+        let json = message.into_packet().to_json().map_err(|err| {
+            warn!("Failed to serialize outgoing message! {err}");
+            TestWebsocketError::Serialization
+        })?;
+        if let Some(capture) = &self.capture {
+            capture.record(Direction::Outbound, &json);
+        }
+        // Whatever we send, a turtle stopped at a step takes it as the go-ahead.
+        self.stopped_at = None;
         let sent = self
             .sender
-            .send_timeout(message, Duration::from_secs(sec_timeout))
+            .send_timeout(json, Duration::from_secs(sec_timeout))
             .await;
         match sent {
             Ok(_) => Ok(()),
@@ -90,29 +181,315 @@ impl TestWebsocket {
     }
 
     /// Receive a message from the websocket
+    ///
+    /// Acks from the turtle are skipped, since tests never track what they
+    /// send. Anything [TestWebsocket::receive_packet] passed over comes out
+    /// first.
     #[must_use = "You really should check if that worked."]
     pub async fn receive(&mut self, sec_timeout: u64) -> Result<String, TestWebsocketError> {
+        if let Some(message) = self.pending.pop_front() {
+            return Ok(message);
+        }
+
+        // run the send
+        let sending =
+            tokio::time::timeout(Duration::from_secs(sec_timeout), self.next_message()).await;
+        match sending {
+            Ok(ok) => ok,
+            Err(_) => Err(TestWebsocketError::TimedOut),
+        }
+    }
+
+    /// Receive the next packet of a certain type, like a [WalkbackPacket].
+    ///
+    /// Packets of other types are passed over, and saved for later calls to
+    /// [TestWebsocket::receive] or [TestWebsocket::receive_packet].
+    ///
+    /// [WalkbackPacket]: crate::minecraft::computercraft::computer_types::packet_types::WalkbackPacket
+    #[must_use = "You really should check if that worked."]
+    pub async fn receive_packet<T>(&mut self, sec_timeout: u64) -> Result<T, ReceivePacketError>
+    where
+        T: TryFrom<RawTurtlePacket, Error = PacketDecodeError>,
+    {
+        // Maybe we already passed over it.
+        for index in 0..self.pending.len() {
+            if let Some(packet) = decode_as(&self.pending[index])? {
+                self.pending.remove(index);
+                return Ok(packet);
+            }
+        }
+
+        let mut passed_over = Vec::new();
         let hang = async {
+            loop {
+                let message = match self.next_message().await {
+                    Ok(message) => message,
+                    Err(error) => {
+                        return Err(ReceivePacketError::Missing {
+                            error,
+                            passed_over: std::mem::take(&mut passed_over),
+                        });
+                    }
+                };
+                if let Some(packet) = decode_as(&message)? {
+                    return Ok(packet);
+                }
+                passed_over.push(message.clone());
+                self.pending.push_back(message);
+            }
+        };
+        match tokio::time::timeout(Duration::from_secs(sec_timeout), hang).await {
+            Ok(result) => result,
+            Err(_) => Err(ReceivePacketError::Missing {
+                error: TestWebsocketError::TimedOut,
+                passed_over,
+            }),
+        }
+    }
+
+    /// Wait for the next message that isn't an ack, with no timeout.
+    async fn next_message(&mut self) -> Result<String, TestWebsocketError> {
+        loop {
             let got = self.receiver.recv().await;
             match got {
                 Some(ok) => {
+                    if let Some(capture) = &self.capture {
+                        capture.record(Direction::Inbound, &ok);
+                    }
+                    if is_ack(&ok) {
+                        continue;
+                    }
                     // TODO: TEMP
                     info!("{}", ok);
-                    Ok(ok)
+                    return Ok(ok);
                 }
-                None => Err(TestWebsocketError::Closed),
+                None => return Err(TestWebsocketError::Closed),
             }
+        }
+    }
+}
+
+/// Decode a message as a certain packet type. Returns None if it is some other
+/// type of packet.
+fn decode_as<T>(message: &str) -> Result<Option<T>, ReceivePacketError>
+where
+    T: TryFrom<RawTurtlePacket, Error = PacketDecodeError>,
+{
+    let decoded = serde_json::from_str::<RawTurtlePacket>(message)
+        .map_err(PacketDecodeError::Malformed)
+        .and_then(T::try_from);
+    match decoded {
+        Ok(packet) => Ok(Some(packet)),
+        Err(PacketDecodeError::WrongPacketType { .. }) => Ok(None),
+        Err(error) => Err(ReceivePacketError::Decode {
+            error,
+            raw: message.to_string(),
+        }),
+    }
+}
+
+// ===
+// Steps
+// ===
+
+// Test scripts call `debugging.waitStep("some step")` to stop until the test
+// lets them carry on, usually so the test can change the world around the
+// turtle first. The turtle sends a debugging packet with `{"wait": name}` in
+// it, then waits for any packet at all to come back.
+
+/// A point in a test script where the turtle stopped. Old scripts call
+/// `waitStep()` without a name, those match any step the test waits for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub name: Option<String>,
+}
+
+impl Step {
+    /// Read a step out of an incoming message, if it is one.
+    pub fn from_message(message: &str) -> Option<Self> {
+        let raw: RawTurtlePacket = serde_json::from_str(message).ok()?;
+        let DebuggingPacket { inner_data, .. } = raw.try_into().ok()?;
+        match inner_data {
+            Value::String(wait) if wait == "wait" => Some(Step { name: None }),
+            Value::Object(mut fields) => match fields.remove("wait")? {
+                Value::String(name) => Some(Step { name: Some(name) }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Wether this is the step the test is waiting on.
+    fn matches(&self, name: &str) -> bool {
+        self.name.as_deref().is_none_or(|ours| ours == name)
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "'{name}'"),
+            None => write!(f, "<unnamed>"),
+        }
+    }
+}
+
+/// Something went wrong stepping through a test script.
+#[derive(Debug)]
+pub enum StepError {
+    /// The turtle never got to the step.
+    NeverReached {
+        step: String,
+        error: TestWebsocketError,
+    },
+    /// The turtle stopped at a different step than the one we waited on.
+    WrongStep { expected: String, reached: Step },
+    /// The turtle sent something other than a step while we waited on one.
+    Unexpected { step: String, message: String },
+    /// We tried to let the turtle carry on, but it was not stopped.
+    NotStopped,
+    /// The go-ahead could not be sent.
+    Release {
+        step: Step,
+        error: TestWebsocketError,
+    },
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepError::NeverReached { step, error } => {
+                write!(f, "turtle never reached step '{step}': {error}")
+            }
+            StepError::WrongStep { expected, reached } => {
+                write!(
+                    f,
+                    "expected turtle to reach step '{expected}', but it reached {reached}"
+                )
+            }
+            StepError::Unexpected { step, message } => {
+                write!(
+                    f,
+                    "turtle sent something else while we waited for step '{step}': {message}"
+                )
+            }
+            StepError::NotStopped => write!(f, "turtle is not stopped at any step"),
+            StepError::Release { step, error } => {
+                write!(f, "could not release turtle from step {step}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+/// Keeps a test and a turtle in lock step. Get one from
+/// [TestWebsocket::steps].
+///
+/// ```ignore
+/// // The turtle calls `debugging.waitStep("equipped axe")`
+/// let mut steps = socket.steps();
+/// steps.await_step("equipped axe", 15).await?;
+/// // ... change the world around the turtle ...
+/// steps.release().await?;
+/// ```
+pub struct StepBarrier<'a> {
+    socket: &'a mut TestWebsocket,
+}
+
+impl TestWebsocket {
+    /// Step through a script that uses `debugging.waitStep`.
+    pub fn steps(&mut self) -> StepBarrier<'_> {
+        StepBarrier { socket: self }
+    }
+}
+
+impl StepBarrier<'_> {
+    /// Wait for the turtle to stop at a step. Anything else the turtle sends in
+    /// the meantime is an error, since the test should have read it first.
+    pub async fn await_step(&mut self, name: &str, sec_timeout: u64) -> Result<(), StepError> {
+        let message =
+            self.socket
+                .receive(sec_timeout)
+                .await
+                .map_err(|error| StepError::NeverReached {
+                    step: name.to_string(),
+                    error,
+                })?;
+        let Some(step) = Step::from_message(&message) else {
+            return Err(StepError::Unexpected {
+                step: name.to_string(),
+                message,
+            });
         };
+        if !step.matches(name) {
+            return Err(StepError::WrongStep {
+                expected: name.to_string(),
+                reached: step,
+            });
+        }
+        self.socket.stopped_at = Some(step);
+        Ok(())
+    }
 
-        // run the send
-        let sending = tokio::time::timeout(Duration::from_secs(sec_timeout), hang).await;
-        match sending {
-            Ok(ok) => ok,
-            Err(_) => Err(TestWebsocketError::TimedOut),
+    /// Let a stopped turtle carry on.
+    pub async fn release(&mut self) -> Result<(), StepError> {
+        let Some(step) = self.socket.stopped_at.clone() else {
+            return Err(StepError::NotStopped);
+        };
+        self.socket
+            .send(ServerMessage::DebugReply("go".into()), 5)
+            .await
+            .map_err(|error| StepError::Release { step, error })
+    }
+
+    /// Wait for the turtle to stop at a step, then let it carry on right away.
+    pub async fn pass(&mut self, name: &str, sec_timeout: u64) -> Result<(), StepError> {
+        self.await_step(name, sec_timeout).await?;
+        self.release().await
+    }
+}
+
+/// The recorder for a computer, if [CAPTURE_DIR_VARIABLE] is set.
+fn capture_from_environment(id: u16) -> Option<ComputerCapture> {
+    let folder = PathBuf::from(std::env::var_os(CAPTURE_DIR_VARIABLE)?);
+    let opened = std::fs::create_dir_all(&folder)
+        .and_then(|_| CaptureRecorder::append(folder.join(format!("computer_{id}.jsonl"))));
+    match opened {
+        Ok(recorder) => Some(recorder.for_computer(id)),
+        Err(err) => {
+            warn!("Failed to open a capture for computer {id}! {err}");
+            None
         }
     }
 }
 
+/// Every library on disk, to check hellos against.
+fn bundled_libraries() -> &'static LibraryManifest {
+    static BUNDLED: OnceLock<LibraryManifest> = OnceLock::new();
+    BUNDLED
+        .get_or_init(|| LibraryManifest::bundled().expect("Should be able to read the lua files."))
+}
+
+/// Check if an incoming message is an ack packet.
+fn is_ack(message: &str) -> bool {
+    serde_json::from_str::<RawTurtlePacket>(message)
+        .is_ok_and(|packet| *packet.packet_type() == PacketType::Ack)
+}
+
+/// The ack for an incoming message, if it needs one. Turtles keep re-sending
+/// until they are acked, same as with the real server.
+fn ack_for(message: &str) -> Option<String> {
+    let packet = serde_json::from_str::<RawTurtlePacket>(message).ok()?;
+    if matches!(packet.packet_type(), PacketType::Ack | PacketType::Pong) {
+        return None;
+    }
+    let ack = ServerMessage::Ack {
+        uuid: packet.uuid().to_string(),
+    };
+    ack.into_packet().to_json().ok()
+}
+
 /// Internal storage for our websockets.
 struct ComputerChannel {
     to_test: mpsc::Sender<String>,
@@ -129,9 +506,7 @@ fn get_registry() -> &'static Registry {
 
 /// Run the websocket.
 async fn run_test_websocket_server() {
-    // This is currently hardcoded.
-    let address = "localhost:4816";
-    let listener = TcpListener::bind(address)
+    let listener = TcpListener::bind(DEFAULT_BIND_ADDRESS)
         .await
         .expect("Failed to bind for websocket!");
 
@@ -139,52 +514,26 @@ async fn run_test_websocket_server() {
         tokio::spawn(async move {
             let mut computer_id = None;
 
-            // We need a callback so we can get the computer ID header on the handshake
-            // No idea how this works.
-            // TODO: Make this deny requests instead of panicking if its missing the computer ID? Better connection handshake?
-            let callback = |req: &Request, mut response: Response| {
-                // Pull out the computer ID if it exists.
-                if req.uri().path() != "/meshpit" {
-                    warn!("Tried to connect at the non-meshpit uri! {req:#?}");
-                    return Err(Response::builder()
-                        .status(404)
-                        .body(Some("Invalid Path".into()))
-                        .unwrap());
-                }
-                // set protocol headers TODO: do we really need this
-                if let Some(sub) = req.headers().get("Sec-WebSocket-Protocol") {
-                    response
-                        .headers_mut()
-                        .insert("Sec-WebSocket-Protocol", sub.clone());
-                }
-
-                if let Some(id) = req.headers().get("Computer-ID") {
-                    computer_id = Some(id.to_str().unwrap().parse::<u16>().unwrap());
-                } else {
-                    // No computer id header.
-                    return Err(Response::builder()
-                        .status(400)
-                        .body(Some("Missing headers".into()))
-                        .unwrap());
-                }
-
-                Ok(response)
+            // We need a callback so we can get the computer ID header on the handshake.
+            let check = UpgradeCheck {
+                computer_id: &mut computer_id,
             };
 
-            let websocket_stream = match tokio_tungstenite::accept_hdr_async(stream, callback).await
-            {
+            let websocket_stream = match tokio_tungstenite::accept_hdr_async(stream, check).await {
                 Ok(ok) => ok,
-                Err(err) => panic!("Failed to accept websocket! {err:#?}"),
+                Err(err) => {
+                    warn!("Failed to accept websocket! {err}");
+                    return;
+                }
             };
 
+            // Always set if the upgrade went through.
             let Some(id) = computer_id else {
-                // no id was given.
-                panic!("Got websocket handshake request that did not contain a computer ID!")
+                return;
             };
 
             // Get the waiting handle in the registry. If nobody is waiting on this ID then we discard everything.
-            // TODO: when merging into final implementation, we need to accept all connections regardless if they
-            // are expected or not, and funnel them into some handler for all packets.
+            // Unlike the real server, tests only care about computers they asked for.
             let Some((_, mut broker)) = get_registry().remove(&id) else {
                 // return; // Nobody has a handle to this computer so the computer cannot connect.
                 // Usually happens when tests fail but turtles try to re-connect afterwards. So we ignore it.
@@ -195,10 +544,53 @@ async fn run_test_websocket_server() {
             // split the socket so we can spawn the threads for the channels
             let (mut websocket_sender, mut websocket_receiver) = websocket_stream.split();
 
+            // Every computer says hello first. Tests always get let through, but
+            // we complain if the computer is running different lua than what is
+            // on disk.
+            let Some(Ok(first_message)) = websocket_receiver.next().await else {
+                warn!("Computer {id} disconnected before saying hello!");
+                return;
+            };
+            let Ok(first_text) = first_message.into_text() else {
+                warn!("Computer {id} sent a non-text hello!");
+                return;
+            };
+            match TurtlePacket::from_json(first_text.as_str()) {
+                Ok(TurtlePacket::Hello(hello)) => {
+                    let response =
+                        bundled_libraries().judge(hello.protocol_version, &hello.libraries);
+                    if response != HelloResponse::Accepted {
+                        warn!("Computer {id} had a bad hello: {response:?}");
+                    }
+                    let reply = ServerMessage::HelloReply {
+                        response: HelloResponse::Accepted,
+                        encoding: Encoding::negotiate(&hello.encodings),
+                    }
+                    .into_packet()
+                    .to_json()
+                    .expect("Hello replies should always serialize.");
+                    if websocket_sender.send(reply.into()).await.is_err() {
+                        return;
+                    }
+                }
+                _ => {
+                    warn!("Computer {id} did not say hello first! Got: {first_text}");
+                    return;
+                }
+            }
+
+            // Acks go straight back out, tests never see them.
+            let (ack_tx, mut ack_rx) = mpsc::unbounded_channel::<String>();
+
             // Computer -> Server
             let incoming = tokio::spawn(async move {
                 while let Some(Ok(message)) = websocket_receiver.next().await {
                     if let Ok(text) = message.into_text() {
+                        if let Some(ack) = ack_for(text.as_str())
+                            && ack_tx.send(ack).is_err()
+                        {
+                            break;
+                        }
                         // close the socket if the person on the other side of the channel is gone.
                         // TODO: Performance: This makes heap allocated strings. This is slow. This will need to be swapped
                         // to some other format.
@@ -216,7 +608,12 @@ async fn run_test_websocket_server() {
 
             // Server -> Computer
             let outgoing = tokio::spawn(async move {
-                while let Some(message) = broker.from_test.recv().await {
+                loop {
+                    let message = tokio::select! {
+                        Some(ack) = ack_rx.recv() => ack,
+                        Some(message) = broker.from_test.recv() => message,
+                        else => break,
+                    };
                     // Close the socket if the computer doesn't accept the message.
                     if websocket_sender.send(message.into()).await.is_err() {
                         break;
@@ -267,6 +664,9 @@ impl TestWebsocket {
             id,
             sender: from_test_tx,
             receiver: to_test_rx,
+            capture: capture_from_environment(id),
+            stopped_at: None,
+            pending: VecDeque::new(),
         })
     }
 }
@@ -300,3 +700,65 @@ async fn double_open_websocket() {
     assert!(handle);
     assert!(!handle2);
 }
+
+/// Steps should be read out of debugging packets, named or not.
+#[test]
+fn reading_steps() {
+    let packet = |data: Value| {
+        serde_json::json!({
+            "id": 1,
+            "uuid": "a",
+            "timestamp": 0,
+            "packet_type": "debugging",
+            "data": data,
+        })
+        .to_string()
+    };
+    let named = Step::from_message(&packet(serde_json::json!({"wait": "equipped axe"}))).unwrap();
+    assert_eq!(named.name.as_deref(), Some("equipped axe"));
+    assert!(named.matches("equipped axe"));
+    assert!(!named.matches("placed tree"));
+
+    let unnamed = Step::from_message(&packet(Value::String("wait".into()))).unwrap();
+    assert!(unnamed.matches("anything at all"));
+
+    assert!(Step::from_message(&packet(serde_json::json!(27))).is_none());
+    assert!(Step::from_message(&packet(Value::String("waiting".into()))).is_none());
+    assert!(Step::from_message("not even json").is_none());
+
+    let error = StepError::NeverReached {
+        step: "equipped axe".into(),
+        error: TestWebsocketError::TimedOut,
+    };
+    assert_eq!(
+        error.to_string(),
+        "turtle never reached step 'equipped axe': timed out"
+    );
+}
+
+/// Packets should be picked out by type, and bad ones should say what they
+/// were.
+#[test]
+fn decoding_packets_by_type() {
+    use crate::minecraft::computercraft::computer_types::packet_types::WalkbackPacket;
+
+    let debug = serde_json::json!({
+        "id": 1,
+        "uuid": "a",
+        "timestamp": 0,
+        "packet_type": "debugging",
+        "data": "pass",
+    })
+    .to_string();
+    let packet: DebuggingPacket = decode_as(&debug).unwrap().unwrap();
+    assert_eq!(packet.inner_data, "pass");
+    assert!(decode_as::<WalkbackPacket>(&debug).unwrap().is_none());
+
+    let broken = debug.replace("debugging", "walkback");
+    let error = decode_as::<WalkbackPacket>(&broken).unwrap_err();
+    assert!(matches!(error, ReceivePacketError::Decode { .. }));
+    assert!(error.to_string().contains(&broken));
+
+    let error = decode_as::<DebuggingPacket>("not json").unwrap_err();
+    assert!(error.to_string().ends_with("raw: not json"));
+}
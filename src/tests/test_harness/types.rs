@@ -1,5 +1,7 @@
 // miscellaneous types used only in tests.
 
+use crate::minecraft::types::{CoordinatePosition, Cuboid};
+
 /// The area that a test occupies. Will make a floor under the test of the size specified.
 #[derive(Clone, Copy)]
 pub struct TestArea {
@@ -8,3 +10,11 @@ pub struct TestArea {
     /// How many blocks wide (south direction) this test needs
     pub size_z: u16,
 }
+
+impl TestArea {
+    /// The floor of a test with this area, growing from its corner towards
+    /// positive x and z.
+    pub fn floor(&self, corner: CoordinatePosition) -> Cuboid {
+        Cuboid::from_size(corner, self.size_x.into(), 1, self.size_z.into())
+    }
+}
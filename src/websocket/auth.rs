@@ -0,0 +1,443 @@
+// Making sure turtles are who they say they are.
+//
+// Anything that can reach the server can put whatever it wants in the
+// `Computer-ID` header, so when the server is set up with a token file, every
+// turtle needs to prove it holds the token that was issued to its computer ID:
+// - The turtle says hello as usual.
+// - We answer with a challenge, a random nonce that is never used again.
+// - The turtle answers with an `auth` packet, holding the HMAC-SHA256 of
+//   `<computer id>:<nonce>`, keyed with its token.
+// - We work out the same HMAC, and only reply to the hello if they match.
+//
+// Tokens are written to `turtle_token` in the root of the turtle. New turtles
+// get theirs from their parent during mitosis, which asks us for one with a
+// `provision` packet.
+//
+// The lua side has its own SHA-256, since CC has nothing built in. See the
+// "Authentication" section of `networking.lua`.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use rand::Rng;
+
+/// The round constants of SHA-256.
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The starting state of SHA-256.
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// How many bytes SHA-256 works on at a time. HMAC pads its key out to this.
+const BLOCK_SIZE: usize = 64;
+
+/// How many random bytes go into a token. Tokens are sent around as hex, so
+/// they end up twice as long.
+const TOKEN_BYTES: usize = 32;
+
+/// How many random bytes go into a challenge nonce.
+const NONCE_BYTES: usize = 16;
+
+/// SHA-256 of some bytes.
+///
+/// Must match `sha256` in `networking.lua`.
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut message = bytes.to_vec();
+    let bit_length = (bytes.len() as u64).wrapping_mul(8);
+    message.push(0x80);
+    while message.len() % BLOCK_SIZE != BLOCK_SIZE - 8 {
+        message.push(0);
+    }
+    message.extend_from_slice(&bit_length.to_be_bytes());
+
+    let mut state = INITIAL_STATE;
+    for block in message.chunks_exact(BLOCK_SIZE) {
+        let mut schedule = [0u32; 64];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..64 {
+            let (back_15, back_2) = (schedule[i - 15], schedule[i - 2]);
+            let s0 = back_15.rotate_right(7) ^ back_15.rotate_right(18) ^ (back_15 >> 3);
+            let s1 = back_2.rotate_right(17) ^ back_2.rotate_right(19) ^ (back_2 >> 10);
+            schedule[i] = schedule[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for (constant, word) in ROUND_CONSTANTS.iter().zip(schedule) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*constant)
+                .wrapping_add(word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// HMAC-SHA256 of a message.
+///
+/// Must match `hmacSha256` in `networking.lua`.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut padded_key = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        padded_key[..32].copy_from_slice(&sha256(key));
+    } else {
+        padded_key[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner: Vec<u8> = padded_key.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = padded_key.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+/// Lowercase hex of some bytes.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Some random bytes, as hex.
+fn random_hex(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    rand::rng().fill(&mut bytes[..]);
+    to_hex(&bytes)
+}
+
+/// A brand new token, to be handed to a single turtle.
+pub fn new_token() -> String {
+    random_hex(TOKEN_BYTES)
+}
+
+/// A brand new challenge nonce. Never re-use these, or a recorded answer could
+/// be played back.
+pub fn new_nonce() -> String {
+    random_hex(NONCE_BYTES)
+}
+
+/// What a turtle holding `token` should answer a challenge with.
+pub fn proof(token: &str, computer_id: u16, nonce: &str) -> String {
+    to_hex(&hmac_sha256(
+        token.as_bytes(),
+        format!("{computer_id}:{nonce}").as_bytes(),
+    ))
+}
+
+/// Compare two strings without bailing out at the first difference, so how
+/// long the comparison takes gives nothing away.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.bytes()
+        .zip(b.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// Why a turtle could not prove who it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// We never issued a token to this computer ID.
+    NoToken,
+    /// The turtle did not answer the challenge. It probably has no token.
+    NoProof,
+    /// The turtle answered the challenge wrong. Either it has the wrong token,
+    /// or it is not who it says it is.
+    BadProof,
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::NoToken => write!(f, "no token was ever issued to this computer"),
+            AuthError::NoProof => write!(f, "did not answer the challenge"),
+            AuthError::BadProof => write!(f, "answered the challenge wrong"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// Every token we have issued, keyed by computer ID. Kept in a single json
+/// file, which is re-written whenever a token changes.
+#[derive(Debug)]
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: Mutex<Tokens>,
+}
+
+/// What a [TokenStore] keeps behind its lock.
+#[derive(Debug, Default)]
+struct Tokens {
+    /// Every token, by computer ID. This is all that is written to the file.
+    issued: HashMap<u16, String>,
+    /// Provisioned tokens that no turtle has proven it holds yet, and who
+    /// asked for each. Only kept in memory, so a restart just means the parent
+    /// can't ask again.
+    unclaimed: HashMap<u16, u16>,
+}
+
+impl TokenStore {
+    /// Open the token file, or start with no tokens if it does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let issued = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path,
+            tokens: Mutex::new(Tokens {
+                issued,
+                unclaimed: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Where the tokens are written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The token issued to a computer, if any.
+    pub fn get(&self, computer_id: u16) -> Option<String> {
+        self.lock().issued.get(&computer_id).cloned()
+    }
+
+    /// Issue a new token to a computer, replacing its old one if it had one.
+    /// The turtle will not get in until it has the new token.
+    pub fn issue(&self, computer_id: u16) -> std::io::Result<String> {
+        let mut tokens = self.lock();
+        let token = new_token();
+        self.change(&mut tokens, computer_id, Some(token.clone()))?;
+        tokens.unclaimed.remove(&computer_id);
+        Ok(token)
+    }
+
+    /// Issue a token to a computer that `parent` is building. Returns None if
+    /// it already has one, since turtles must not be able to take over each
+    /// other.
+    ///
+    /// The answer to the parent can get lost, so the same parent can ask again
+    /// and get the same token, up until the new turtle first proves it holds
+    /// it.
+    pub fn provision(&self, parent: u16, computer_id: u16) -> std::io::Result<Option<String>> {
+        let mut tokens = self.lock();
+        if let Some(token) = tokens.issued.get(&computer_id) {
+            let asked_before = tokens.unclaimed.get(&computer_id) == Some(&parent);
+            return Ok(asked_before.then(|| token.clone()));
+        }
+        let token = new_token();
+        self.change(&mut tokens, computer_id, Some(token.clone()))?;
+        tokens.unclaimed.insert(computer_id, parent);
+        Ok(Some(token))
+    }
+
+    /// Take a computer's token away, locking it out. Returns wether it had
+    /// one.
+    pub fn revoke(&self, computer_id: u16) -> std::io::Result<bool> {
+        let mut tokens = self.lock();
+        if !tokens.issued.contains_key(&computer_id) {
+            return Ok(false);
+        }
+        self.change(&mut tokens, computer_id, None)?;
+        tokens.unclaimed.remove(&computer_id);
+        Ok(true)
+    }
+
+    /// Check a turtle's answer to a challenge.
+    pub fn verify(
+        &self,
+        computer_id: u16,
+        nonce: &str,
+        answer: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let token = self.get(computer_id).ok_or(AuthError::NoToken)?;
+        let answer = answer.ok_or(AuthError::NoProof)?;
+        if !constant_time_eq(&proof(&token, computer_id, nonce), answer) {
+            return Err(AuthError::BadProof);
+        }
+        // The turtle has its token, so its parent has no reason to ask again.
+        self.lock().unclaimed.remove(&computer_id);
+        Ok(())
+    }
+
+    /// Nothing in here can panic halfway through changing the tokens, so
+    /// poisoning is ignored.
+    fn lock(&self) -> MutexGuard<'_, Tokens> {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Set or remove a computer's token, and write every token out. Undone if
+    /// writing fails, so we never hold a token the file does not.
+    fn change(
+        &self,
+        tokens: &mut Tokens,
+        computer_id: u16,
+        token: Option<String>,
+    ) -> std::io::Result<()> {
+        let previous = match token {
+            Some(token) => tokens.issued.insert(computer_id, token),
+            None => tokens.issued.remove(&computer_id),
+        };
+        if let Err(err) = self.save(&tokens.issued) {
+            match previous {
+                Some(previous) => tokens.issued.insert(computer_id, previous),
+                None => tokens.issued.remove(&computer_id),
+            };
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Write every token out. Written to a temporary file first, so a crash
+    /// halfway through never leaves us with half a token file.
+    fn save(&self, tokens: &HashMap<u16, String>) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(tokens)?;
+        let temporary = self.path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Nobody else on the machine has any business reading these.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&temporary)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Known values, from the SHA-2 spec and RFC 4231.
+fn sha256_matches_reference() {
+    assert_eq!(
+        to_hex(&sha256(b"")),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        to_hex(&sha256(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    // Long enough to need a second block.
+    assert_eq!(
+        to_hex(&sha256(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+    assert_eq!(
+        to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    // Keys longer than a block are hashed first.
+    assert_eq!(
+        to_hex(&hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+/// Tokens should survive a reopen, and only the right answer should get in.
+fn issuing_and_verifying_tokens() {
+    let path = std::env::temp_dir().join(format!("meshpit_tokens_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let store = TokenStore::open(&path).unwrap();
+    assert_eq!(store.verify(7, "nonce", None), Err(AuthError::NoToken));
+
+    let token = store.provision(1, 7).unwrap().unwrap();
+    assert_eq!(token.len(), TOKEN_BYTES * 2);
+    // Nobody gets to take over a computer that already has a token.
+    assert_eq!(store.provision(2, 7).unwrap(), None);
+    // Unless it is the turtle that asked for it, which may have missed the
+    // answer.
+    assert_eq!(store.provision(1, 7).unwrap(), Some(token.clone()));
+
+    let nonce = new_nonce();
+    let answer = proof(&token, 7, &nonce);
+    assert_eq!(store.verify(7, &nonce, Some(&answer)), Ok(()));
+    // Claimed now.
+    assert_eq!(store.provision(1, 7).unwrap(), None);
+    assert_eq!(store.verify(7, &nonce, None), Err(AuthError::NoProof));
+    // Replayed against a different nonce, or claiming a different ID.
+    assert_eq!(
+        store.verify(7, &new_nonce(), Some(&answer)),
+        Err(AuthError::BadProof)
+    );
+    let other = store.issue(8).unwrap();
+    assert_eq!(
+        store.verify(8, &nonce, Some(&proof(&other, 7, &nonce))),
+        Err(AuthError::BadProof)
+    );
+
+    let reopened = TokenStore::open(&path).unwrap();
+    assert_eq!(reopened.get(7), Some(token));
+    assert!(reopened.revoke(8).unwrap());
+    assert!(!reopened.revoke(8).unwrap());
+    assert_eq!(TokenStore::open(&path).unwrap().get(8), None);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+/// A token that could not be written down should not be handed out.
+fn failed_saves_are_undone() {
+    let folder = std::env::temp_dir().join(format!("meshpit_tokens_gone_{}", std::process::id()));
+    std::fs::create_dir_all(&folder).unwrap();
+    let store = TokenStore::open(folder.join("tokens.json")).unwrap();
+    std::fs::remove_dir(&folder).unwrap();
+
+    assert!(store.provision(1, 7).is_err());
+    assert_eq!(store.get(7), None);
+    assert!(store.issue(7).is_err());
+    assert_eq!(store.get(7), None);
+}
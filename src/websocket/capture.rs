@@ -0,0 +1,506 @@
+// Recording websocket traffic, and playing it back without minecraft.
+//
+// Every frame is written as one line of json to a capture file, along with
+// which computer it was to or from, and when. Frames are written by their own
+// thread, so recording never holds up a connection.
+//
+// Replaying a capture feeds the frames back through packet decoding and the
+// ack/dedupe layer the same way a live connection would, and optionally
+// through a `PacketHandler`, so a failure from a test run can be picked apart
+// offline in seconds.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    minecraft::computercraft::computer_types::{
+        packet_types::{PacketDecodeError, TurtlePacket},
+        server_message::{ServerMessage, ServerPacket, current_timestamp},
+    },
+    websocket::{
+        handler::PacketHandler,
+        reliable::{Received, ReliableLink},
+    },
+};
+
+/// Which way a frame went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// From a computer to us.
+    Inbound,
+    /// From us to a computer.
+    Outbound,
+}
+
+/// A single websocket frame, as written to a capture file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedFrame {
+    pub computer_id: u16,
+    /// When the frame was sent or received, in milliseconds since the unix
+    /// epoch.
+    pub timestamp: u64,
+    pub direction: Direction,
+    /// The frame, exactly as it went over the wire.
+    pub text: String,
+}
+
+/// What the thread writing a capture file is asked to do.
+#[derive(Debug)]
+enum ToWriter {
+    Frame(CapturedFrame),
+    /// Let the sender know once everything before this is written.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Writes frames to a capture file. Cheap to clone, every clone writes to the
+/// same file.
+///
+/// The file is written by its own thread, which stops once every clone is
+/// dropped.
+#[derive(Debug, Clone)]
+pub struct CaptureRecorder {
+    writer: mpsc::Sender<ToWriter>,
+}
+
+impl CaptureRecorder {
+    /// Start a new capture, replacing the file if it exists.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::from_file(File::create(path)?)
+    }
+
+    /// Add on to the end of an existing capture, or start a new one.
+    pub fn append(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Self::from_file(file)
+    }
+
+    fn from_file(file: File) -> std::io::Result<Self> {
+        let (writer, requests) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("meshpit-capture".to_string())
+            .spawn(move || write_frames(file, requests))?;
+        Ok(Self { writer })
+    }
+
+    /// Record frames to and from a single computer.
+    pub fn for_computer(&self, computer_id: u16) -> ComputerCapture {
+        ComputerCapture {
+            computer_id,
+            recorder: self.clone(),
+        }
+    }
+
+    /// Queue a frame up to be written down. Never blocks.
+    pub fn record(&self, frame: CapturedFrame) {
+        // Only fails if the writer panicked, which it already complained about.
+        let _ = self.writer.send(ToWriter::Frame(frame));
+    }
+
+    /// Wait until every frame recorded so far is in the file, IE before
+    /// reading it back.
+    pub fn flush(&self) {
+        let (done, written) = mpsc::channel();
+        if self.writer.send(ToWriter::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+}
+
+/// Write frames down as they come in, until every recorder is dropped.
+///
+/// The file is flushed whenever we catch up, so a busy server does not flush
+/// after every frame, and a killed one loses as little as possible. Failing to
+/// record is never worth breaking a connection over, so errors are only
+/// logged.
+fn write_frames(file: File, requests: mpsc::Receiver<ToWriter>) {
+    let mut file = BufWriter::new(file);
+    while let Ok(first) = requests.recv() {
+        let mut flushed = Vec::new();
+        for request in std::iter::once(first).chain(requests.try_iter()) {
+            let frame = match request {
+                ToWriter::Frame(frame) => frame,
+                ToWriter::Flush(done) => {
+                    flushed.push(done);
+                    continue;
+                }
+            };
+            let mut line = match serde_json::to_string(&frame) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    warn!("Failed to serialize a captured frame! {err}");
+                    continue;
+                }
+            };
+            line.push('\n');
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                warn!("Failed to write a captured frame! {err}");
+            }
+        }
+        if let Err(err) = file.flush() {
+            warn!("Failed to write captured frames! {err}");
+        }
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+/// A [CaptureRecorder] for a single computer.
+#[derive(Debug, Clone)]
+pub struct ComputerCapture {
+    computer_id: u16,
+    recorder: CaptureRecorder,
+}
+
+impl ComputerCapture {
+    /// Record a frame, stamped with the current time.
+    pub fn record(&self, direction: Direction, text: &str) {
+        self.recorder.record(CapturedFrame {
+            computer_id: self.computer_id,
+            timestamp: current_timestamp(),
+            direction,
+            text: text.to_string(),
+        });
+    }
+}
+
+/// Read every frame out of a capture file.
+///
+/// A capture cut off mid-line (IE the server was killed) still reads, the
+/// partial line is skipped with a warning.
+pub fn read_capture(path: impl AsRef<Path>) -> std::io::Result<Vec<CapturedFrame>> {
+    let file = File::open(&path)?;
+    let mut frames = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(frame) => frames.push(frame),
+            Err(err) => warn!(
+                "Skipping line {} of {}, it is not a frame! {err}",
+                index + 1,
+                path.as_ref().display()
+            ),
+        }
+    }
+    Ok(frames)
+}
+
+// ======
+// Replay
+// ======
+
+/// What the server made of a replayed frame.
+#[derive(Debug)]
+pub enum ReplayEvent {
+    /// A packet from a computer. Only [Received::Fresh] packets would have made
+    /// it past the ack/dedupe layer.
+    Received {
+        frame: CapturedFrame,
+        packet: TurtlePacket,
+        received: Received,
+        /// What the handler sent back, if the replay has one. See
+        /// [Replay::handled_by]. Boxed, since it is rarely there.
+        reply: Option<Box<ServerMessage>>,
+    },
+    /// A packet we sent.
+    Sent {
+        frame: CapturedFrame,
+        packet: ServerPacket,
+    },
+    /// A frame from a computer that did not decode.
+    BadPacket {
+        frame: CapturedFrame,
+        error: PacketDecodeError,
+    },
+    /// A frame we sent that did not decode. Only happens if the packet format
+    /// changed since the capture was made.
+    BadServerPacket {
+        frame: CapturedFrame,
+        error: serde_json::Error,
+    },
+}
+
+/// Plays a capture back, frame by frame, in the order they were recorded.
+///
+/// Every computer gets its own [ReliableLink], so duplicates and acks are
+/// sorted out exactly like they were live.
+pub struct Replay {
+    frames: std::vec::IntoIter<CapturedFrame>,
+    links: HashMap<u16, ReliableLink>,
+    handler: Option<PacketHandler>,
+}
+
+impl Replay {
+    pub fn new(frames: Vec<CapturedFrame>) -> Self {
+        Self {
+            frames: frames.into_iter(),
+            links: HashMap::new(),
+            handler: None,
+        }
+    }
+
+    /// Hand every fresh packet to a handler, the same way the server does, IE
+    /// to rebuild the world model a test run ended up with.
+    ///
+    /// Nothing is waiting on responses to calls in a replay, so those are
+    /// left alone.
+    pub fn handled_by(mut self, handler: PacketHandler) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// The handler, as of the last replayed frame.
+    pub fn handler(&self) -> Option<&PacketHandler> {
+        self.handler.as_ref()
+    }
+
+    /// Replay a capture file. See [read_capture].
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(read_capture(path)?))
+    }
+
+    /// The delivery state of a computer as of the last replayed frame, IE to
+    /// see what was never acked.
+    pub fn link(&self, computer_id: u16) -> Option<&ReliableLink> {
+        self.links.get(&computer_id)
+    }
+
+    /// Only the packets that would have been handed up from the websocket,
+    /// skipping everything else.
+    pub fn fresh_packets(self) -> impl Iterator<Item = (u16, TurtlePacket)> {
+        self.filter_map(|event| match event {
+            ReplayEvent::Received {
+                frame,
+                packet,
+                received: Received::Fresh,
+                ..
+            } => Some((frame.computer_id, packet)),
+            _ => None,
+        })
+    }
+}
+
+impl Iterator for Replay {
+    type Item = ReplayEvent;
+
+    fn next(&mut self) -> Option<ReplayEvent> {
+        loop {
+            let frame = self.frames.next()?;
+            let link = self.links.entry(frame.computer_id).or_default();
+            return Some(match frame.direction {
+                Direction::Inbound => match TurtlePacket::from_json(&frame.text) {
+                    Ok(packet) => {
+                        let id = frame.computer_id;
+                        let (packet, received, reply) = match packet {
+                            // Dealt with by the handshake, before the ack/dedupe
+                            // layer or the handler ever see them. The handshake
+                            // also starts the turtle's world deltas over.
+                            TurtlePacket::Hello(_) => {
+                                if let Some(handler) = &self.handler {
+                                    handler.reset_stream(id);
+                                }
+                                (packet, Received::Fresh, None)
+                            }
+                            _ => match (link.receive(&packet), &self.handler) {
+                                (Received::Fresh, Some(handler)) => {
+                                    let handled = handler.handle(id, packet, None);
+                                    // Only answered calls are used up, and there
+                                    // are none to answer in a replay.
+                                    let Some(packet) = handled.packet else {
+                                        warn!(
+                                            "Replayed packet from computer {id} was used up by the handler, skipping it."
+                                        );
+                                        continue;
+                                    };
+                                    (packet, Received::Fresh, handled.reply.map(Box::new))
+                                }
+                                (received, _) => (packet, received, None),
+                            },
+                        };
+                        ReplayEvent::Received {
+                            frame,
+                            packet,
+                            received,
+                            reply,
+                        }
+                    }
+                    Err(error) => ReplayEvent::BadPacket { frame, error },
+                },
+                Direction::Outbound => match serde_json::from_str::<ServerPacket>(&frame.text) {
+                    Ok(packet) => {
+                        link.track(&packet);
+                        ReplayEvent::Sent { frame, packet }
+                    }
+                    Err(error) => ReplayEvent::BadServerPacket { frame, error },
+                },
+            });
+        }
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// A recorded conversation should replay the same way it happened live.
+fn record_and_replay() {
+    use crate::minecraft::computercraft::computer_types::{
+        server_message::ServerMessage,
+        tasks::{TaskData, TaskDefinition},
+    };
+
+    let path = std::env::temp_dir().join(format!("meshpit_capture_{}.jsonl", std::process::id()));
+    let recorder = CaptureRecorder::create(&path).unwrap();
+    let turtle = recorder.for_computer(5);
+    let other = recorder.for_computer(6);
+
+    let debug = r#"{"id":5,"uuid":"AAAAAAAA","timestamp":0,"packet_type":"debugging","data":"hi"}"#;
+    let task = ServerMessage::AssignTask(TaskDefinition {
+        return_to_start: false,
+        return_to_facing: false,
+        fuel_buffer: 0,
+        task_data: TaskData::NormalizeHeight,
+    })
+    .into_packet();
+    let ack = format!(
+        r#"{{"id":5,"uuid":"BBBBBBBB","timestamp":0,"packet_type":"ack","data":{{"uuid":"{}"}}}}"#,
+        task.uuid
+    );
+
+    turtle.record(Direction::Inbound, debug);
+    // The turtle never got our ack, so sent it again.
+    turtle.record(Direction::Inbound, debug);
+    turtle.record(Direction::Outbound, &task.to_json().unwrap());
+    // Same packet, different computer.
+    other.record(Direction::Inbound, &debug.replace(r#""id":5"#, r#""id":6"#));
+    other.record(Direction::Inbound, "not json");
+    turtle.record(Direction::Inbound, &ack);
+    recorder.flush();
+    drop((turtle, other, recorder));
+
+    let frames = read_capture(&path).unwrap();
+    assert_eq!(frames.len(), 6);
+    assert_eq!(frames[0].computer_id, 5);
+    assert_eq!(frames[0].text, debug);
+
+    let mut replay = Replay::from_file(&path).unwrap();
+    let mut received = Vec::new();
+    let mut sent = 0;
+    let mut bad = 0;
+    for event in replay.by_ref() {
+        match event {
+            ReplayEvent::Received {
+                frame, received: r, ..
+            } => received.push((frame.computer_id, r)),
+            ReplayEvent::Sent { packet, .. } => {
+                assert_eq!(packet, task);
+                sent += 1;
+            }
+            ReplayEvent::BadPacket { frame, .. } => {
+                assert_eq!(frame.computer_id, 6);
+                bad += 1;
+            }
+            ReplayEvent::BadServerPacket { error, .. } => panic!("{error}"),
+        }
+    }
+    assert_eq!(
+        received,
+        [
+            (5, Received::Fresh),
+            (5, Received::Duplicate),
+            (6, Received::Fresh),
+            (5, Received::Ack),
+        ]
+    );
+    assert_eq!((sent, bad), (1, 1));
+    // The ack cleared the task.
+    assert_eq!(replay.link(5).unwrap().unacked().count(), 0);
+
+    let fresh: Vec<u16> = Replay::from_file(&path)
+        .unwrap()
+        .fresh_packets()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(fresh, [5, 6]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+/// A replay with a handler should end up where the server did live.
+fn replay_through_handler() {
+    use crate::{
+        minecraft::{
+            computercraft::computer_types::hello::PROTOCOL_VERSION, types::CoordinatePosition,
+        },
+        world::WorldModel,
+    };
+
+    let path = std::env::temp_dir().join(format!(
+        "meshpit_capture_handled_{}.jsonl",
+        std::process::id()
+    ));
+    let recorder = CaptureRecorder::create(&path).unwrap();
+    let turtle = recorder.for_computer(4);
+    let stone = r#"{"id":4,"uuid":"DELTAAAA","timestamp":1234,"packet_type":"world_delta","data":{"sequence":0,"full":true,"blocks":{"x:0|y:9|z:0":{"name":"minecraft:stone","pos":{"x":0,"y":9,"z":0},"state":null,"tag":null}},"positions":null}}"#;
+    // Delta 1 never made it.
+    let gap = r#"{"id":4,"uuid":"DELTAAAC","timestamp":1300,"packet_type":"world_delta","data":{"sequence":2,"full":false,"blocks":null,"positions":null}}"#;
+    turtle.record(Direction::Inbound, stone);
+    turtle.record(Direction::Inbound, gap);
+    turtle.record(Direction::Inbound, gap);
+    // Rebooted, and said hello again.
+    let hello = format!(
+        r#"{{"id":4,"uuid":"HELLOHEL","timestamp":1400,"packet_type":"hello","data":{{"protocol_version":{PROTOCOL_VERSION},"libraries":null,"encodings":["json"]}}}}"#
+    );
+    turtle.record(Direction::Inbound, &hello);
+    recorder.flush();
+    drop((turtle, recorder));
+
+    let handler = PacketHandler::new(WorldModel::new(), None, None);
+    let mut replay = Replay::from_file(&path).unwrap().handled_by(handler);
+    let replies: Vec<Option<ServerMessage>> = replay
+        .by_ref()
+        .take(3)
+        .map(|event| match event {
+            ReplayEvent::Received { reply, .. } => reply.map(|reply| *reply),
+            other => panic!("Expected a packet, got {other:?}!"),
+        })
+        .collect();
+    // The duplicate never made it to the handler.
+    assert_eq!(replies, [None, Some(ServerMessage::ResyncWorld), None]);
+
+    let handler = replay.handler().unwrap();
+    assert!(handler.delta_stream(4).unwrap().awaiting_resync());
+    {
+        let world = handler.world_model();
+        let stone = world.get(CoordinatePosition { x: 0, y: 9, z: 0 }).unwrap();
+        assert_eq!(stone.block.name, "minecraft:stone");
+        assert_eq!((stone.observer, stone.observed_at), (4, 1234));
+    }
+
+    // Saying hello starts the deltas over, same as live.
+    assert!(matches!(
+        replay.next(),
+        Some(ReplayEvent::Received {
+            packet: TurtlePacket::Hello(_),
+            ..
+        })
+    ));
+    assert!(replay.handler().unwrap().delta_stream(4).is_none());
+    assert!(replay.next().is_none());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,225 @@
+// What the server does with a packet once it is past the ack/dedupe layer.
+//
+// Live connections and replayed captures both go through here, so a capture
+// from a test run ends up with the same world model, resyncs, tokens, and
+// panics that the server had live. See `capture.rs`.
+//
+// Everything in here may block on locks or the disk, so live connections call
+// it off of the async threads.
+
+use std::sync::{Mutex, MutexGuard};
+
+use dashmap::DashMap;
+use log::{error, warn};
+
+use crate::{
+    minecraft::{
+        computercraft::computer_types::{
+            packet_types::TurtlePacket,
+            server_message::ServerMessage,
+            world_delta::{DeltaOutcome, DeltaStream},
+        },
+        peripherals::inventory::GenericInventory,
+    },
+    panic_archive::PanicArchive,
+    websocket::{auth::TokenStore, rpc::RpcChannel},
+    world::WorldModel,
+};
+
+/// What came of handling a packet.
+#[derive(Debug)]
+pub struct Handled {
+    /// The packet, to be handed up. None if it was used up, IE it was a
+    /// response to a call.
+    pub packet: Option<TurtlePacket>,
+    /// What to send back to the computer, if anything.
+    pub reply: Option<ServerMessage>,
+}
+
+/// Everything the server keeps track of from the packets turtles send it.
+pub struct PacketHandler {
+    /// The last inventory each turtle sent us.
+    inventories: DashMap<u16, GenericInventory>,
+    /// Where each turtle's world deltas are at.
+    streams: DashMap<u16, DeltaStream>,
+    /// Everything every turtle has seen, merged together.
+    world: Mutex<WorldModel>,
+    panics: Option<Mutex<PanicArchive>>,
+    tokens: Option<TokenStore>,
+}
+
+impl PacketHandler {
+    /// Panics are only kept if given an archive, and tokens are only handed
+    /// out if given a token store.
+    pub fn new(
+        world: WorldModel,
+        panics: Option<PanicArchive>,
+        tokens: Option<TokenStore>,
+    ) -> Self {
+        Self {
+            inventories: DashMap::new(),
+            streams: DashMap::new(),
+            world: Mutex::new(world),
+            panics: panics.map(Mutex::new),
+            tokens,
+        }
+    }
+
+    /// The last inventory a turtle sent us.
+    pub fn inventory(&self, id: u16) -> Option<GenericInventory> {
+        self.inventories.get(&id).map(|inventory| inventory.clone())
+    }
+
+    /// Where a turtle's world deltas are at. None if it never sent one.
+    pub fn delta_stream(&self, id: u16) -> Option<DeltaStream> {
+        self.streams.get(&id).map(|stream| *stream)
+    }
+
+    /// Forget where a turtle's world deltas are at. Called whenever it says
+    /// hello, since a rebooted turtle starts counting from zero again.
+    pub fn reset_stream(&self, id: u16) {
+        self.streams.remove(&id);
+    }
+
+    /// Everything every turtle has seen, merged together. See
+    /// `world/mod.rs`.
+    ///
+    /// No new blocks can be merged in while this is held.
+    pub fn world_model(&self) -> MutexGuard<'_, WorldModel> {
+        lock_world(&self.world)
+    }
+
+    /// Save the world model, if it has a file and anything changed. Returns
+    /// wether anything was written.
+    ///
+    /// The world is only held long enough to copy it, so turtles can keep
+    /// merging blocks in while it is written.
+    pub fn save_world(&self) -> std::io::Result<bool> {
+        let Some(snapshot) = self.world_model().snapshot() else {
+            return Ok(false);
+        };
+        if let Err(err) = snapshot.write() {
+            self.world_model().save_failed();
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Every panic turtles have sent us, if we are keeping them.
+    ///
+    /// No new panics can be recorded while this is held.
+    pub fn panics(&self) -> Option<MutexGuard<'_, PanicArchive>> {
+        self.panics.as_ref().map(lock_archive)
+    }
+
+    /// Every turtle's token, if we are handing them out.
+    pub fn tokens(&self) -> Option<&TokenStore> {
+        self.tokens.as_ref()
+    }
+
+    /// Deal with a packet we have not seen before from computer `id`.
+    ///
+    /// Responses are handed to `calls`, if given. Without it, IE in a replay,
+    /// there is nothing waiting on them, so they are handed back up instead.
+    pub fn handle(&self, id: u16, packet: TurtlePacket, calls: Option<&RpcChannel>) -> Handled {
+        let mut reply = None;
+        match &packet {
+            TurtlePacket::Inventory(inventory) => {
+                self.inventories.insert(id, inventory.inventory.clone());
+            }
+            TurtlePacket::Walkback(walkback) => {
+                self.world_model()
+                    .merge_walkback(id, walkback.timestamp, &walkback.walkback);
+            }
+            TurtlePacket::WorldDelta(world_delta) => {
+                self.world_model()
+                    .merge_delta(id, world_delta.timestamp, &world_delta.delta);
+                let outcome = self
+                    .streams
+                    .entry(id)
+                    .or_default()
+                    .apply(&world_delta.delta);
+                if let DeltaOutcome::Gap { expected, got } = outcome {
+                    if got > expected {
+                        warn!(
+                            "Missed world deltas {expected} to {} from computer {id}, asking for a resync.",
+                            got - 1
+                        );
+                    } else {
+                        warn!(
+                            "World deltas from computer {id} went back to {got}, asking for a resync."
+                        );
+                    }
+                    reply = Some(ServerMessage::ResyncWorld);
+                }
+            }
+            TurtlePacket::Provision(provision) => {
+                reply = Some(ServerMessage::Provisioned {
+                    computer_id: provision.computer_id,
+                    token: self.provision_token(id, provision.computer_id),
+                });
+            }
+            TurtlePacket::Panic(panic) => {
+                if let Some(mut archive) = self.panics()
+                    && let Err(err) = archive.record(panic)
+                {
+                    error!("Failed to archive a panic from computer {id}! {err}");
+                }
+            }
+            _ => {}
+        }
+
+        let packet = match (packet, calls) {
+            (TurtlePacket::Response(response), Some(calls)) => {
+                calls.resolve(response);
+                None
+            }
+            (packet, _) => Some(packet),
+        };
+        Handled { packet, reply }
+    }
+
+    /// Issue a token for a turtle that `parent` is building, if we are handing
+    /// out tokens and it does not have one already. See
+    /// [TokenStore::provision].
+    ///
+    /// We have no way of knowing what a turtle is really building, so any
+    /// authenticated turtle can get a token for any computer that does not have
+    /// one yet. Loud on purpose, so a turtle doing that when it should not
+    /// stands out.
+    fn provision_token(&self, parent: u16, computer_id: u16) -> Option<String> {
+        let tokens = self.tokens.as_ref()?;
+        match tokens.provision(parent, computer_id) {
+            Ok(Some(token)) => {
+                warn!("Computer {parent} provisioned a token for computer {computer_id}.");
+                Some(token)
+            }
+            Ok(None) => {
+                warn!(
+                    "Computer {parent} asked for a token for computer {computer_id}, which already has one!"
+                );
+                None
+            }
+            Err(err) => {
+                error!("Failed to save a token for computer {computer_id}! {err}");
+                None
+            }
+        }
+    }
+}
+
+/// A panic while holding the archive can't leave it half written in memory, so
+/// poisoning is ignored.
+fn lock_archive(archive: &Mutex<PanicArchive>) -> MutexGuard<'_, PanicArchive> {
+    archive
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Merging a block in is a single write, a panic can't leave the world half
+/// changed, so poisoning is ignored.
+fn lock_world(world: &Mutex<WorldModel>) -> MutexGuard<'_, WorldModel> {
+    world
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,178 @@
+// The hello handshake, run on every new connection before anything else.
+
+use std::{fmt::Display, time::Duration};
+
+use log::{info, warn};
+use tokio::sync::mpsc;
+
+use crate::{
+    minecraft::computercraft::computer_types::{
+        compact::Encoding,
+        hello::{HelloResponse, LibraryManifest},
+        packet_types::{HelloPacket, PacketDecodeError, PacketType, TurtlePacket},
+        server_message::ServerMessage,
+    },
+    websocket::{
+        CCWebsocket, CCWebsocketError,
+        auth::{AuthError, TokenStore, new_nonce},
+    },
+};
+
+/// Reasons a handshake did not go through.
+#[derive(Debug)]
+pub enum HandshakeError {
+    /// The turtle never said hello.
+    TimedOut,
+    /// The websocket closed before the turtle said hello.
+    Closed,
+    /// The first packet could not be decoded.
+    Decode(PacketDecodeError),
+    /// The first packet was not a hello.
+    NotHello(PacketType),
+    /// We could not send our reply.
+    Send(CCWebsocketError),
+    /// We rejected the turtle. The turtle has already been told why.
+    Rejected(String),
+    /// The turtle could not prove it is the computer it says it is. The turtle
+    /// has already been turned away.
+    Unauthenticated(AuthError),
+}
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::TimedOut => write!(f, "turtle did not say hello in time"),
+            HandshakeError::Closed => write!(f, "websocket closed during the handshake"),
+            HandshakeError::Decode(error) => write!(f, "bad hello packet: {error}"),
+            HandshakeError::NotHello(packet_type) => {
+                write!(f, "expected a hello packet, got a `{packet_type}` packet")
+            }
+            HandshakeError::Send(error) => write!(f, "failed to reply to hello: {error}"),
+            HandshakeError::Rejected(reason) => write!(f, "turtle was rejected: {reason}"),
+            HandshakeError::Unauthenticated(error) => {
+                write!(f, "turtle failed to authenticate: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+/// A turtle that made it through the handshake.
+#[derive(Debug)]
+pub struct Handshake {
+    pub hello: HelloPacket,
+    /// Either [HelloResponse::Accepted] or [HelloResponse::Outdated].
+    pub response: HelloResponse,
+    /// What the turtle will send its large packets in.
+    pub encoding: Encoding,
+}
+
+/// Wait for the turtle's hello, check it against the manifest, and reply.
+///
+/// If given `tokens`, the turtle must also prove that it holds the token for
+/// `computer_id` before it gets a reply. See `auth.rs`.
+///
+/// Outdated turtles are let through, but it is up to the caller to do something
+/// about them.
+pub async fn handshake(
+    socket: &CCWebsocket,
+    incoming: &mut mpsc::UnboundedReceiver<String>,
+    manifest: &LibraryManifest,
+    tokens: Option<(&TokenStore, u16)>,
+    timeout: Duration,
+) -> Result<Handshake, HandshakeError> {
+    let hello = match next_packet(incoming, timeout).await? {
+        TurtlePacket::Hello(hello) => hello,
+        other => return Err(HandshakeError::NotHello(other.packet_type())),
+    };
+
+    if let Some((tokens, computer_id)) = tokens
+        && let Err(error) = authenticate(socket, incoming, tokens, computer_id, timeout).await?
+    {
+        // The turtle gets no hints as to why.
+        socket
+            .send(ServerMessage::HelloReply {
+                response: HelloResponse::Rejected {
+                    reason: "authentication failed".to_string(),
+                },
+                encoding: Encoding::Json,
+            })
+            .map_err(HandshakeError::Send)?;
+        return Err(HandshakeError::Unauthenticated(error));
+    }
+
+    let response = manifest.judge(hello.protocol_version, &hello.libraries);
+    let encoding = Encoding::negotiate(&hello.encodings);
+    socket
+        .send(ServerMessage::HelloReply {
+            response: response.clone(),
+            encoding,
+        })
+        .map_err(HandshakeError::Send)?;
+
+    match response {
+        HelloResponse::Rejected { reason } => Err(HandshakeError::Rejected(reason)),
+        HelloResponse::Outdated {
+            ref stale_libraries,
+        } => {
+            warn!(
+                "Computer {} is running outdated libraries: {stale_libraries:?}",
+                hello.from
+            );
+            Ok(Handshake {
+                hello,
+                response,
+                encoding,
+            })
+        }
+        HelloResponse::Accepted => Ok(Handshake {
+            hello,
+            response,
+            encoding,
+        }),
+    }
+}
+
+/// Challenge the turtle, and check its answer.
+///
+/// Anything other than an `auth` packet in answer counts as no answer.
+async fn authenticate(
+    socket: &CCWebsocket,
+    incoming: &mut mpsc::UnboundedReceiver<String>,
+    tokens: &TokenStore,
+    computer_id: u16,
+    timeout: Duration,
+) -> Result<Result<(), AuthError>, HandshakeError> {
+    let nonce = new_nonce();
+    socket
+        .send(ServerMessage::Challenge {
+            nonce: nonce.clone(),
+        })
+        .map_err(HandshakeError::Send)?;
+
+    let proof = match next_packet(incoming, timeout).await? {
+        TurtlePacket::Auth(auth) => auth.proof,
+        other => {
+            info!(
+                "Computer {computer_id} answered its challenge with a `{}` packet.",
+                other.packet_type()
+            );
+            None
+        }
+    };
+    Ok(tokens.verify(computer_id, &nonce, proof.as_deref()))
+}
+
+/// Wait for the next packet during the handshake.
+async fn next_packet(
+    incoming: &mut mpsc::UnboundedReceiver<String>,
+    timeout: Duration,
+) -> Result<TurtlePacket, HandshakeError> {
+    let text = match tokio::time::timeout(timeout, incoming.recv()).await {
+        Ok(Some(text)) => text,
+        Ok(None) => return Err(HandshakeError::Closed),
+        Err(_) => return Err(HandshakeError::TimedOut),
+    };
+    TurtlePacket::from_json(&text).map_err(HandshakeError::Decode)
+}
@@ -0,0 +1,215 @@
+// Keeping track of which turtles are still alive.
+//
+// Turtles vanish without warning whenever the chunk they are in unloads, and
+// come back just as suddenly when it loads again. We ping every connected
+// turtle, and go off of when we last heard anything from them.
+
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+/// What we think of a turtle right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Liveness {
+    /// Connected, and talking to us.
+    Connected,
+    /// Still connected, but has not answered in a while.
+    Silent,
+    /// The websocket closed. The turtle will probably be back.
+    Disconnected,
+    /// Gone for so long that it probably is not coming back. Could have been
+    /// broken, run out of fuel in an unloaded chunk, etc.
+    PresumedDead,
+}
+
+impl Display for Liveness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Liveness::Connected => write!(f, "connected"),
+            Liveness::Silent => write!(f, "silent"),
+            Liveness::Disconnected => write!(f, "disconnected"),
+            Liveness::PresumedDead => write!(f, "presumed dead"),
+        }
+    }
+}
+
+/// How often to check on turtles, and how long to wait on them.
+#[derive(Debug, Clone, Copy)]
+pub struct LivenessConfig {
+    /// How often connected turtles are pinged.
+    pub ping_every: Duration,
+    /// How long a connected turtle can go without saying anything before it is
+    /// [Liveness::Silent].
+    pub silent_after: Duration,
+    /// How long a turtle can go without saying anything before it is
+    /// [Liveness::PresumedDead], connected or not.
+    pub dead_after: Duration,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        // Turtles give up on us after 60 seconds without a ping, see
+        // `PING_TIMEOUT_MS` in `networking.lua`.
+        Self {
+            ping_every: Duration::from_secs(10),
+            silent_after: Duration::from_secs(30),
+            dead_after: Duration::from_secs(300),
+        }
+    }
+}
+
+/// The liveness of a single turtle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurtleLiveness {
+    state: Liveness,
+    /// When we last heard anything from the turtle.
+    last_seen: Instant,
+    /// When `state` was entered.
+    since: Instant,
+    /// Wether the turtle's websocket is open. Silent turtles can be presumed
+    /// dead without it ever closing.
+    socket_open: bool,
+}
+
+impl TurtleLiveness {
+    /// A turtle that just connected.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            state: Liveness::Connected,
+            last_seen: now,
+            since: now,
+            socket_open: true,
+        }
+    }
+
+    pub fn state(&self) -> Liveness {
+        self.state
+    }
+
+    /// When we last heard anything from the turtle.
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+
+    /// When the turtle entered its current state.
+    pub fn since(&self) -> Instant {
+        self.since
+    }
+
+    /// The turtle said something. Returns the new state if it changed, IE a
+    /// silent turtle spoke up again.
+    ///
+    /// Only connected turtles can say anything, so this does nothing to
+    /// disconnected turtles. Turtles that went quiet for long enough to be
+    /// presumed dead come back, as long as their websocket never closed.
+    pub fn heard_from(&mut self, at: Instant) -> Option<Liveness> {
+        self.last_seen = self.last_seen.max(at);
+        match self.state {
+            Liveness::Silent => self.enter(Liveness::Connected, at),
+            Liveness::PresumedDead if self.socket_open => self.enter(Liveness::Connected, at),
+            Liveness::Connected | Liveness::Disconnected | Liveness::PresumedDead => None,
+        }
+    }
+
+    /// The turtle (re)connected.
+    pub fn connected(&mut self, now: Instant) -> Option<Liveness> {
+        self.last_seen = self.last_seen.max(now);
+        self.socket_open = true;
+        self.enter(Liveness::Connected, now)
+    }
+
+    /// The turtle's websocket closed.
+    pub fn disconnected(&mut self, now: Instant) -> Option<Liveness> {
+        self.socket_open = false;
+        match self.state {
+            // Already as gone as it gets.
+            Liveness::PresumedDead => None,
+            _ => self.enter(Liveness::Disconnected, now),
+        }
+    }
+
+    /// Move on to the next state if the turtle has been quiet for too long.
+    /// Returns the new state if it changed.
+    pub fn check(&mut self, now: Instant, config: &LivenessConfig) -> Option<Liveness> {
+        let quiet_for = now.saturating_duration_since(self.last_seen);
+        let next = match self.state {
+            _ if quiet_for >= config.dead_after => Liveness::PresumedDead,
+            Liveness::Connected if quiet_for >= config.silent_after => Liveness::Silent,
+            _ => return None,
+        };
+        self.enter(next, now)
+    }
+
+    fn enter(&mut self, state: Liveness, now: Instant) -> Option<Liveness> {
+        if self.state == state {
+            return None;
+        }
+        self.state = state;
+        self.since = now;
+        Some(state)
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Walk a turtle through every state.
+fn liveness_transitions() {
+    let config = LivenessConfig {
+        ping_every: Duration::from_secs(1),
+        silent_after: Duration::from_secs(10),
+        dead_after: Duration::from_secs(100),
+    };
+    let start = Instant::now();
+    let at = |secs: u64| start + Duration::from_secs(secs);
+
+    let mut turtle = TurtleLiveness::new(start);
+    assert_eq!(turtle.check(at(5), &config), None);
+    assert_eq!(turtle.check(at(10), &config), Some(Liveness::Silent));
+    assert_eq!(turtle.check(at(11), &config), None);
+
+    // Speaking up again brings it back.
+    assert_eq!(turtle.heard_from(at(12)), Some(Liveness::Connected));
+    assert_eq!(turtle.heard_from(at(13)), None);
+    assert_eq!(turtle.last_seen(), at(13));
+
+    // Chunk unloads.
+    assert_eq!(turtle.disconnected(at(20)), Some(Liveness::Disconnected));
+    assert_eq!(turtle.check(at(50), &config), None);
+    assert_eq!(turtle.check(at(113), &config), Some(Liveness::PresumedDead));
+    assert_eq!(turtle.since(), at(113));
+    assert_eq!(turtle.disconnected(at(114)), None);
+
+    // Back from the dead.
+    assert_eq!(turtle.connected(at(200)), Some(Liveness::Connected));
+
+    // Silent turtles die too, even if the socket never closes.
+    assert_eq!(turtle.check(at(210), &config), Some(Liveness::Silent));
+    assert_eq!(turtle.check(at(300), &config), Some(Liveness::PresumedDead));
+}
+
+#[test]
+/// Turtles presumed dead while still connected come back once they speak up,
+/// but disconnected ones have to reconnect first.
+fn presumed_dead_turtles_can_speak_up() {
+    let config = LivenessConfig {
+        ping_every: Duration::from_secs(1),
+        silent_after: Duration::from_secs(10),
+        dead_after: Duration::from_secs(100),
+    };
+    let start = Instant::now();
+    let at = |secs: u64| start + Duration::from_secs(secs);
+
+    let mut turtle = TurtleLiveness::new(start);
+    assert_eq!(turtle.check(at(100), &config), Some(Liveness::PresumedDead));
+    assert_eq!(turtle.heard_from(at(150)), Some(Liveness::Connected));
+    assert_eq!(turtle.since(), at(150));
+
+    assert_eq!(turtle.disconnected(at(160)), Some(Liveness::Disconnected));
+    assert_eq!(turtle.check(at(250), &config), Some(Liveness::PresumedDead));
+    assert_eq!(turtle.heard_from(at(260)), None);
+    assert_eq!(turtle.state(), Liveness::PresumedDead);
+}
@@ -0,0 +1,180 @@
+// management of the websocket
+
+use std::{fmt::Display, sync::Arc};
+
+use futures_util::{SinkExt, StreamExt};
+use log::{error, warn};
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{
+    WebSocketStream, accept_async,
+    tungstenite::{self, Message},
+};
+
+use crate::{
+    minecraft::computercraft::computer_types::server_message::{ServerMessage, ServerPacket},
+    websocket::{
+        capture::{ComputerCapture, Direction},
+        outbound::{OutboundConfig, OutboundQueue, Priority},
+    },
+};
+
+pub mod auth;
+pub mod capture;
+pub mod handler;
+pub mod handshake;
+pub mod liveness;
+pub mod outbound;
+pub mod reliable;
+pub mod rpc;
+pub mod server;
+
+#[derive(Clone)]
+pub struct CCWebsocket {
+    // Messages put into this queue are sent into Minecraft, most urgent first.
+    // These have already been serialized by `send`.
+    outbound: Arc<OutboundQueue>,
+}
+
+/// Reasons a message could not be sent down the websocket.
+#[derive(Debug)]
+pub enum CCWebsocketError {
+    /// The message could not be turned into json.
+    Serialization(serde_json::Error),
+    /// The websocket has already closed.
+    Closed,
+    /// Too many messages of this priority are already waiting to go out. Try
+    /// again later.
+    Full(Priority),
+}
+
+impl Display for CCWebsocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CCWebsocketError::Serialization(error) => {
+                write!(f, "failed to serialize message: {error}")
+            }
+            CCWebsocketError::Closed => write!(f, "websocket is closed"),
+            CCWebsocketError::Full(priority) => {
+                write!(f, "too many {priority} messages are waiting to be sent")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CCWebsocketError {}
+
+impl CCWebsocket {
+    /// Accept a new websocket connection.
+    pub async fn new(
+        stream: TcpStream,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<String>), tungstenite::Error> {
+        let websocket_stream = accept_async(stream).await?;
+        Ok(Self::from_stream(
+            websocket_stream,
+            None,
+            OutboundConfig::default(),
+        ))
+    }
+
+    /// Wrap a websocket that has already been accepted. Every frame in and out
+    /// is recorded to `capture`, if given. Outgoing messages are scheduled
+    /// according to `outbound`, see `outbound.rs`.
+    ///
+    /// The receiver closes once the websocket does.
+    pub fn from_stream(
+        websocket_stream: WebSocketStream<TcpStream>,
+        capture: Option<ComputerCapture>,
+        outbound: OutboundConfig,
+    ) -> (Self, mpsc::UnboundedReceiver<String>) {
+        // Split the websocket into its sender and receiver components
+        let (mut websocket_sender, mut websocket_receiver) = websocket_stream.split();
+
+        // Outgoing queue
+        let (outgoing_queue, mut outgoing_rx) = outbound::channel(outbound);
+        // Incoming channel
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel::<String>();
+
+        // set up threads to send the contents of the channels out the websocket, and vice-versa
+
+        // Outgoing
+        let outgoing_capture = capture.clone();
+        tokio::spawn(async move {
+            while let Some(outgoing) = outgoing_rx.recv().await {
+                // Recorded as it actually goes out, not when it was queued.
+                if let Some(capture) = &outgoing_capture {
+                    capture.record(Direction::Outbound, &outgoing);
+                }
+                // Stop sending if the socket went away.
+                if let Err(err) = websocket_sender.send(Message::Text(outgoing.into())).await {
+                    error!("Failed to send message down websocket! {err}");
+                    break;
+                }
+            }
+            // Turn away anything sent from now on.
+            outgoing_rx.close();
+        });
+
+        // Incoming
+        tokio::spawn(async move {
+            while let Some(incoming) = websocket_receiver.next().await {
+                let message = match incoming {
+                    Ok(ok) => ok,
+                    Err(err) => {
+                        warn!("Failed to read from websocket, closing it. {err}");
+                        break;
+                    }
+                };
+                match message {
+                    Message::Text(text) => {
+                        if let Some(capture) = &capture {
+                            capture.record(Direction::Inbound, &text);
+                        }
+                        // Nobody is listening anymore.
+                        if incoming_tx.send(text.to_string()).is_err() {
+                            break;
+                        }
+                    }
+                    Message::Close(_) => break,
+                    // Pings are answered for us, and turtles never send binary.
+                    _ => continue,
+                }
+            }
+        });
+
+        (
+            Self {
+                outbound: Arc::new(outgoing_queue),
+            },
+            incoming_rx,
+        )
+    }
+
+    /// Send a message out the websocket.
+    ///
+    /// The message is wrapped in a packet before sending. Returns the UUID of
+    /// that packet.
+    pub fn send(&self, message: ServerMessage) -> Result<String, CCWebsocketError> {
+        let packet = message.into_packet();
+        self.send_packet(&packet)?;
+        Ok(packet.uuid)
+    }
+
+    /// Send an already built packet out the websocket. Used when re-sending a
+    /// packet, since it must keep its original UUID.
+    ///
+    /// Fails with [CCWebsocketError::Full] if too many packets of the same
+    /// priority are already waiting to go out.
+    pub fn send_packet(&self, packet: &ServerPacket) -> Result<(), CCWebsocketError> {
+        let json = packet.to_json().map_err(CCWebsocketError::Serialization)?;
+        self.outbound.push(Priority::of(&packet.message), json)
+    }
+
+    /// Same as [CCWebsocket::send_packet], but waits for room instead of
+    /// failing when too many packets are waiting to go out.
+    pub async fn send_packet_wait(&self, packet: &ServerPacket) -> Result<(), CCWebsocketError> {
+        let json = packet.to_json().map_err(CCWebsocketError::Serialization)?;
+        self.outbound
+            .push_wait(Priority::of(&packet.message), json)
+            .await
+    }
+}
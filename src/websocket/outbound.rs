@@ -0,0 +1,400 @@
+// Scheduling what goes out to each turtle.
+//
+// Turtles can only take in so much at once, since they have to yield to pick
+// up every packet. See `shelved_info.md`, which puts the practical limit at
+// around 20 packets a second. So instead of throwing everything down the
+// websocket as soon as it is sent, every connection has its own queue:
+// - Packets are sorted into priority classes, and the most important class
+//   always goes first. An ack should never be stuck behind a pile of debug
+//   replies.
+// - Packets leave no faster than the configured budget allows.
+// - Every class only holds so many packets. Once it is full, senders are told
+//   so (or made to wait), instead of the queue growing forever.
+
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use crate::{
+    minecraft::computercraft::computer_types::server_message::ServerMessage,
+    websocket::CCWebsocketError,
+};
+
+/// How urgent an outgoing message is. Every queued [Priority::Control] message
+/// goes out before any [Priority::Task] message, and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Keeps the connection itself working: acks, pings, cancelling tasks, etc.
+    Control,
+    /// Giving the turtle work, and asking it about that work.
+    Task,
+    /// Only used in tests and debugging.
+    Debug,
+}
+
+impl Priority {
+    /// Every priority, most urgent first.
+    pub const ALL: [Priority; 3] = [Priority::Control, Priority::Task, Priority::Debug];
+
+    /// Which class a message goes out in.
+    pub fn of(message: &ServerMessage) -> Self {
+        match message {
+            ServerMessage::Ack { .. }
+            | ServerMessage::CancelTask
+            | ServerMessage::Challenge { .. }
+            | ServerMessage::HelloReply { .. }
+            | ServerMessage::Ping
+            | ServerMessage::ResyncWorld => Priority::Control,
+            ServerMessage::AssignTask(_)
+            | ServerMessage::Provisioned { .. }
+            | ServerMessage::Request { .. } => Priority::Task,
+            ServerMessage::DebugReply(_) => Priority::Debug,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::Control => write!(f, "control"),
+            Priority::Task => write!(f, "task"),
+            Priority::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+/// How fast packets go out, and how many can wait to go out.
+#[derive(Debug, Clone, Copy)]
+pub struct OutboundConfig {
+    /// How many packets can go out per second, on average. Zero means there is
+    /// no limit.
+    pub packets_per_second: u32,
+    /// How many packets can go out back to back after a quiet spell, before
+    /// the rate limit kicks in.
+    pub burst: u32,
+    /// How many packets each priority class can hold before senders are
+    /// turned away.
+    pub capacity: usize,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            packets_per_second: 20,
+            burst: 10,
+            capacity: 64,
+        }
+    }
+}
+
+/// A token bucket, refilled at `packets_per_second`, holding at most `burst`
+/// tokens. Every packet sent takes a token.
+#[derive(Debug, Clone, Copy)]
+pub struct RateBudget {
+    packets_per_second: u32,
+    burst: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateBudget {
+    /// A full budget.
+    pub fn new(config: &OutboundConfig, now: Instant) -> Self {
+        // A burst of zero would never let anything through.
+        let burst = f64::from(config.burst.max(1));
+        Self {
+            packets_per_second: config.packets_per_second,
+            burst,
+            tokens: burst,
+            refilled: now,
+        }
+    }
+
+    /// Take a token if there is one. Otherwise returns how long until there
+    /// will be.
+    pub fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        if self.packets_per_second == 0 {
+            return Ok(());
+        }
+        let rate = f64::from(self.packets_per_second);
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.burst);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+/// Create a new outbound queue. Packets pushed into the [OutboundQueue] come
+/// out of the [OutboundReceiver], most urgent first, at the configured rate.
+///
+/// The receiver stops once the queue is dropped and everything in it has been
+/// received.
+pub fn channel(config: OutboundConfig) -> (OutboundQueue, OutboundReceiver) {
+    let shared = Arc::new(Shared {
+        config,
+        state: Mutex::new(State {
+            classes: Default::default(),
+            closed: false,
+        }),
+        queued: Notify::new(),
+        freed: Notify::new(),
+    });
+    (
+        OutboundQueue {
+            shared: shared.clone(),
+        },
+        OutboundReceiver {
+            budget: RateBudget::new(&config, Instant::now()),
+            shared,
+        },
+    )
+}
+
+struct Shared {
+    config: OutboundConfig,
+    state: Mutex<State>,
+    /// Something was pushed, or the queue closed.
+    queued: Notify,
+    /// Something was received, or the queue closed.
+    freed: Notify,
+}
+
+struct State {
+    /// Serialized packets, indexed by [Priority::index].
+    classes: [VecDeque<String>; 3],
+    closed: bool,
+}
+
+impl Shared {
+    /// Nothing in here can panic halfway through changing the state, so
+    /// poisoning is ignored.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.queued.notify_one();
+        self.freed.notify_waiters();
+    }
+}
+
+/// The sending half of an outbound queue. Closes the queue when dropped.
+pub struct OutboundQueue {
+    shared: Arc<Shared>,
+}
+
+impl OutboundQueue {
+    /// Queue up a serialized packet, failing right away if its class is full.
+    pub fn push(&self, priority: Priority, json: String) -> Result<(), CCWebsocketError> {
+        let mut state = self.shared.lock();
+        if state.closed {
+            return Err(CCWebsocketError::Closed);
+        }
+        let class = &mut state.classes[priority.index()];
+        if class.len() >= self.shared.config.capacity {
+            return Err(CCWebsocketError::Full(priority));
+        }
+        class.push_back(json);
+        drop(state);
+        self.shared.queued.notify_one();
+        Ok(())
+    }
+
+    /// Queue up a serialized packet, waiting for room in its class if needed.
+    pub async fn push_wait(
+        &self,
+        priority: Priority,
+        json: String,
+    ) -> Result<(), CCWebsocketError> {
+        loop {
+            // Registered before checking, so a receive in between is not
+            // missed.
+            let freed = self.shared.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            match self.push(priority, json.clone()) {
+                Err(CCWebsocketError::Full(_)) => freed.await,
+                other => return other,
+            }
+        }
+    }
+
+    /// How many packets of each class are waiting to go out, most urgent
+    /// first.
+    pub fn queued(&self) -> [usize; 3] {
+        let state = self.shared.lock();
+        Priority::ALL.map(|priority| state.classes[priority.index()].len())
+    }
+
+    /// Stop taking new packets. Anything already queued still comes out of the
+    /// receiver.
+    pub fn close(&self) {
+        self.shared.close();
+    }
+}
+
+impl Drop for OutboundQueue {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+/// The receiving half of an outbound queue, see [channel].
+pub struct OutboundReceiver {
+    shared: Arc<Shared>,
+    budget: RateBudget,
+}
+
+impl OutboundReceiver {
+    /// Wait for the next packet that should go out, and for the budget to let
+    /// it go. Returns None once the queue is closed and empty.
+    pub async fn recv(&mut self) -> Option<String> {
+        loop {
+            let queued = self.shared.queued.notified();
+            {
+                let state = self.shared.lock();
+                let empty = state.classes.iter().all(VecDeque::is_empty);
+                if empty && state.closed {
+                    return None;
+                }
+                if !empty {
+                    break;
+                }
+            }
+            queued.await;
+        }
+
+        while let Err(wait) = self.budget.take(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+
+        // Picked only once the budget allows it, so anything more urgent that
+        // came in while waiting goes first.
+        let mut state = self.shared.lock();
+        let json = state.classes.iter_mut().find_map(VecDeque::pop_front);
+        drop(state);
+        self.shared.freed.notify_waiters();
+        json
+    }
+
+    /// Stop taking new packets, for when there is nowhere to send them.
+    pub fn close(&self) {
+        self.shared.close();
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[tokio::test]
+/// More urgent packets should always jump the queue.
+async fn outbound_priority_order() {
+    let (queue, mut receiver) = channel(OutboundConfig {
+        packets_per_second: 0,
+        ..Default::default()
+    });
+    queue.push(Priority::Debug, "debug".into()).unwrap();
+    queue.push(Priority::Task, "task 1".into()).unwrap();
+    queue.push(Priority::Control, "control".into()).unwrap();
+    queue.push(Priority::Task, "task 2".into()).unwrap();
+    assert_eq!(queue.queued(), [1, 2, 1]);
+
+    for expected in ["control", "task 1", "task 2", "debug"] {
+        assert_eq!(receiver.recv().await.unwrap(), expected);
+    }
+
+    // Everything already queued still comes out after closing.
+    queue.push(Priority::Debug, "last".into()).unwrap();
+    drop(queue);
+    assert_eq!(receiver.recv().await.unwrap(), "last");
+    assert!(receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+/// Full classes should turn senders away, or make them wait, without blocking
+/// any other class.
+async fn outbound_backpressure() {
+    let (queue, mut receiver) = channel(OutboundConfig {
+        packets_per_second: 0,
+        capacity: 2,
+        ..Default::default()
+    });
+    queue.push(Priority::Debug, "1".into()).unwrap();
+    queue.push(Priority::Debug, "2".into()).unwrap();
+    assert!(matches!(
+        queue.push(Priority::Debug, "3".into()),
+        Err(CCWebsocketError::Full(Priority::Debug))
+    ));
+    queue.push(Priority::Control, "ack".into()).unwrap();
+
+    let queue = Arc::new(queue);
+    let waiting = tokio::spawn({
+        let queue = queue.clone();
+        async move { queue.push_wait(Priority::Debug, "3".into()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!waiting.is_finished());
+
+    assert_eq!(receiver.recv().await.unwrap(), "ack");
+    assert_eq!(receiver.recv().await.unwrap(), "1");
+    waiting.await.unwrap().unwrap();
+    assert_eq!(queue.queued(), [0, 0, 2]);
+
+    receiver.close();
+    assert!(matches!(
+        queue.push(Priority::Control, "ack".into()),
+        Err(CCWebsocketError::Closed)
+    ));
+}
+
+#[test]
+/// The budget should allow a burst, then settle down to the configured rate.
+fn outbound_rate_budget() {
+    let config = OutboundConfig {
+        packets_per_second: 20,
+        burst: 3,
+        capacity: 1,
+    };
+    let start = Instant::now();
+    let mut budget = RateBudget::new(&config, start);
+    for _ in 0..3 {
+        assert!(budget.take(start).is_ok());
+    }
+    let wait = budget.take(start).unwrap_err();
+    assert_eq!(wait.as_millis(), 50);
+
+    // A token every 50ms, never more than the burst.
+    assert!(budget.take(start + Duration::from_millis(50)).is_ok());
+    assert!(budget.take(start + Duration::from_millis(60)).is_err());
+    let later = start + Duration::from_secs(60);
+    let sent = (0..10).filter(|_| budget.take(later).is_ok()).count();
+    assert_eq!(sent, 3);
+
+    // No limit at all.
+    let mut unlimited = RateBudget::new(
+        &OutboundConfig {
+            packets_per_second: 0,
+            ..config
+        },
+        start,
+    );
+    assert!((0..1000).all(|_| unlimited.take(start).is_ok()));
+}
@@ -0,0 +1,455 @@
+// Ack / retransmit layer on top of the raw websocket.
+//
+// Turtles re-send packets with the same UUID until we ack them, so we may see
+// the same packet more than once. We ack every packet we get, and only hand
+// each UUID up to the caller once.
+//
+// Going the other way, messages that need to be acked (see
+// `ServerMessage::needs_ack`) are kept around until the turtle acks them, so
+// they can be re-sent if the turtle never got them.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use tokio::sync::mpsc;
+
+use crate::{
+    minecraft::computercraft::computer_types::{
+        packet_types::{PacketDecodeError, TurtlePacket},
+        server_message::{ServerMessage, ServerPacket},
+    },
+    websocket::{CCWebsocket, CCWebsocketError, rpc::RpcChannel},
+};
+
+/// How many packet UUIDs we remember per computer by default. Turtles only
+/// retry a handful of times in quick succession, so this is plenty.
+pub const DEFAULT_SEEN_WINDOW: usize = 256;
+
+/// The most recently seen packet UUIDs from a single computer. Once full, the
+/// oldest UUID is forgotten to make room for the new one.
+#[derive(Debug)]
+struct SeenWindow {
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+    capacity: usize,
+}
+
+impl SeenWindow {
+    fn new(capacity: usize) -> Self {
+        Self {
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remember a UUID. Returns false if we have already seen it.
+    fn insert(&mut self, uuid: &str) -> bool {
+        if self.seen.contains(uuid) {
+            return false;
+        }
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.order.push_back(uuid.to_string());
+        self.seen.insert(uuid.to_string());
+        true
+    }
+}
+
+/// An outbound packet that the turtle has not acknowledged yet.
+#[derive(Debug, Clone)]
+pub struct PendingPacket {
+    /// The packet, exactly as it was sent.
+    pub packet: ServerPacket,
+    /// When the packet was last sent.
+    pub last_sent: Instant,
+    /// How many times this packet has been sent, including the first time.
+    /// Zero if it was queued while the turtle was not connected.
+    pub attempts: u32,
+}
+
+/// What happened to an incoming packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Received {
+    /// First time seeing this packet.
+    Fresh,
+    /// We have already handled this packet. It should still be acked, since the
+    /// turtle probably never got our first ack.
+    Duplicate,
+    /// The turtle acknowledged one of our packets.
+    Ack,
+    /// The turtle answered a ping. Pongs are never acked.
+    Pong,
+}
+
+/// All of the delivery state for a single computer.
+///
+/// This does not touch the websocket at all, so it can outlive a connection and
+/// be handed to the next one when the turtle reconnects.
+#[derive(Debug)]
+pub struct ReliableLink {
+    seen: SeenWindow,
+    /// Outbound packets waiting on an ack, keyed by UUID.
+    unacked: HashMap<String, PendingPacket>,
+}
+
+impl ReliableLink {
+    /// Make a new link that remembers up to `window` incoming UUIDs.
+    pub fn new(window: usize) -> Self {
+        Self {
+            seen: SeenWindow::new(window),
+            unacked: HashMap::new(),
+        }
+    }
+
+    /// Sort an incoming packet into fresh, duplicate, ack, or pong.
+    pub fn receive(&mut self, packet: &TurtlePacket) -> Received {
+        if let TurtlePacket::Ack(ack) = packet {
+            if self.unacked.remove(&ack.acked).is_none() {
+                // Already acked, or we never sent it. Either way, nothing to do.
+                debug!("Computer {} acked unknown packet {}", ack.from, ack.acked);
+            }
+            return Received::Ack;
+        }
+        if let TurtlePacket::Pong(_) = packet {
+            return Received::Pong;
+        }
+        if self.seen.insert(packet.uuid()) {
+            Received::Fresh
+        } else {
+            Received::Duplicate
+        }
+    }
+
+    /// Keep track of a packet that was just sent, if it needs an ack.
+    pub fn track(&mut self, packet: &ServerPacket) {
+        if !packet.message.needs_ack() {
+            return;
+        }
+        self.unacked.insert(
+            packet.uuid.clone(),
+            PendingPacket {
+                packet: packet.clone(),
+                last_sent: Instant::now(),
+                attempts: 1,
+            },
+        );
+    }
+
+    /// Hold on to a packet for a turtle that is not connected right now, so it
+    /// goes out as soon as the turtle comes back. Returns false if the packet
+    /// does not need an ack, since there is no point in sending those late.
+    pub fn queue(&mut self, packet: ServerPacket) -> bool {
+        if !packet.message.needs_ack() {
+            return false;
+        }
+        self.unacked.insert(
+            packet.uuid.clone(),
+            PendingPacket {
+                packet,
+                last_sent: Instant::now(),
+                attempts: 0,
+            },
+        );
+        true
+    }
+
+    /// Take over every unacked packet from another link to the same computer.
+    pub fn absorb(&mut self, other: ReliableLink) {
+        for (uuid, pending) in other.unacked {
+            self.unacked.entry(uuid).or_insert(pending);
+        }
+    }
+
+    /// Every outbound packet that has not been acked yet.
+    pub fn unacked(&self) -> impl Iterator<Item = &PendingPacket> {
+        self.unacked.values()
+    }
+
+    /// Get every unacked packet that was last sent more than `older_than` ago,
+    /// oldest first. Mark each one with [ReliableLink::resent] once it has
+    /// actually gone out again.
+    pub fn due_for_resend(&self, older_than: Duration) -> Vec<ServerPacket> {
+        let now = Instant::now();
+        let mut due: Vec<&PendingPacket> = self
+            .unacked
+            .values()
+            .filter(|pending| now.duration_since(pending.last_sent) >= older_than)
+            .collect();
+        // Re-send in the order they were originally sent.
+        due.sort_by_key(|pending| pending.packet.timestamp);
+        due.into_iter()
+            .map(|pending| pending.packet.clone())
+            .collect()
+    }
+
+    /// Mark an unacked packet as sent again just now.
+    pub fn resent(&mut self, uuid: &str) {
+        if let Some(pending) = self.unacked.get_mut(uuid) {
+            pending.last_sent = Instant::now();
+            pending.attempts += 1;
+        }
+    }
+}
+
+impl Default for ReliableLink {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_WINDOW)
+    }
+}
+
+/// A websocket connection to a single computer, with acks and deduplication.
+pub struct ReliableWebsocket {
+    /// The ID of the computer on the other end.
+    id: u16,
+    socket: CCWebsocket,
+    incoming: mpsc::UnboundedReceiver<String>,
+    link: ReliableLink,
+    rpc: RpcChannel,
+    /// When anything at all last came in from the computer.
+    last_heard: Instant,
+}
+
+impl ReliableWebsocket {
+    /// Wrap a websocket. Pass in the link from the last connection to this
+    /// computer if there was one, so nothing is delivered twice.
+    pub fn new(
+        id: u16,
+        socket: CCWebsocket,
+        incoming: mpsc::UnboundedReceiver<String>,
+        link: ReliableLink,
+    ) -> Self {
+        Self {
+            id,
+            rpc: RpcChannel::new(socket.clone()),
+            socket,
+            incoming,
+            link,
+            last_heard: Instant::now(),
+        }
+    }
+
+    /// The ID of the computer on the other end.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// When anything at all last came in from the computer, including acks and
+    /// pongs.
+    pub fn last_heard(&self) -> Instant {
+        self.last_heard
+    }
+
+    /// Make calls to this computer. Responses come out of
+    /// [ReliableWebsocket::receive] like any other packet, and have to be
+    /// handed to [RpcChannel::resolve], see `handler.rs`.
+    pub fn rpc(&self) -> RpcChannel {
+        self.rpc.clone()
+    }
+
+    /// Wait for the next packet that we have not seen before. Acks, pongs, and
+    /// duplicates are handled here and never returned.
+    ///
+    /// Returns `None` once the websocket closes.
+    pub async fn receive(&mut self) -> Option<Result<TurtlePacket, PacketDecodeError>> {
+        while let Some(text) = self.incoming.recv().await {
+            self.last_heard = Instant::now();
+            let packet = match TurtlePacket::from_json(&text) {
+                Ok(ok) => ok,
+                Err(err) => return Some(Err(err)),
+            };
+            match self.link.receive(&packet) {
+                Received::Ack | Received::Pong => continue,
+                Received::Duplicate => {
+                    debug!(
+                        "Dropping duplicate packet {} from computer {}",
+                        packet.uuid(),
+                        self.id
+                    );
+                    self.ack(packet.uuid());
+                }
+                Received::Fresh => {
+                    self.ack(packet.uuid());
+                    return Some(Ok(packet));
+                }
+            }
+        }
+        None
+    }
+
+    /// Send a message, keeping a copy around until it is acked if needed.
+    /// Returns the UUID of the sent packet.
+    pub fn send(&mut self, message: ServerMessage) -> Result<String, CCWebsocketError> {
+        let packet = message.into_packet();
+        self.send_packet(&packet)?;
+        Ok(packet.uuid)
+    }
+
+    /// Send an already built packet, keeping a copy around until it is acked if
+    /// needed.
+    pub fn send_packet(&mut self, packet: &ServerPacket) -> Result<(), CCWebsocketError> {
+        self.socket.send_packet(packet)?;
+        self.link.track(packet);
+        Ok(())
+    }
+
+    /// The websocket underneath, for sending without holding on to this.
+    /// Anything sent on it that needs an ack must be handed to
+    /// [ReliableWebsocket::track] once it has gone out.
+    pub fn socket(&self) -> CCWebsocket {
+        self.socket.clone()
+    }
+
+    /// Keep a copy of a packet that was sent on [ReliableWebsocket::socket]
+    /// until it is acked, if needed.
+    pub fn track(&mut self, packet: &ServerPacket) {
+        self.link.track(packet);
+    }
+
+    /// Same as [ReliableWebsocket::send_packet], but waits for room instead of
+    /// failing when too many packets are waiting to go out.
+    pub async fn send_packet_wait(
+        &mut self,
+        packet: &ServerPacket,
+    ) -> Result<(), CCWebsocketError> {
+        self.socket.send_packet_wait(packet).await?;
+        self.link.track(packet);
+        Ok(())
+    }
+
+    /// Every outbound packet that has not been acked yet.
+    pub fn unacked(&self) -> impl Iterator<Item = &PendingPacket> {
+        self.link.unacked()
+    }
+
+    /// Re-send everything that has gone unacked for longer than `older_than`.
+    /// Returns how many packets were re-sent.
+    ///
+    /// Stops early if the outbound queue fills up. Whatever did not fit is
+    /// still due, and goes out on the next call.
+    pub fn resend_unacked(&mut self, older_than: Duration) -> Result<usize, CCWebsocketError> {
+        let due = self.link.due_for_resend(older_than);
+        for (sent, packet) in due.iter().enumerate() {
+            match self.socket.send_packet(packet) {
+                Ok(()) => self.link.resent(&packet.uuid),
+                Err(CCWebsocketError::Full(_)) => return Ok(sent),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(due.len())
+    }
+
+    /// Pick up where an older connection to the same computer left off. Should
+    /// be called before anything is sent on this connection, since anything
+    /// tracked so far is replaced.
+    ///
+    /// Follow this up with [ReliableWebsocket::resend_unacked], so anything the
+    /// computer missed goes out right away.
+    pub fn resume(&mut self, link: ReliableLink) {
+        self.link = link;
+    }
+
+    /// Close this connection, keeping the delivery state for the next one.
+    /// Every call still waiting on the computer fails, see [RpcChannel::close].
+    pub fn into_link(self) -> ReliableLink {
+        self.rpc.close();
+        self.link
+    }
+
+    /// Acknowledge a turtle packet. Failing to send an ack is not fatal, the
+    /// turtle will just send the packet again.
+    fn ack(&self, uuid: &str) {
+        let ack = ServerMessage::Ack {
+            uuid: uuid.to_string(),
+        };
+        if let Err(err) = self.socket.send(ack) {
+            warn!("Failed to ack packet from computer {}! {err}", self.id);
+        }
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn debug_packet(uuid: &str) -> TurtlePacket {
+    let json = format!(
+        r#"{{"id":1,"uuid":"{uuid}","timestamp":0,"packet_type":"debugging","data":"hi"}}"#
+    );
+    TurtlePacket::from_json(&json).unwrap()
+}
+
+#[test]
+/// The same UUID should only make it through once, until it falls out of the
+/// window.
+fn duplicates_are_dropped() {
+    let mut link = ReliableLink::new(2);
+    assert!(matches!(
+        link.receive(&debug_packet("AAAAAAAA")),
+        Received::Fresh
+    ));
+    assert!(matches!(
+        link.receive(&debug_packet("AAAAAAAA")),
+        Received::Duplicate
+    ));
+    assert!(matches!(
+        link.receive(&debug_packet("BBBBBBBB")),
+        Received::Fresh
+    ));
+    // Pushes A out of the window.
+    assert!(matches!(
+        link.receive(&debug_packet("CCCCCCCC")),
+        Received::Fresh
+    ));
+    assert!(matches!(
+        link.receive(&debug_packet("BBBBBBBB")),
+        Received::Duplicate
+    ));
+    assert!(matches!(
+        link.receive(&debug_packet("AAAAAAAA")),
+        Received::Fresh
+    ));
+}
+
+#[test]
+/// Only messages that need acks are tracked, and acks clear them.
+fn unacked_packets_are_resent() {
+    use crate::minecraft::computercraft::computer_types::tasks::{TaskData, TaskDefinition};
+
+    let mut link = ReliableLink::default();
+    link.track(&ServerMessage::Ping.into_packet());
+    let task = ServerMessage::AssignTask(TaskDefinition {
+        return_to_start: false,
+        return_to_facing: false,
+        fuel_buffer: 0,
+        task_data: TaskData::NormalizeHeight,
+    })
+    .into_packet();
+    link.track(&task);
+    assert_eq!(link.unacked().count(), 1);
+
+    // Not old enough yet.
+    assert!(link.due_for_resend(Duration::from_secs(60)).is_empty());
+    let resend = link.due_for_resend(Duration::ZERO);
+    assert_eq!(resend, vec![task.clone()]);
+    // Only counts once it actually went out.
+    assert_eq!(link.unacked().next().unwrap().attempts, 1);
+    link.resent(&task.uuid);
+    assert_eq!(link.unacked().next().unwrap().attempts, 2);
+    assert!(link.due_for_resend(Duration::from_secs(60)).is_empty());
+
+    let json = format!(
+        r#"{{"id":1,"uuid":"ZZZZZZZZ","timestamp":0,"packet_type":"ack","data":{{"uuid":"{}"}}}}"#,
+        task.uuid
+    );
+    let ack = TurtlePacket::from_json(&json).unwrap();
+    assert!(matches!(link.receive(&ack), Received::Ack));
+    assert_eq!(link.unacked().count(), 0);
+}
@@ -0,0 +1,266 @@
+// Request / response calls to a turtle.
+//
+// Each request gets a fresh id, and the turtle echoes that id back in its
+// response, so any number of calls can be waiting on the same turtle at once.
+
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use log::warn;
+use tokio::sync::oneshot;
+
+use crate::{
+    minecraft::computercraft::computer_types::{
+        packet_types::ResponsePacket,
+        rpc_types::{TurtleRequest, TurtleResponse},
+        server_message::{ServerMessage, new_packet_uuid},
+    },
+    websocket::{CCWebsocket, CCWebsocketError},
+};
+
+type Waiting = oneshot::Sender<Result<TurtleResponse, String>>;
+
+/// Reasons a call to a turtle did not get an answer.
+#[derive(Debug)]
+pub enum RpcError {
+    /// The request could not be sent.
+    Send(CCWebsocketError),
+    /// The turtle did not answer in time.
+    TimedOut,
+    /// The channel was dropped or closed before the turtle answered, IE the
+    /// turtle disconnected.
+    Dropped,
+    /// The turtle answered, but could not do what we asked.
+    Turtle(String),
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Send(error) => write!(f, "failed to send request: {error}"),
+            RpcError::TimedOut => write!(f, "turtle did not respond in time"),
+            RpcError::Dropped => write!(f, "channel closed before the turtle responded"),
+            RpcError::Turtle(error) => write!(f, "turtle could not respond: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Makes calls to a single turtle. Cheap to clone, every clone shares the same
+/// set of outstanding calls.
+///
+/// Something still has to feed responses in with [RpcChannel::resolve],
+/// [PacketHandler::handle] does this for the server.
+///
+/// [PacketHandler::handle]: crate::websocket::handler::PacketHandler::handle
+#[derive(Clone)]
+pub struct RpcChannel {
+    socket: CCWebsocket,
+    /// Calls waiting on a response, keyed by request id.
+    pending: Arc<DashMap<String, Waiting>>,
+    /// Set once the connection is gone, see [RpcChannel::close].
+    closed: Arc<AtomicBool>,
+}
+
+/// Removes a call from the pending calls once nobody is waiting on it anymore,
+/// including when the caller gives up on the call's future.
+struct PendingCall<'a> {
+    pending: &'a DashMap<String, Waiting>,
+    id: String,
+}
+
+impl Drop for PendingCall<'_> {
+    fn drop(&mut self) {
+        self.pending.remove(&self.id);
+    }
+}
+
+impl RpcChannel {
+    pub fn new(socket: CCWebsocket) -> Self {
+        Self {
+            socket,
+            pending: Arc::new(DashMap::new()),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Ask the turtle something, and wait up to `timeout` for the answer.
+    pub async fn call(
+        &self,
+        request: TurtleRequest,
+        timeout: Duration,
+    ) -> Result<TurtleResponse, RpcError> {
+        let id = new_packet_uuid();
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id.clone(), tx);
+        let _call = PendingCall {
+            pending: &self.pending,
+            id: id.clone(),
+        };
+        // Checked after inserting, so a call can't sneak in while closing.
+        if self.closed.load(Ordering::Acquire) {
+            return Err(RpcError::Dropped);
+        }
+
+        let message = ServerMessage::Request { id, request };
+        if let Err(err) = self.socket.send(message) {
+            return Err(RpcError::Send(err));
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(Ok(response))) => Ok(response),
+            Ok(Ok(Err(error))) => Err(RpcError::Turtle(error)),
+            Ok(Err(_)) => Err(RpcError::Dropped),
+            Err(_) => Err(RpcError::TimedOut),
+        }
+    }
+
+    /// Fail every outstanding call with [RpcError::Dropped], along with any
+    /// call made from now on. Called once the connection to the turtle is
+    /// gone, since nothing will ever answer.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        // Dropping the senders wakes up everyone waiting.
+        self.pending.clear();
+    }
+
+    /// Hand a response to whoever is waiting on it. Returns false if nobody
+    /// was, IE the call already timed out.
+    pub fn resolve(&self, packet: ResponsePacket) -> bool {
+        let Some((_, waiting)) = self.pending.remove(&packet.id) else {
+            warn!(
+                "Computer {} responded to request {}, but nobody was waiting on it.",
+                packet.from, packet.id
+            );
+            return false;
+        };
+        // The caller may have given up between us removing it and sending.
+        waiting.send(packet.result).is_ok()
+    }
+
+    /// How many calls are waiting on a response.
+    pub fn outstanding(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::websocket::outbound::{self, OutboundConfig, OutboundReceiver};
+
+#[cfg(test)]
+use crate::minecraft::computercraft::computer_types::{
+    packet_types::TurtlePacket, server_message::ServerPacket,
+};
+
+/// Pretend to be a turtle, answering the next request with `response`.
+#[cfg(test)]
+async fn answer_next(outgoing: &mut OutboundReceiver, rpc: &RpcChannel, response: &str) {
+    let sent: ServerPacket = serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+    let ServerMessage::Request { id, .. } = sent.message else {
+        panic!("Expected a request!")
+    };
+    let json = format!(
+        r#"{{"id":1,"uuid":"ABCDEFGH","timestamp":0,"packet_type":"response","data":{{"id":"{id}",{response}}}}}"#
+    );
+    let TurtlePacket::Response(packet) = TurtlePacket::from_json(&json).unwrap() else {
+        panic!("Expected a response!")
+    };
+    assert!(rpc.resolve(packet));
+}
+
+#[tokio::test]
+/// Responses should find their way back to the right call.
+async fn rpc_round_trip() {
+    let (outbound, mut outgoing_rx) = outbound::channel(OutboundConfig::default());
+    let rpc = RpcChannel::new(CCWebsocket {
+        outbound: Arc::new(outbound),
+    });
+
+    let call = tokio::spawn({
+        let rpc = rpc.clone();
+        async move { rpc.call(TurtleRequest::Fuel, Duration::from_secs(5)).await }
+    });
+    answer_next(
+        &mut outgoing_rx,
+        &rpc,
+        r#""response":{"kind":"fuel","level":10,"limit":20}"#,
+    )
+    .await;
+    let response = call.await.unwrap().unwrap();
+    assert!(matches!(
+        response,
+        TurtleResponse::Fuel {
+            level: Some(10),
+            limit: Some(20)
+        }
+    ));
+
+    let call = tokio::spawn({
+        let rpc = rpc.clone();
+        async move {
+            rpc.call(TurtleRequest::Walkback, Duration::from_secs(5))
+                .await
+        }
+    });
+    answer_next(&mut outgoing_rx, &rpc, r#""error":"unknown request""#).await;
+    assert!(matches!(call.await.unwrap(), Err(RpcError::Turtle(_))));
+    assert_eq!(rpc.outstanding(), 0);
+}
+
+#[tokio::test]
+/// Calls that time out should not leak.
+async fn rpc_timeout() {
+    let (outbound, _outgoing_rx) = outbound::channel(OutboundConfig::default());
+    let rpc = RpcChannel::new(CCWebsocket {
+        outbound: Arc::new(outbound),
+    });
+    let result = rpc
+        .call(TurtleRequest::Position, Duration::from_millis(10))
+        .await;
+    assert!(matches!(result, Err(RpcError::TimedOut)));
+    assert_eq!(rpc.outstanding(), 0);
+
+    // Nor should calls that are given up on.
+    let given_up = tokio::time::timeout(
+        Duration::from_millis(10),
+        rpc.call(TurtleRequest::Position, Duration::from_secs(5)),
+    )
+    .await;
+    assert!(given_up.is_err());
+    assert_eq!(rpc.outstanding(), 0);
+}
+
+#[tokio::test]
+/// Closing the channel should fail every call, waiting or not.
+async fn rpc_close() {
+    let (outbound, _outgoing_rx) = outbound::channel(OutboundConfig::default());
+    let rpc = RpcChannel::new(CCWebsocket {
+        outbound: Arc::new(outbound),
+    });
+    let call = tokio::spawn({
+        let rpc = rpc.clone();
+        async move { rpc.call(TurtleRequest::Fuel, Duration::from_secs(5)).await }
+    });
+    while rpc.outstanding() == 0 {
+        tokio::task::yield_now().await;
+    }
+    rpc.close();
+    assert!(matches!(call.await.unwrap(), Err(RpcError::Dropped)));
+    assert!(matches!(
+        rpc.call(TurtleRequest::Fuel, Duration::from_secs(5)).await,
+        Err(RpcError::Dropped)
+    ));
+    assert_eq!(rpc.outstanding(), 0);
+}
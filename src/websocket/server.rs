@@ -0,0 +1,1269 @@
+// The websocket server every turtle connects to.
+//
+// Every connection goes through the same steps:
+// - The HTTP upgrade, where the computer ID is pulled out of the headers.
+// - The hello handshake, see `handshake.rs`. If the server has a token file,
+//   this is also where the turtle proves the computer ID is really its own,
+//   see `auth.rs`.
+// - Taking over from any older connection to the same computer.
+// - Forwarding packets into the central event channel until it closes.
+//
+// Turtles that are not connected keep their place. Anything sent to them that
+// needs an ack is held on to, and goes out as soon as they reconnect.
+//
+// Nothing a turtle sends us should be able to take down the server, so every
+// failure along the way is logged and only closes that one connection.
+
+use std::{
+    fmt::Display,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        Arc, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use log::{error, info, warn};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        handshake::server::{Callback, ErrorResponse, Request, Response},
+        http::StatusCode,
+    },
+};
+
+use crate::{
+    minecraft::{
+        computercraft::computer_types::{
+            hello::LibraryManifest,
+            packet_types::{PacketDecodeError, TurtlePacket},
+            server_message::{ServerMessage, ServerPacket},
+            world_delta::DeltaStream,
+        },
+        peripherals::inventory::GenericInventory,
+    },
+    panic_archive::PanicArchive,
+    websocket::{
+        CCWebsocket, CCWebsocketError,
+        auth::TokenStore,
+        capture::CaptureRecorder,
+        handler::PacketHandler,
+        handshake::{Handshake, handshake},
+        liveness::{Liveness, LivenessConfig, TurtleLiveness},
+        outbound::OutboundConfig,
+        reliable::{ReliableLink, ReliableWebsocket},
+        rpc::RpcChannel,
+    },
+    world::WorldModel,
+};
+
+/// Where the server listens if not told otherwise. Must match the default of
+/// the `meshpit.server_url` setting in `networking.lua`.
+pub const DEFAULT_BIND_ADDRESS: &str = "localhost:4816";
+
+/// The only path turtles are allowed to connect on.
+pub const WEBSOCKET_PATH: &str = "/meshpit";
+
+/// The header turtles put their computer ID in.
+pub const COMPUTER_ID_HEADER: &str = "Computer-ID";
+
+/// How the server should run.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// The address to listen on, IE `0.0.0.0:4816`.
+    pub bind_address: String,
+    /// How long a turtle has to say hello after connecting.
+    pub handshake_timeout: Duration,
+    /// How long to wait on an ack before re-sending a packet.
+    pub resend_after: Duration,
+    /// The libraries turtles are checked against during the handshake.
+    pub manifest: LibraryManifest,
+    /// How often to ping turtles, and when to give up on them.
+    pub liveness: LivenessConfig,
+    /// How fast packets go out to each turtle, and how many can wait to go
+    /// out. See `outbound.rs`.
+    pub outbound: OutboundConfig,
+    /// The folder to keep every panic in. Panics are not kept if this is None.
+    pub panic_archive: Option<PathBuf>,
+    /// The file to record every frame to, see `capture.rs`. Added on to if it
+    /// already exists.
+    pub capture: Option<PathBuf>,
+    /// The file every turtle's token is kept in, see `auth.rs`. If set, turtles
+    /// must prove they hold the token for their computer ID when connecting.
+    /// If not, anyone can claim to be any computer.
+    pub tokens: Option<PathBuf>,
+    /// The file the world model is kept in, see `world/mod.rs`. The world is
+    /// only kept in memory if this is None.
+    pub world: Option<PathBuf>,
+    /// How often the world model is saved, if anything changed.
+    pub world_save_every: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let manifest = LibraryManifest::bundled().unwrap_or_else(|err| {
+            warn!(
+                "Failed to hash the bundled libraries, no turtle will be flagged as outdated! {err}"
+            );
+            LibraryManifest::default()
+        });
+        Self {
+            bind_address: DEFAULT_BIND_ADDRESS.to_string(),
+            handshake_timeout: Duration::from_secs(10),
+            resend_after: Duration::from_secs(5),
+            manifest,
+            liveness: LivenessConfig::default(),
+            outbound: OutboundConfig::default(),
+            panic_archive: None,
+            capture: None,
+            tokens: None,
+            world: None,
+            world_save_every: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Everything that happens on the server, in the order it happened per
+/// computer.
+#[derive(Debug)]
+pub enum ServerEvent {
+    /// A computer made it through the handshake. If it was already connected,
+    /// the old connection has already been closed.
+    Connected { id: u16, handshake: Handshake },
+    /// A packet we have not seen before. Acks, duplicates, and responses to
+    /// calls are handled by the server and never show up here.
+    Packet { id: u16, packet: TurtlePacket },
+    /// A packet that could not be decoded.
+    BadPacket { id: u16, error: PacketDecodeError },
+    /// A computer's connection closed, or was replaced by a newer one.
+    Disconnected { id: u16 },
+    /// A computer went silent, spoke up again, or is presumed dead. Connecting
+    /// and disconnecting have their own events.
+    LivenessChanged { id: u16, liveness: Liveness },
+}
+
+/// Reasons a message could not be handed to a computer.
+#[derive(Debug)]
+pub enum ServerError {
+    /// That computer is not connected right now, and the message was not worth
+    /// holding on to.
+    NotConnected(u16),
+    /// Too many messages are already waiting to go out to that computer. Try
+    /// again later, or use [MeshpitServer::send_wait].
+    Busy(u16),
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::NotConnected(id) => write!(f, "computer {id} is not connected"),
+            ServerError::Busy(id) => write!(f, "computer {id} has too many messages waiting"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// A live connection to a computer.
+struct Session {
+    /// Tells apart connections to the same computer, so an old connection
+    /// never cleans up after a newer one.
+    generation: u64,
+    /// Packets to be sent by the connection's task.
+    outbound: mpsc::Sender<ServerPacket>,
+    rpc: RpcChannel,
+    /// Asks the connection to close.
+    stop: oneshot::Sender<()>,
+    /// Closes once the connection has fully shut down and put its link away.
+    stopped: oneshot::Receiver<()>,
+}
+
+impl Session {
+    /// Close the connection, and wait until it has put its link away.
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.stopped.await;
+    }
+}
+
+/// State shared between the server handle and every connection.
+struct Shared {
+    config: ServerConfig,
+    sessions: DashMap<u16, Session>,
+    /// The delivery state of computers that are not connected right now, so
+    /// nothing is delivered twice when they come back.
+    links: DashMap<u16, ReliableLink>,
+    /// Every computer that has ever connected.
+    liveness: DashMap<u16, TurtleLiveness>,
+    /// Everything turtles have told us.
+    handler: PacketHandler,
+    capture: Option<CaptureRecorder>,
+    events: mpsc::UnboundedSender<ServerEvent>,
+    next_generation: AtomicU64,
+}
+
+impl Shared {
+    /// Update a computer's liveness, letting everyone know if it changed.
+    fn update_liveness(
+        &self,
+        id: u16,
+        update: impl FnOnce(&mut TurtleLiveness) -> Option<Liveness>,
+    ) -> Option<Liveness> {
+        let now = Instant::now();
+        let mut turtle = self
+            .liveness
+            .entry(id)
+            .or_insert_with(|| TurtleLiveness::new(now));
+        update(&mut turtle)
+    }
+
+    /// We heard from a connected computer.
+    fn heard_from(&self, id: u16, at: Instant) {
+        if let Some(liveness) = self.update_liveness(id, |turtle| turtle.heard_from(at)) {
+            let _ = self
+                .events
+                .send(ServerEvent::LivenessChanged { id, liveness });
+        }
+    }
+}
+
+/// The running server. Cheap to clone, every clone talks to the same server.
+#[derive(Clone)]
+pub struct MeshpitServer {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+}
+
+impl MeshpitServer {
+    /// Start listening for turtles.
+    ///
+    /// Every turtle that connects is let in, and everything they send comes
+    /// out of the returned receiver. The server stops accepting new
+    /// connections once that receiver is dropped.
+    pub async fn bind(
+        config: ServerConfig,
+    ) -> std::io::Result<(Self, mpsc::UnboundedReceiver<ServerEvent>)> {
+        let listener = TcpListener::bind(&config.bind_address).await?;
+        let local_addr = listener.local_addr()?;
+        info!("Listening for turtles on {local_addr}");
+
+        let panics = match &config.panic_archive {
+            Some(folder) => Some(PanicArchive::open(folder)?),
+            None => None,
+        };
+        let capture = match &config.capture {
+            Some(path) => Some(CaptureRecorder::append(path)?),
+            None => None,
+        };
+        let tokens = match &config.tokens {
+            Some(path) => Some(TokenStore::open(path)?),
+            None => {
+                warn!("No token file was given, turtles will not be authenticated!");
+                None
+            }
+        };
+        let world = match &config.world {
+            Some(path) => WorldModel::open(path)?,
+            None => WorldModel::new(),
+        };
+
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            config,
+            sessions: DashMap::new(),
+            links: DashMap::new(),
+            liveness: DashMap::new(),
+            handler: PacketHandler::new(world, panics, tokens),
+            capture,
+            events: events_tx,
+            next_generation: AtomicU64::new(0),
+        });
+
+        tokio::spawn(accept_loop(listener, shared.clone()));
+        tokio::spawn(watch_liveness(shared.clone()));
+        tokio::spawn(save_world(shared.clone()));
+
+        Ok((Self { shared, local_addr }, events_rx))
+    }
+
+    /// The address the server actually bound to. Useful when binding to port
+    /// 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Send a message to a computer. Returns the UUID of the sent packet.
+    ///
+    /// Messages that need acks are re-sent until the computer acks them. If the
+    /// computer is not connected, those are held on to until it reconnects,
+    /// and everything else is refused.
+    ///
+    /// Fails with [ServerError::Busy] if the computer already has too many
+    /// messages waiting to go out.
+    pub fn send(&self, id: u16, message: ServerMessage) -> Result<String, ServerError> {
+        let mut packet = message.into_packet();
+        let uuid = packet.uuid.clone();
+        if let Some(session) = self.shared.sessions.get(&id) {
+            match session.outbound.try_send(packet) {
+                Ok(()) => return Ok(uuid),
+                Err(mpsc::error::TrySendError::Full(_)) => return Err(ServerError::Busy(id)),
+                // The connection is on its way out.
+                Err(mpsc::error::TrySendError::Closed(unsent)) => packet = unsent,
+            }
+        }
+        self.hold(id, packet)
+    }
+
+    /// Same as [MeshpitServer::send], but waits for room instead of failing
+    /// when the computer has too many messages waiting to go out.
+    pub async fn send_wait(&self, id: u16, message: ServerMessage) -> Result<String, ServerError> {
+        let mut packet = message.into_packet();
+        let uuid = packet.uuid.clone();
+        // Cloned out, so the session is not held locked while waiting.
+        let outbound = self
+            .shared
+            .sessions
+            .get(&id)
+            .map(|session| session.outbound.clone());
+        if let Some(outbound) = outbound {
+            match outbound.send(packet).await {
+                Ok(()) => return Ok(uuid),
+                Err(mpsc::error::SendError(unsent)) => packet = unsent,
+            }
+        }
+        self.hold(id, packet)
+    }
+
+    /// Hold on to a packet for a computer that is not connected, if it is
+    /// worth holding on to.
+    fn hold(&self, id: u16, packet: ServerPacket) -> Result<String, ServerError> {
+        let uuid = packet.uuid.clone();
+        let held = match self.shared.links.get_mut(&id) {
+            Some(mut link) => link.queue(packet),
+            None => {
+                // Only stored if the packet was worth holding on to, so
+                // refused packets don't leave empty links behind.
+                let mut link = ReliableLink::default();
+                let held = link.queue(packet);
+                if held {
+                    self.shared.links.entry(id).or_default().absorb(link);
+                }
+                held
+            }
+        };
+        if held {
+            Ok(uuid)
+        } else {
+            Err(ServerError::NotConnected(id))
+        }
+    }
+
+    /// Make calls to a computer, if it is connected.
+    pub fn rpc(&self, id: u16) -> Option<RpcChannel> {
+        self.shared
+            .sessions
+            .get(&id)
+            .map(|session| session.rpc.clone())
+    }
+
+    /// What we think of a computer right now. `None` if it has never connected.
+    pub fn liveness(&self, id: u16) -> Option<TurtleLiveness> {
+        self.shared.liveness.get(&id).map(|turtle| *turtle)
+    }
+
+    /// The last inventory a turtle sent us. Turtles send a new one every time
+    /// their inventory changes.
+    pub fn inventory(&self, id: u16) -> Option<GenericInventory> {
+        self.shared.handler.inventory(id)
+    }
+
+    /// Where a turtle's world deltas are at. None if it never sent one.
+    pub fn delta_stream(&self, id: u16) -> Option<DeltaStream> {
+        self.shared.handler.delta_stream(id)
+    }
+
+    /// Everything every turtle has seen, merged together. See
+    /// `world/mod.rs`.
+    ///
+    /// No new blocks can be merged in while this is held.
+    pub fn world_model(&self) -> MutexGuard<'_, WorldModel> {
+        self.shared.handler.world_model()
+    }
+
+    /// Save the world model now instead of waiting, IE before shutting down.
+    /// Returns wether anything was written.
+    pub fn save_world(&self) -> std::io::Result<bool> {
+        self.shared.handler.save_world()
+    }
+
+    /// Every panic turtles have sent us, if the server is keeping them. See
+    /// [ServerConfig::panic_archive].
+    ///
+    /// No new panics can be recorded while this is held.
+    pub fn panics(&self) -> Option<MutexGuard<'_, PanicArchive>> {
+        self.shared.handler.panics()
+    }
+
+    /// Every turtle's token, if the server requires them. See
+    /// [ServerConfig::tokens].
+    ///
+    /// Turtles that were not built by another turtle need their token issued
+    /// here, and written to `turtle_token` on the computer by hand.
+    pub fn tokens(&self) -> Option<&TokenStore> {
+        self.shared.handler.tokens()
+    }
+
+    /// Every computer that is connected right now.
+    pub fn connected(&self) -> Vec<u16> {
+        let mut ids: Vec<u16> = self
+            .shared
+            .sessions
+            .iter()
+            .map(|entry| *entry.key())
+            .collect();
+        ids.sort();
+        ids
+    }
+}
+
+async fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    while !shared.events.is_closed() {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                tokio::spawn(serve_connection(stream, address, shared.clone()));
+            }
+            Err(err) => warn!("Failed to accept a connection! {err}"),
+        }
+    }
+    info!("Nobody is listening for turtles anymore, stopping the server.");
+}
+
+/// Check on every computer that has been quiet for too long.
+async fn watch_liveness(shared: Arc<Shared>) {
+    let config = shared.config.liveness;
+    let mut interval = tokio::time::interval(config.ping_every);
+    while !shared.events.is_closed() {
+        interval.tick().await;
+        let now = Instant::now();
+        // Collect first, so nothing is held locked while sending events.
+        let changed: Vec<(u16, Liveness)> = shared
+            .liveness
+            .iter_mut()
+            .filter_map(|mut turtle| {
+                let liveness = turtle.check(now, &config)?;
+                Some((*turtle.key(), liveness))
+            })
+            .collect();
+        for (id, liveness) in changed {
+            match liveness {
+                Liveness::Silent => warn!("Computer {id} has gone silent."),
+                Liveness::PresumedDead => warn!("Computer {id} is presumed dead."),
+                Liveness::Connected | Liveness::Disconnected => {}
+            }
+            let _ = shared
+                .events
+                .send(ServerEvent::LivenessChanged { id, liveness });
+        }
+    }
+}
+
+/// Save the world model every so often, for as long as the server runs.
+async fn save_world(shared: Arc<Shared>) {
+    if shared.config.world.is_none() {
+        return;
+    }
+    let mut interval = tokio::time::interval(shared.config.world_save_every);
+    while !shared.events.is_closed() {
+        interval.tick().await;
+        // Serializing a big world takes a while, so it is done off of the
+        // async threads.
+        let saving = shared.clone();
+        let saved = tokio::task::spawn_blocking(move || saving.handler.save_world()).await;
+        match saved {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => error!("Failed to save the world! {err}"),
+            Err(err) => error!("Saving the world panicked! {err}"),
+        }
+    }
+}
+
+/// Why an upgrade request was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rejection {
+    status: StatusCode,
+    reason: &'static str,
+}
+
+impl Rejection {
+    const fn new(status: StatusCode, reason: &'static str) -> Self {
+        Self { status, reason }
+    }
+
+    /// The HTTP response to send back instead of upgrading.
+    fn into_response(self) -> ErrorResponse {
+        let mut response = ErrorResponse::new(Some(self.reason.to_string()));
+        *response.status_mut() = self.status;
+        response
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.reason, self.status)
+    }
+}
+
+/// Check the upgrade request, and pull out the computer ID.
+fn check_request(request: &Request) -> Result<u16, Rejection> {
+    if request.uri().path() != WEBSOCKET_PATH {
+        return Err(Rejection::new(StatusCode::NOT_FOUND, "Invalid path"));
+    }
+    let Some(header) = request.headers().get(COMPUTER_ID_HEADER) else {
+        return Err(Rejection::new(
+            StatusCode::BAD_REQUEST,
+            "Missing Computer-ID header",
+        ));
+    };
+    header
+        .to_str()
+        .ok()
+        .and_then(|id| id.trim().parse::<u16>().ok())
+        .ok_or(Rejection::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid Computer-ID header",
+        ))
+}
+
+/// Checks websocket upgrade requests, and remembers the computer ID of the one
+/// it let through.
+pub(crate) struct UpgradeCheck<'a> {
+    pub computer_id: &'a mut Option<u16>,
+}
+
+impl Callback for UpgradeCheck<'_> {
+    fn on_request(
+        self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        let id = match check_request(request) {
+            Ok(id) => id,
+            Err(rejection) => {
+                warn!("Rejected websocket at `{}`: {rejection}", request.uri());
+                return Err(rejection.into_response());
+            }
+        };
+        *self.computer_id = Some(id);
+        // CC wants its protocol echoed back, if it asked for one.
+        if let Some(protocol) = request.headers().get("Sec-WebSocket-Protocol") {
+            response
+                .headers_mut()
+                .insert("Sec-WebSocket-Protocol", protocol.clone());
+        }
+        Ok(response)
+    }
+}
+
+/// Waits for room in a connection's outbound queue, then sends a packet.
+type WaitForRoom = Pin<Box<dyn Future<Output = Result<(), CCWebsocketError>> + Send>>;
+
+async fn serve_connection(stream: TcpStream, address: SocketAddr, shared: Arc<Shared>) {
+    let mut computer_id = None;
+    let check = UpgradeCheck {
+        computer_id: &mut computer_id,
+    };
+    let websocket_stream = match accept_hdr_async(stream, check).await {
+        Ok(ok) => ok,
+        Err(err) => {
+            warn!("Failed to accept websocket from {address}! {err}");
+            return;
+        }
+    };
+    // Always set if the upgrade went through.
+    let Some(id) = computer_id else {
+        return;
+    };
+
+    let capture = shared
+        .capture
+        .as_ref()
+        .map(|recorder| recorder.for_computer(id));
+    let (socket, mut incoming) =
+        CCWebsocket::from_stream(websocket_stream, capture, shared.config.outbound);
+    let handshake = match handshake(
+        &socket,
+        &mut incoming,
+        &shared.config.manifest,
+        shared.handler.tokens().map(|tokens| (tokens, id)),
+        shared.config.handshake_timeout,
+    )
+    .await
+    {
+        Ok(ok) => ok,
+        Err(err) => {
+            warn!("Computer {id} at {address} failed its handshake! {err}");
+            return;
+        }
+    };
+    if handshake.hello.from != id {
+        warn!(
+            "Computer {id} says it is computer {} in its hello, trusting the header.",
+            handshake.hello.from
+        );
+    }
+    // The turtle may have rebooted, so its world deltas start over.
+    shared.handler.reset_stream(id);
+
+    // Only one connection per computer. The turtle only reconnects if it
+    // thinks the old connection is dead, so believe it.
+    if let Some((_, stale)) = shared.sessions.remove(&id) {
+        info!("Computer {id} reconnected, replacing its old connection.");
+        stale.stop().await;
+    }
+    let mut websocket = ReliableWebsocket::new(id, socket, incoming, ReliableLink::default());
+
+    let generation = shared.next_generation.fetch_add(1, Ordering::Relaxed);
+    let (outbound_tx, mut outbound_rx) = mpsc::channel(shared.config.outbound.capacity.max(1));
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    let (stopped_tx, stopped_rx) = oneshot::channel();
+    let session = Session {
+        generation,
+        outbound: outbound_tx,
+        rpc: websocket.rpc(),
+        stop: stop_tx,
+        stopped: stopped_rx,
+    };
+    if let Some(raced) = shared.sessions.insert(id, session) {
+        // Another connection from the same computer got in while we were
+        // waiting. Newest wins.
+        raced.stop().await;
+    }
+
+    // Only safe now that new messages go to our session instead of the link.
+    if let Some((_, link)) = shared.links.remove(&id) {
+        websocket.resume(link);
+        match websocket.resend_unacked(Duration::ZERO) {
+            Ok(0) => {}
+            Ok(resent) => info!("Re-sent {resent} packets to computer {id}."),
+            Err(err) => warn!("Failed to re-send to computer {id}! {err}"),
+        }
+    }
+
+    shared.update_liveness(id, |turtle| turtle.connected(Instant::now()));
+    if shared
+        .events
+        .send(ServerEvent::Connected { id, handshake })
+        .is_err()
+    {
+        return;
+    }
+
+    let mut resend = tokio::time::interval(shared.config.resend_after);
+    let mut ping = tokio::time::interval(shared.config.liveness.ping_every);
+    // A packet from `MeshpitServer::send` that did not fit in the outbound
+    // queue, and the wait for room for it. Nothing more is taken from
+    // `outbound_rx` until it goes out, which pushes back on senders.
+    let mut blocked: Option<(ServerPacket, WaitForRoom)> = None;
+    loop {
+        tokio::select! {
+            received = websocket.receive() => {
+                shared.heard_from(id, websocket.last_heard());
+                let event = match received {
+                    Some(Ok(packet)) => {
+                        // Merging into the world and archiving panics can
+                        // take a while, so it is done off of the async
+                        // threads. Still waited on, so packets are handled in
+                        // the order they came in.
+                        let handling = shared.clone();
+                        let calls = websocket.rpc();
+                        let handled = tokio::task::spawn_blocking(move || {
+                            handling.handler.handle(id, packet, Some(&calls))
+                        })
+                        .await;
+                        let handled = match handled {
+                            Ok(ok) => ok,
+                            Err(err) => {
+                                error!("Handling a packet from computer {id} panicked! {err}");
+                                continue;
+                            }
+                        };
+                        if let Some(reply) = handled.reply
+                            && let Err(err) = websocket.send(reply)
+                        {
+                            warn!("Failed to reply to computer {id}! {err}");
+                            if !matches!(err, CCWebsocketError::Full(_)) {
+                                break;
+                            }
+                        }
+                        match handled.packet {
+                            Some(packet) => ServerEvent::Packet { id, packet },
+                            None => continue,
+                        }
+                    }
+                    Some(Err(error)) => ServerEvent::BadPacket { id, error },
+                    None => break,
+                };
+                if shared.events.send(event).is_err() {
+                    break;
+                }
+            }
+            Some(packet) = outbound_rx.recv(), if blocked.is_none() => {
+                match websocket.send_packet(&packet) {
+                    Ok(()) => {}
+                    Err(CCWebsocketError::Full(_)) => {
+                        // Waited on alongside everything else, so the
+                        // connection keeps going in the meantime.
+                        let socket = websocket.socket();
+                        let copy = packet.clone();
+                        let wait: WaitForRoom =
+                            Box::pin(async move { socket.send_packet_wait(&copy).await });
+                        blocked = Some((packet, wait));
+                    }
+                    Err(err) => {
+                        warn!("Failed to send to computer {id}! {err}");
+                        break;
+                    }
+                }
+            }
+            sent = async { blocked.as_mut().expect("Only polled while blocked.").1.as_mut().await }, if blocked.is_some() => {
+                let (packet, _) = blocked.take().expect("Only polled while blocked.");
+                if let Err(err) = sent {
+                    warn!("Failed to send to computer {id}! {err}");
+                    break;
+                }
+                websocket.track(&packet);
+            }
+            _ = resend.tick() => {
+                if let Err(err) = websocket.resend_unacked(shared.config.resend_after) {
+                    warn!("Failed to re-send to computer {id}! {err}");
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                // Pongs never make it out of `receive`, so this is the only
+                // place we hear about them.
+                shared.heard_from(id, websocket.last_heard());
+                if let Err(err) = websocket.send(ServerMessage::Ping) {
+                    warn!("Failed to ping computer {id}! {err}");
+                    // Plenty is already on its way to the turtle.
+                    if !matches!(err, CCWebsocketError::Full(_)) {
+                        break;
+                    }
+                }
+            }
+            _ = &mut stop_rx => break,
+        }
+    }
+
+    let mut link = websocket.into_link();
+    // Anything sent while we were shutting down still needs to go out.
+    if let Some((packet, _)) = blocked {
+        link.queue(packet);
+    }
+    outbound_rx.close();
+    while let Ok(packet) = outbound_rx.try_recv() {
+        link.queue(packet);
+    }
+    shared
+        .sessions
+        .remove_if(&id, |_, session| session.generation == generation);
+    {
+        // Anything queued up after our session was removed.
+        let mut stored = shared.links.entry(id).or_default();
+        let queued = std::mem::replace(stored.value_mut(), link);
+        stored.absorb(queued);
+    }
+    shared.update_liveness(id, |turtle| turtle.disconnected(Instant::now()));
+    let _ = shared.events.send(ServerEvent::Disconnected { id });
+    // Let whoever replaced us know the link is ready.
+    let _ = stopped_tx.send(());
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use futures_util::{SinkExt, StreamExt};
+
+#[cfg(test)]
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{self, client::IntoClientRequest},
+};
+
+#[cfg(test)]
+type TestClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Connect to the server as a computer, without saying hello.
+#[cfg(test)]
+async fn connect_as(
+    address: SocketAddr,
+    path: &str,
+    id: Option<&str>,
+) -> Result<TestClient, tungstenite::Error> {
+    let mut request = format!("ws://{address}{path}")
+        .into_client_request()
+        .unwrap();
+    if let Some(id) = id {
+        request
+            .headers_mut()
+            .insert(COMPUTER_ID_HEADER, id.parse().unwrap());
+    }
+    connect_async(request).await.map(|(client, _)| client)
+}
+
+/// Say hello as a turtle, without waiting on the reply.
+#[cfg(test)]
+async fn send_hello(client: &mut TestClient, id: u16) {
+    use crate::minecraft::computercraft::computer_types::hello::PROTOCOL_VERSION;
+
+    let json = format!(
+        r#"{{"id":{id},"uuid":"HELLOHEL","timestamp":0,"packet_type":"hello","data":{{"protocol_version":{PROTOCOL_VERSION},"libraries":null,"encodings":["compact","json","carrier_pigeon"]}}}}"#
+    );
+    client.send(json.into()).await.unwrap();
+}
+
+/// Pretend to be a turtle booting up, and wait for the server to let it in.
+#[cfg(test)]
+async fn hello(client: &mut TestClient, id: u16) {
+    use crate::minecraft::computercraft::computer_types::{
+        compact::Encoding, hello::HelloResponse,
+    };
+
+    send_hello(client, id).await;
+    let reply = next_server_packet(client).await;
+    assert!(matches!(
+        reply.message,
+        ServerMessage::HelloReply {
+            response: HelloResponse::Accepted,
+            encoding: Encoding::Compact
+        }
+    ));
+}
+
+/// The next packet from the server, skipping pings.
+#[cfg(test)]
+async fn next_server_packet(client: &mut TestClient) -> ServerPacket {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let packet: ServerPacket = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        if packet.message != ServerMessage::Ping {
+            return packet;
+        }
+    }
+}
+
+#[cfg(test)]
+async fn next_event(events: &mut mpsc::UnboundedReceiver<ServerEvent>) -> ServerEvent {
+    tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+/// Skip events until one matches.
+#[cfg(test)]
+async fn wait_for_event(
+    events: &mut mpsc::UnboundedReceiver<ServerEvent>,
+    wanted: impl Fn(&ServerEvent) -> bool,
+) -> ServerEvent {
+    loop {
+        let event = next_event(events).await;
+        if wanted(&event) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test]
+/// Bad upgrade requests get HTTP errors, and do not take down the server.
+async fn bad_handshakes_are_rejected() {
+    let config = ServerConfig {
+        bind_address: "127.0.0.1:0".to_string(),
+        ..Default::default()
+    };
+    let (server, _events) = MeshpitServer::bind(config).await.unwrap();
+    let address = server.local_addr();
+
+    for (path, id, status) in [
+        ("/elsewhere", Some("1"), StatusCode::NOT_FOUND),
+        (WEBSOCKET_PATH, None, StatusCode::BAD_REQUEST),
+        (
+            WEBSOCKET_PATH,
+            Some("not a number"),
+            StatusCode::BAD_REQUEST,
+        ),
+        (WEBSOCKET_PATH, Some("70000"), StatusCode::BAD_REQUEST),
+    ] {
+        match connect_as(address, path, id).await {
+            Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), status),
+            Err(err) => panic!("Expected an HTTP error, got {err}"),
+            Ok(_) => panic!("Connecting to {path} with {id:?} should have failed!"),
+        }
+    }
+
+    // Still up.
+    let mut client = connect_as(address, WEBSOCKET_PATH, Some("1"))
+        .await
+        .unwrap();
+    hello(&mut client, 1).await;
+}
+
+#[tokio::test]
+/// Packets are routed to the event channel, and reconnecting replaces the old
+/// connection.
+async fn sessions_are_replaced_on_reconnect() {
+    let capture = std::env::temp_dir().join(format!("meshpit_server_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&capture);
+    let config = ServerConfig {
+        bind_address: "127.0.0.1:0".to_string(),
+        capture: Some(capture.clone()),
+        ..Default::default()
+    };
+    let (server, mut events) = MeshpitServer::bind(config).await.unwrap();
+    let address = server.local_addr();
+
+    let mut first = connect_as(address, WEBSOCKET_PATH, Some("7"))
+        .await
+        .unwrap();
+    hello(&mut first, 7).await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        ServerEvent::Connected { id: 7, .. }
+    ));
+
+    let debug = r#"{"id":7,"uuid":"DEBUGDEB","timestamp":0,"packet_type":"debugging","data":"hi"}"#;
+    first.send(debug.into()).await.unwrap();
+    assert!(matches!(
+        next_event(&mut events).await,
+        ServerEvent::Packet {
+            id: 7,
+            packet: TurtlePacket::Debugging(_)
+        }
+    ));
+    assert!(matches!(
+        next_server_packet(&mut first).await.message,
+        ServerMessage::Ack { .. }
+    ));
+
+    let mut second = connect_as(address, WEBSOCKET_PATH, Some("7"))
+        .await
+        .unwrap();
+    hello(&mut second, 7).await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        ServerEvent::Disconnected { id: 7 }
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ServerEvent::Connected { id: 7, .. }
+    ));
+    assert_eq!(server.connected(), vec![7]);
+
+    // The new connection remembers what the old one saw.
+    second.send(debug.into()).await.unwrap();
+    assert!(matches!(
+        next_server_packet(&mut second).await.message,
+        ServerMessage::Ack { .. }
+    ));
+
+    server
+        .send(7, ServerMessage::DebugReply("hi".into()))
+        .unwrap();
+    assert!(matches!(
+        next_server_packet(&mut second).await.message,
+        ServerMessage::DebugReply(_)
+    ));
+    // The duplicate was acked, but never handed up.
+    assert!(events.try_recv().is_err());
+
+    let inventory = r#"{"id":7,"uuid":"INVENTOR","timestamp":0,"packet_type":"inventory","data":{"size":2,"slots":[{"item":"minecraft:coal","count":3},null]}}"#;
+    second.send(inventory.into()).await.unwrap();
+    assert!(matches!(
+        next_event(&mut events).await,
+        ServerEvent::Packet {
+            id: 7,
+            packet: TurtlePacket::Inventory(_)
+        }
+    ));
+    let stored = server.inventory(7).unwrap();
+    assert_eq!(stored.slots[0].unwrap().count, 3);
+    assert!(matches!(
+        server.send(8, ServerMessage::Ping),
+        Err(ServerError::NotConnected(8))
+    ));
+    // Nothing was held for it either.
+    assert!(!server.shared.links.contains_key(&8));
+
+    // Everything was recorded, and replays the same way.
+    server.shared.capture.as_ref().unwrap().flush();
+    use crate::{
+        minecraft::computercraft::computer_types::packet_types::PacketType,
+        websocket::capture::Replay,
+    };
+    let replayed: Vec<PacketType> = Replay::from_file(&capture)
+        .unwrap()
+        .fresh_packets()
+        .map(|(_, packet)| packet.packet_type())
+        .collect();
+    assert_eq!(
+        replayed,
+        [
+            PacketType::Hello,
+            PacketType::Debugging,
+            PacketType::Hello,
+            PacketType::Inventory
+        ]
+    );
+    std::fs::remove_file(&capture).unwrap();
+}
+
+#[tokio::test]
+/// Quiet turtles go silent and then die, and anything sent to them while they
+/// were gone is delivered once they come back.
+async fn liveness_and_resumption() {
+    use crate::minecraft::computercraft::computer_types::tasks::{TaskData, TaskDefinition};
+
+    let config = ServerConfig {
+        bind_address: "127.0.0.1:0".to_string(),
+        liveness: LivenessConfig {
+            ping_every: Duration::from_millis(20),
+            silent_after: Duration::from_millis(100),
+            dead_after: Duration::from_millis(400),
+        },
+        ..Default::default()
+    };
+    let (server, mut events) = MeshpitServer::bind(config).await.unwrap();
+    let address = server.local_addr();
+
+    let mut client = connect_as(address, WEBSOCKET_PATH, Some("3"))
+        .await
+        .unwrap();
+    hello(&mut client, 3).await;
+    let is_liveness = |wanted: Liveness| move |event: &ServerEvent| matches!(event, ServerEvent::LivenessChanged { id: 3, liveness } if *liveness == wanted);
+
+    // Never answering pings.
+    wait_for_event(&mut events, is_liveness(Liveness::Silent)).await;
+    let pong = r#"{"id":3,"uuid":"PONGPONG","timestamp":0,"packet_type":"pong","data":{"uuid":"WHATEVER"}}"#;
+    client.send(pong.into()).await.unwrap();
+    wait_for_event(&mut events, is_liveness(Liveness::Connected)).await;
+
+    client.close(None).await.unwrap();
+    wait_for_event(&mut events, |event| {
+        matches!(event, ServerEvent::Disconnected { id: 3 })
+    })
+    .await;
+
+    let task = ServerMessage::AssignTask(TaskDefinition {
+        return_to_start: false,
+        return_to_facing: false,
+        fuel_buffer: 0,
+        task_data: TaskData::NormalizeHeight,
+    });
+    let uuid = server.send(3, task).unwrap();
+    assert!(matches!(
+        server.send(3, ServerMessage::Ping),
+        Err(ServerError::NotConnected(3))
+    ));
+    wait_for_event(&mut events, is_liveness(Liveness::PresumedDead)).await;
+
+    let mut client = connect_as(address, WEBSOCKET_PATH, Some("3"))
+        .await
+        .unwrap();
+    hello(&mut client, 3).await;
+    let resent = next_server_packet(&mut client).await;
+    assert_eq!(resent.uuid, uuid);
+    assert!(matches!(resent.message, ServerMessage::AssignTask(_)));
+    assert_eq!(server.liveness(3).unwrap().state(), Liveness::Connected);
+}
+
+#[tokio::test]
+/// Turtles are asked to resync their world once a delta goes missing.
+async fn world_delta_gaps_are_resynced() {
+    let config = ServerConfig {
+        bind_address: "127.0.0.1:0".to_string(),
+        ..Default::default()
+    };
+    let (server, mut events) = MeshpitServer::bind(config).await.unwrap();
+    let mut client = connect_as(server.local_addr(), WEBSOCKET_PATH, Some("4"))
+        .await
+        .unwrap();
+    hello(&mut client, 4).await;
+
+    let delta = |uuid: &str, sequence: u64, full: bool, x: i64| {
+        format!(
+            r#"{{"id":4,"uuid":"{uuid}","timestamp":0,"packet_type":"world_delta","data":{{"sequence":{sequence},"full":{full},"blocks":null,"positions":{{"x:{x}|y:0|z:0":true}}}}}}"#
+        )
+    };
+    let is_delta = |event: &ServerEvent| {
+        matches!(
+            event,
+            ServerEvent::Packet {
+                id: 4,
+                packet: TurtlePacket::WorldDelta(_)
+            }
+        )
+    };
+
+    for (uuid, sequence, full) in [("DELTAAAA", 0, true), ("DELTAAAB", 1, false)] {
+        client
+            .send(delta(uuid, sequence, full, sequence as i64).into())
+            .await
+            .unwrap();
+        wait_for_event(&mut events, is_delta).await;
+    }
+    assert_eq!(server.delta_stream(4).unwrap().next_sequence(), 2);
+
+    // Delta 2 never made it.
+    client
+        .send(delta("DELTAAAD", 3, false, 3).into())
+        .await
+        .unwrap();
+    wait_for_event(&mut events, is_delta).await;
+    let resync = loop {
+        let packet = next_server_packet(&mut client).await;
+        if packet.message == ServerMessage::ResyncWorld {
+            break packet;
+        }
+    };
+    assert!(server.delta_stream(4).unwrap().awaiting_resync());
+
+    let ack = format!(
+        r#"{{"id":4,"uuid":"ACKACKAC","timestamp":0,"packet_type":"ack","data":{{"uuid":"{}"}}}}"#,
+        resync.uuid
+    );
+    client.send(ack.into()).await.unwrap();
+    client
+        .send(delta("DELTAAAE", 4, true, 4).into())
+        .await
+        .unwrap();
+    wait_for_event(&mut events, is_delta).await;
+    let stream = server.delta_stream(4).unwrap();
+    assert!(!stream.awaiting_resync());
+    assert_eq!(stream.next_sequence(), 5);
+
+    // Blocks make it into the world model too.
+    let stone = r#"{"id":4,"uuid":"DELTAAAF","timestamp":1234,"packet_type":"world_delta","data":{"sequence":5,"full":false,"blocks":{"x:0|y:9|z:0":{"name":"minecraft:stone","pos":{"x":0,"y":9,"z":0},"state":null,"tag":null}},"positions":null}}"#;
+    client.send(stone.into()).await.unwrap();
+    wait_for_event(&mut events, is_delta).await;
+    {
+        let world = server.world_model();
+        let stone = world
+            .get(crate::minecraft::types::CoordinatePosition { x: 0, y: 9, z: 0 })
+            .unwrap();
+        assert_eq!(stone.block.name, "minecraft:stone");
+        assert_eq!((stone.observer, stone.observed_at), (4, 1234));
+    }
+
+    // Saying hello again starts the stream over, the turtle may have rebooted.
+    let mut rebooted = connect_as(server.local_addr(), WEBSOCKET_PATH, Some("4"))
+        .await
+        .unwrap();
+    hello(&mut rebooted, 4).await;
+    wait_for_event(&mut events, |event| {
+        matches!(event, ServerEvent::Connected { id: 4, .. })
+    })
+    .await;
+    assert!(server.delta_stream(4).is_none());
+}
+
+#[tokio::test]
+/// With a token file, only turtles that can prove who they are get in, and
+/// turtles can get tokens for the turtles they build.
+async fn turtles_must_authenticate() {
+    use crate::{
+        minecraft::computercraft::computer_types::hello::HelloResponse, websocket::auth::proof,
+    };
+
+    let path =
+        std::env::temp_dir().join(format!("meshpit_server_tokens_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let config = ServerConfig {
+        bind_address: "127.0.0.1:0".to_string(),
+        tokens: Some(path.clone()),
+        ..Default::default()
+    };
+    let (server, mut events) = MeshpitServer::bind(config).await.unwrap();
+    let token = server.tokens().unwrap().issue(5).unwrap();
+
+    // Say hello as `id`, and answer the challenge as if we held `token`.
+    let attempt = async |id: u16, token: Option<&str>| -> (TestClient, HelloResponse) {
+        let mut client = connect_as(server.local_addr(), WEBSOCKET_PATH, Some(&id.to_string()))
+            .await
+            .unwrap();
+        send_hello(&mut client, id).await;
+        let ServerMessage::Challenge { nonce } = next_server_packet(&mut client).await.message
+        else {
+            panic!("Expected a challenge!")
+        };
+        let data = match token {
+            Some(token) => format!(r#"{{"proof":"{}"}}"#, proof(token, id, &nonce)),
+            None => "null".to_string(),
+        };
+        let json = format!(
+            r#"{{"id":{id},"uuid":"AUTHAUTH","timestamp":0,"packet_type":"auth","data":{data}}}"#
+        );
+        client.send(json.into()).await.unwrap();
+        let ServerMessage::HelloReply { response, .. } =
+            next_server_packet(&mut client).await.message
+        else {
+            panic!("Expected a hello reply!")
+        };
+        (client, response)
+    };
+    let rejected = |(_, response): (TestClient, HelloResponse)| {
+        matches!(response, HelloResponse::Rejected { .. })
+    };
+
+    // Someone else claiming to be computer 5, or using 5's token as 6.
+    assert!(rejected(attempt(5, Some("not the token")).await));
+    assert!(rejected(attempt(5, None).await));
+    assert!(rejected(attempt(6, Some(&token)).await));
+    assert!(server.connected().is_empty());
+
+    let (mut client, response) = attempt(5, Some(&token)).await;
+    assert_eq!(response, HelloResponse::Accepted);
+    wait_for_event(&mut events, |event| {
+        matches!(event, ServerEvent::Connected { id: 5, .. })
+    })
+    .await;
+
+    // Building computer 9. Asking again gets the same token, in case the
+    // answer got lost, but only until computer 9 uses it.
+    let mut provision = async |uuid: &str| -> Option<String> {
+        let json = format!(
+            r#"{{"id":5,"uuid":"{uuid}","timestamp":0,"packet_type":"provision","data":{{"computer_id":9}}}}"#
+        );
+        client.send(json.into()).await.unwrap();
+        // Skipping the server's ack.
+        let (computer_id, token) = loop {
+            if let ServerMessage::Provisioned { computer_id, token } =
+                next_server_packet(&mut client).await.message
+            {
+                break (computer_id, token);
+            }
+        };
+        assert_eq!(computer_id, 9);
+        token
+    };
+    let provisioned = provision("PROVISIA").await.unwrap();
+    assert_eq!(provision("PROVISIB").await, Some(provisioned.clone()));
+    assert_eq!(server.tokens().unwrap().get(9), Some(provisioned.clone()));
+    let (_built, response) = attempt(9, Some(&provisioned)).await;
+    assert_eq!(response, HelloResponse::Accepted);
+    assert_eq!(provision("PROVISIC").await, None);
+    std::fs::remove_file(&path).unwrap();
+}
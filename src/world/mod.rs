@@ -0,0 +1,684 @@
+// Everything the swarm knows about the world, in one place.
+//
+// Every turtle keeps track of the blocks it has seen, see `walkback.lua`, and
+// sends them to us in its walkbacks and world deltas. Those are merged in here,
+// so a block one turtle saw is known to all of them, even after that turtle
+// reboots and forgets it.
+//
+// The world is split into sections, see `section.rs`. Every block we know of
+// also remembers which turtle saw it and when.
+//
+// Turtles don't always agree, IE one of them saw stone somewhere, and another
+// one dug it out since. The newest observation always wins, no matter which
+// order they come in. Walkbacks and full deltas don't say when each block in
+// them was seen, so those only fill in blocks we know nothing else about.
+//
+// Turtles doing things to blocks (see `TurtleAction`) flag those blocks as
+// possibly changed until someone looks at them again, so anything that relies
+// on the world, like pathfinding, can tell which blocks it should not trust.
+// See `WorldModel::get_as_of`. For now only moves are tracked, from the
+// positions in world deltas. Turtles don't report what they dig or place yet.
+//
+// Blocks are also indexed by name, so queries like "where is the closest log"
+// only look at the sections that have one, see `query.rs`.
+//
+// The world can be kept in a file, which is read back in when it is opened
+// again. Saving is up to whoever holds the world, the server saves every so
+// often, see `ServerConfig::world_save_every`.
+
+pub mod query;
+pub mod section;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    minecraft::{
+        computercraft::computer_types::{walkback_type::Walkback, world_delta::WorldDelta},
+        types::{CoordinatePosition, SectionPosition},
+        vanilla::block_type::PositionedMinecraftBlock,
+    },
+    world::{
+        query::BlockIndex,
+        section::{Observation, PaletteEntry, SECTION_VOLUME, Section},
+    },
+};
+
+/// The version of the file format. Bump this if the layout changes.
+const WORLD_FILE_VERSION: u32 = 1;
+
+/// Something a turtle did that may have changed a block, without us knowing
+/// what the block is now.
+///
+/// Only [TurtleAction::MovedThrough] comes from turtles so far, see
+/// [WorldModel::merge_delta].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurtleAction {
+    /// Dug out the block. Something could have fallen in since.
+    Dug(CoordinatePosition),
+    /// Placed something in the block.
+    Placed(CoordinatePosition),
+    /// Moved through the block, so it is not solid anymore, if it ever was.
+    MovedThrough(CoordinatePosition),
+}
+
+impl TurtleAction {
+    /// The block that may have changed.
+    pub fn position(&self) -> CoordinatePosition {
+        match self {
+            TurtleAction::Dug(position)
+            | TurtleAction::Placed(position)
+            | TurtleAction::MovedThrough(position) => *position,
+        }
+    }
+}
+
+/// What we know about a block, as of some point in time. See
+/// [WorldModel::get_as_of].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Known<'a> {
+    pub observation: Observation<'a>,
+    /// The block may have changed since, see [Observation::is_stale].
+    pub stale: bool,
+}
+
+/// See the top of this file.
+#[derive(Debug, Default)]
+pub struct WorldModel {
+    /// Where the world is saved, if anywhere.
+    path: Option<PathBuf>,
+    sections: HashMap<SectionPosition, Section>,
+    index: BlockIndex,
+    /// The tags of every block we have been told the tags of, by name. Tags
+    /// never change, so they only need to be learned once per block.
+    tags: BTreeMap<String, Vec<String>>,
+    /// Wether anything changed since the last save.
+    dirty: bool,
+}
+
+impl WorldModel {
+    /// A world that only lives in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a world kept in a file, creating it on the first save if it does
+    /// not exist yet.
+    ///
+    /// Cells that don't make sense are skipped with a warning, instead of
+    /// losing the whole world.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut world = Self {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(world),
+            Err(err) => return Err(err),
+        };
+        let saved: SavedWorld = serde_json::from_str(&json)?;
+        if saved.version != WORLD_FILE_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{} is version {}, we only read version {WORLD_FILE_VERSION}",
+                    path.display(),
+                    saved.version
+                ),
+            ));
+        }
+
+        world.tags = saved.tags;
+        for saved_section in saved.sections {
+            let section = world.sections.entry(saved_section.position).or_default();
+            let undated: HashSet<u16> = saved_section.undated.into_iter().collect();
+            for (index, block, observer, observed_at) in saved_section.cells {
+                let is_undated = undated.contains(&index);
+                let index = usize::from(index);
+                let entry = saved_section.palette.get(usize::from(block));
+                match entry {
+                    Some(entry) if index < SECTION_VOLUME => {
+                        let previous = section.get(index).map(|old| old.block.name.clone());
+                        if is_undated {
+                            // Nothing else is in the cell yet, so this always
+                            // goes in.
+                            section.observe_undated(index, entry, observer, observed_at);
+                        } else {
+                            section.set(index, entry, observer, observed_at);
+                        }
+                        world.index.replace(
+                            saved_section.position,
+                            previous.as_deref(),
+                            &entry.name,
+                        );
+                    }
+                    _ => warn!(
+                        "Skipping cell {index} of section {:?} in {}, it makes no sense!",
+                        saved_section.position,
+                        path.display()
+                    ),
+                }
+            }
+            for (index, at) in saved_section.invalidated {
+                if usize::from(index) < SECTION_VOLUME {
+                    section.invalidate(usize::from(index), at);
+                }
+            }
+        }
+        Ok(world)
+    }
+
+    /// Where the world is saved, if anywhere.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record that a turtle saw a block, at some point in time (in
+    /// milliseconds since the unix epoch). Returns wether it was recorded,
+    /// which it is not if we already heard something newer about that block.
+    pub fn observe(
+        &mut self,
+        observer: u16,
+        observed_at: u64,
+        block: &PositionedMinecraftBlock,
+    ) -> bool {
+        self.record(observer, observed_at, block, false)
+    }
+
+    /// Record that a turtle saw a block at some point, but not when, only that
+    /// we were told about it at `told_at`. Returns wether it was recorded,
+    /// which it is only if we know nothing else about that block. See
+    /// [Section::observe_undated].
+    pub fn observe_undated(
+        &mut self,
+        observer: u16,
+        told_at: u64,
+        block: &PositionedMinecraftBlock,
+    ) -> bool {
+        self.record(observer, told_at, block, true)
+    }
+
+    fn record(
+        &mut self,
+        observer: u16,
+        at: u64,
+        block: &PositionedMinecraftBlock,
+        undated: bool,
+    ) -> bool {
+        let position = block.position();
+        let entry = PaletteEntry::of(block.block());
+        if !block.tags().is_empty()
+            && self.tags.get(&entry.name).map(Vec::as_slice) != Some(block.tags())
+        {
+            self.tags.insert(entry.name.clone(), block.tags().to_vec());
+            self.dirty = true;
+        }
+
+        let section_position = SectionPosition::containing(position);
+        let index = SectionPosition::index_of(position);
+        let section = self.sections.entry(section_position).or_default();
+        let previous = section.get(index).map(|old| old.block.name.clone());
+        let recorded = if undated {
+            section.observe_undated(index, &entry, observer, at)
+        } else {
+            section.observe(index, &entry, observer, at)
+        };
+        if recorded {
+            self.index
+                .replace(section_position, previous.as_deref(), &entry.name);
+        }
+        self.dirty |= recorded;
+        recorded
+    }
+
+    /// Record that a turtle did something to a block at some point in time,
+    /// flagging it as possibly changed. Returns wether a block we knew about
+    /// was flagged.
+    pub fn record_action(&mut self, at: u64, action: TurtleAction) -> bool {
+        let position = action.position();
+        let section = self
+            .sections
+            .entry(SectionPosition::containing(position))
+            .or_default();
+        let flagged = section.invalidate(SectionPosition::index_of(position), at);
+        // Even if nothing was flagged, the time is kept.
+        self.dirty = true;
+        flagged
+    }
+
+    /// Merge in every block a turtle had seen when it dumped its walkback.
+    /// Returns how many blocks were recorded.
+    ///
+    /// Walkbacks don't say when each block was seen, which could be long
+    /// before `dumped_at`, so they are merged in as undated. See
+    /// [WorldModel::observe_undated].
+    pub fn merge_walkback(&mut self, observer: u16, dumped_at: u64, walkback: &Walkback) -> usize {
+        let Some(blocks) = &walkback.all_seen_blocks else {
+            return 0;
+        };
+        blocks
+            .values()
+            .filter(|block| self.observe_undated(observer, dumped_at, block))
+            .count()
+    }
+
+    /// Merge in the blocks from a world delta. Returns how many blocks were
+    /// recorded.
+    ///
+    /// Deltas can be merged in any order, even ones that came after a gap in
+    /// the turtle's `DeltaStream`, since every block in them was still really
+    /// seen.
+    ///
+    /// The turtle saw every block and moved through every position in the
+    /// delta since its last one, so those are taken as seen at `observed_at`,
+    /// and the positions are flagged too. Full deltas hold everything the
+    /// turtle remembers, which could be from long ago, so their blocks are
+    /// merged in as undated and their positions are not flagged.
+    pub fn merge_delta(&mut self, observer: u16, observed_at: u64, delta: &WorldDelta) -> usize {
+        if delta.full {
+            return delta
+                .blocks
+                .values()
+                .filter(|block| self.observe_undated(observer, observed_at, block))
+                .count();
+        }
+        for position in &delta.positions {
+            self.record_action(observed_at, TurtleAction::MovedThrough(*position));
+        }
+        delta
+            .blocks
+            .values()
+            .filter(|block| self.observe(observer, observed_at, block))
+            .count()
+    }
+
+    /// What we know about the block at a position, if anything.
+    pub fn get(&self, position: CoordinatePosition) -> Option<Observation<'_>> {
+        self.sections
+            .get(&SectionPosition::containing(position))?
+            .get(SectionPosition::index_of(position))
+    }
+
+    /// What we know about the block at a position as of some point in time,
+    /// if anything. Blocks that were last seen before then, or have had
+    /// something done to them since they were seen, are flagged as stale.
+    pub fn get_as_of(&self, position: CoordinatePosition, as_of: u64) -> Option<Known<'_>> {
+        let observation = self.get(position)?;
+        Some(Known {
+            observation,
+            stale: observation.is_stale(as_of),
+        })
+    }
+
+    /// Every block we know of, in no particular order.
+    pub fn blocks(&self) -> impl Iterator<Item = (CoordinatePosition, Observation<'_>)> {
+        self.sections.iter().flat_map(|(position, section)| {
+            section
+                .iter()
+                .map(|(index, observation)| (position.block_at(index), observation))
+        })
+    }
+
+    /// Every block we know of as of some point in time, in no particular
+    /// order. See [WorldModel::get_as_of].
+    pub fn blocks_as_of(
+        &self,
+        as_of: u64,
+    ) -> impl Iterator<Item = (CoordinatePosition, Known<'_>)> {
+        self.blocks().map(move |(position, observation)| {
+            (
+                position,
+                Known {
+                    observation,
+                    stale: observation.is_stale(as_of),
+                },
+            )
+        })
+    }
+
+    /// How many blocks we know of.
+    pub fn len(&self) -> usize {
+        self.sections.values().map(Section::known).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.values().all(Section::is_empty)
+    }
+
+    /// Wether anything changed since the world was opened or last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Write the world to its file, if it has one and anything changed.
+    /// Returns wether anything was written.
+    ///
+    /// The world can't be changed while this runs. If it is shared, take a
+    /// [WorldModel::snapshot] instead, and write it without holding the world.
+    pub fn save(&mut self) -> std::io::Result<bool> {
+        let Some(snapshot) = self.snapshot() else {
+            return Ok(false);
+        };
+        if let Err(err) = snapshot.write() {
+            self.save_failed();
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// Everything that needs to be written, if the world has a file and
+    /// anything changed since the last save.
+    ///
+    /// The world counts as saved from here on, so call
+    /// [WorldModel::save_failed] if writing the snapshot fails.
+    pub fn snapshot(&mut self) -> Option<WorldSnapshot> {
+        let path = self.path.clone()?;
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        Some(WorldSnapshot {
+            path,
+            saved: self.to_saved(),
+        })
+    }
+
+    /// Writing a [WorldModel::snapshot] failed, so it needs saving again.
+    pub fn save_failed(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Only blocks we know of are written, along with only the palette
+    /// entries they use.
+    fn to_saved(&self) -> SavedWorld {
+        let mut sections: Vec<SavedSection> = self
+            .sections
+            .iter()
+            .map(|(position, section)| {
+                let mut palette: Vec<PaletteEntry> = Vec::new();
+                let mut lookup: HashMap<&PaletteEntry, u16> = HashMap::new();
+                let cells = section
+                    .iter()
+                    .map(|(index, observation)| {
+                        let block = *lookup.entry(observation.block).or_insert_with(|| {
+                            palette.push(observation.block.clone());
+                            (palette.len() - 1) as u16
+                        });
+                        (
+                            index as u16,
+                            block,
+                            observation.observer,
+                            observation.observed_at,
+                        )
+                    })
+                    .collect();
+                SavedSection {
+                    position: *position,
+                    palette,
+                    cells,
+                    undated: section.undated().map(|index| index as u16).collect(),
+                    invalidated: section
+                        .invalidations()
+                        .map(|(index, at)| (index as u16, at))
+                        .collect(),
+                }
+            })
+            .filter(|section| !section.cells.is_empty() || !section.invalidated.is_empty())
+            .collect();
+        // Keeps saves of the same world the same, which is nice for diffing.
+        sections.sort_by_key(|section| section.position);
+        SavedWorld {
+            version: WORLD_FILE_VERSION,
+            sections,
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+/// A copy of a world, ready to be written to its file. See
+/// [WorldModel::snapshot].
+pub struct WorldSnapshot {
+    path: PathBuf,
+    saved: SavedWorld,
+}
+
+impl WorldSnapshot {
+    /// Write the snapshot to the world's file.
+    ///
+    /// The file is replaced all at once, so a crash halfway through a save
+    /// leaves the last save intact.
+    pub fn write(&self) -> std::io::Result<()> {
+        let json = serde_json::to_string(&self.saved)?;
+        if let Some(folder) = self.path.parent()
+            && !folder.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(folder)?;
+        }
+        let temporary = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+/// The world as it is written to disk.
+#[derive(Serialize, Deserialize)]
+struct SavedWorld {
+    version: u32,
+    sections: Vec<SavedSection>,
+    /// See [WorldModel::tags_of].
+    #[serde(default)]
+    tags: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedSection {
+    position: SectionPosition,
+    palette: Vec<PaletteEntry>,
+    /// `(index, palette index, observer, observed at)` for every block we know
+    /// of. Unlike in memory, palette indexes start at zero.
+    cells: Vec<(u16, u16, u16, u64)>,
+    /// The index of every cell holding an undated block, see
+    /// [Observation::undated].
+    #[serde(default)]
+    undated: Vec<u16>,
+    /// `(index, invalidated at)` for every invalidated cell, see
+    /// [TurtleAction].
+    #[serde(default)]
+    invalidated: Vec<(u16, u64)>,
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::vanilla::block_type::{HasMinecraftBlock, MinecraftBlock};
+
+#[cfg(test)]
+fn test_block(name: &str, x: i64, y: i64, z: i64) -> PositionedMinecraftBlock {
+    PositionedMinecraftBlock::new(
+        MinecraftBlock::from_string(name).unwrap(),
+        CoordinatePosition { x, y, z },
+    )
+}
+
+#[test]
+/// Blocks from every turtle should end up in one world, and survive a reopen.
+fn merging_and_saving_the_world() {
+    let path = std::env::temp_dir().join(format!("meshpit_world_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut world = WorldModel::open(&path).unwrap();
+    assert!(world.is_empty());
+
+    let delta: WorldDelta = serde_json::from_value(serde_json::json!({
+        "sequence": 0,
+        "full": true,
+        "blocks": {
+            "x:0|y:0|z:0": {"name": "minecraft:stone", "pos": {"x": 0, "y": 0, "z": 0}, "state": null, "tag": null},
+            "x:-1|y:0|z:-17": {"name": "minecraft:dirt", "pos": {"x": -1, "y": 0, "z": -17}, "state": null, "tag": null},
+        },
+        "positions": null,
+    }))
+    .unwrap();
+    assert_eq!(world.merge_delta(1, 1_000, &delta), 2);
+    world.observe(2, 2_000, &test_block("oak_log", 40, 70, -3));
+    // Another turtle saw the same place since.
+    world.observe(2, 3_000, &test_block("cobblestone", 0, 0, 0));
+    assert_eq!(world.len(), 3);
+
+    let check = |world: &WorldModel| {
+        assert_eq!(world.len(), 3);
+        let cobble = world.get(CoordinatePosition { x: 0, y: 0, z: 0 }).unwrap();
+        assert_eq!(cobble.block.name, "minecraft:cobblestone");
+        assert_eq!((cobble.observer, cobble.observed_at), (2, 3_000));
+        let dirt = world
+            .get(CoordinatePosition {
+                x: -1,
+                y: 0,
+                z: -17,
+            })
+            .unwrap();
+        assert_eq!((dirt.observer, dirt.observed_at), (1, 1_000));
+        assert_eq!(
+            dirt.block.block().unwrap().get_full_name().as_str(),
+            "minecraft:dirt"
+        );
+        assert!(world.get(CoordinatePosition { x: 1, y: 0, z: 0 }).is_none());
+        assert_eq!(world.blocks().count(), 3);
+    };
+    check(&world);
+
+    assert!(world.is_dirty());
+    assert!(world.save().unwrap());
+    assert!(!world.save().unwrap());
+    check(&WorldModel::open(&path).unwrap());
+
+    // Late news from a turtle that saw something there before.
+    assert!(!world.observe(1, 2_500, &test_block("dirt", 0, 0, 0)));
+
+    // A turtle moves through the cobblestone, and past something we never saw.
+    let moved: WorldDelta = serde_json::from_value(serde_json::json!({
+        "sequence": 1,
+        "full": false,
+        "blocks": null,
+        "positions": {"x:0|y:0|z:0": true, "x:5|y:5|z:5": true},
+    }))
+    .unwrap();
+    world.merge_delta(3, 4_000, &moved);
+    let check_stale = |world: &WorldModel| {
+        let origin = CoordinatePosition { x: 0, y: 0, z: 0 };
+        let cobble = world.get_as_of(origin, 0).unwrap();
+        assert!(cobble.stale);
+        assert_eq!(cobble.observation.invalidated_at, Some(4_000));
+        let log = CoordinatePosition {
+            x: 40,
+            y: 70,
+            z: -3,
+        };
+        assert!(!world.get_as_of(log, 2_000).unwrap().stale);
+        assert!(world.get_as_of(log, 2_001).unwrap().stale);
+        assert_eq!(
+            world
+                .blocks_as_of(1_500)
+                .filter(|(_, known)| known.stale)
+                .count(),
+            2
+        );
+        // Older news about the place we moved through is turned away.
+        assert!(world.get(CoordinatePosition { x: 5, y: 5, z: 5 }).is_none());
+    };
+    check_stale(&world);
+    assert!(world.save().unwrap());
+    let mut reopened = WorldModel::open(&path).unwrap();
+    check_stale(&reopened);
+    assert!(!reopened.observe(1, 3_999, &test_block("stone", 5, 5, 5)));
+    assert!(reopened.observe(3, 4_000, &test_block("air", 0, 0, 0)));
+    assert!(
+        !reopened
+            .get_as_of(CoordinatePosition { x: 0, y: 0, z: 0 }, 4_000)
+            .unwrap()
+            .stale
+    );
+
+    // Blocks merged in while a snapshot is written still need saving, and so
+    // does a snapshot that failed to write.
+    let snapshot = reopened.snapshot().unwrap();
+    assert!(reopened.snapshot().is_none());
+    reopened.observe(3, 5_000, &test_block("stone", 9, 9, 9));
+    snapshot.write().unwrap();
+    assert!(reopened.is_dirty());
+    assert!(reopened.snapshot().is_some());
+    reopened.save_failed();
+    assert!(reopened.save().unwrap());
+    assert_eq!(WorldModel::open(&path).unwrap().len(), 4);
+
+    // Nowhere to save to.
+    let mut memory = WorldModel::new();
+    memory.observe(1, 0, &test_block("stone", 0, 0, 0));
+    assert!(!memory.save().unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+/// Everything a turtle remembers, dumped all at once, should never win over
+/// what other turtles saw, no matter how late it is dumped.
+fn dumps_never_win() {
+    let path =
+        std::env::temp_dir().join(format!("meshpit_world_dumps_{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut world = WorldModel::open(&path).unwrap();
+    let origin = CoordinatePosition { x: 0, y: 0, z: 0 };
+
+    // Turtle B dug out the stone at the origin, and saw air there since.
+    world.observe(2, 1_000, &test_block("stone", 0, 0, 0));
+    world.record_action(2_000, TurtleAction::Dug(origin));
+    assert!(world.observe(2, 2_000, &test_block("air", 0, 0, 0)));
+
+    // Turtle A reboots, and dumps the stone it saw there before any of that.
+    let dump: WorldDelta = serde_json::from_value(serde_json::json!({
+        "sequence": 0,
+        "full": true,
+        "blocks": {
+            "x:0|y:0|z:0": {"name": "minecraft:stone", "pos": {"x": 0, "y": 0, "z": 0}, "state": null, "tag": null},
+            "x:1|y:0|z:0": {"name": "minecraft:dirt", "pos": {"x": 1, "y": 0, "z": 0}, "state": null, "tag": null},
+        },
+        "positions": {"x:0|y:0|z:0": true},
+    }))
+    .unwrap();
+    assert_eq!(world.merge_delta(1, 3_000, &dump), 1);
+    let check = |world: &WorldModel| {
+        let air = world.get_as_of(origin, 2_000).unwrap();
+        assert_eq!(air.observation.block.name, "minecraft:air");
+        assert_eq!(air.observation.observer, 2);
+        assert!(!air.stale);
+        let dirt = world.get(CoordinatePosition { x: 1, y: 0, z: 0 }).unwrap();
+        assert_eq!((dirt.observer, dirt.observed_at), (1, 3_000));
+        assert!(dirt.undated);
+    };
+    check(&world);
+    world.save().unwrap();
+    let mut world = WorldModel::open(&path).unwrap();
+    check(&world);
+
+    // Anything dated wins over the dirt, even from before the dump.
+    assert!(world.observe(2, 2_500, &test_block("cobblestone", 1, 0, 0)));
+    assert!(
+        !world
+            .get(CoordinatePosition { x: 1, y: 0, z: 0 })
+            .unwrap()
+            .undated
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
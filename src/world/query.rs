@@ -0,0 +1,535 @@
+// Asking the world model where things are.
+//
+// Turtles looking for something, see `block_search.lua`, have to wander around
+// until they stumble into it. Once any turtle has seen it though, the server
+// can just send them straight there.
+//
+// None of these look at every block we know of:
+// - Every block name is indexed to the sections holding at least one of it, see
+//   `BlockIndex`. Looking for a block only looks inside those sections, closest
+//   first, and stops as soon as no section left could hold anything closer.
+// - Looking inside an area only looks at the sections that overlap it.
+// - Looking for somewhere unexplored walks outwards from a point, and stops at
+//   the first cell we know nothing about.
+//
+// Distances are taxicab distances, since turtles can't move diagonally.
+
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::{
+    minecraft::{
+        computercraft::computer_types::tasks::BlockGroup,
+        types::{AxisOrder, CoordinatePosition, Cuboid, SectionPosition},
+    },
+    world::{
+        WorldModel,
+        section::{Observation, SECTION_VOLUME, Section},
+    },
+};
+
+/// Which sections hold at least one of each kind of block, by name, and how
+/// many they hold.
+#[derive(Debug, Default)]
+pub(super) struct BlockIndex {
+    sections: HashMap<String, HashMap<SectionPosition, u32>>,
+}
+
+impl BlockIndex {
+    /// A cell in a section went from one block (or nothing) to another.
+    pub(super) fn replace(&mut self, section: SectionPosition, old: Option<&str>, new: &str) {
+        if old == Some(new) {
+            return;
+        }
+        if let Some(old) = old
+            && let Some(sections) = self.sections.get_mut(old)
+            && let Some(count) = sections.get_mut(&section)
+        {
+            *count -= 1;
+            if *count == 0 {
+                sections.remove(&section);
+                if sections.is_empty() {
+                    self.sections.remove(old);
+                }
+            }
+        }
+        *self
+            .sections
+            .entry(new.to_owned())
+            .or_default()
+            .entry(section)
+            .or_default() += 1;
+    }
+
+    /// Every block name we know of at least one of.
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    /// Every section holding at least one block with this name.
+    fn sections_of(&self, name: &str) -> impl Iterator<Item = SectionPosition> {
+        self.sections
+            .get(name)
+            .into_iter()
+            .flat_map(|sections| sections.keys().copied())
+    }
+}
+
+/// A lua pattern that could not be turned into a regex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// Uses something we don't translate, IE `%b` or back references.
+    Unsupported { pattern: String, what: String },
+    /// Not a valid pattern at all, IE it ends in a lone `%`.
+    Malformed { pattern: String },
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::Unsupported { pattern, what } => {
+                write!(
+                    f,
+                    "lua pattern '{pattern}' uses {what}, which is not supported"
+                )
+            }
+            PatternError::Malformed { pattern } => {
+                write!(f, "'{pattern}' is not a valid lua pattern")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Which blocks a query is after. Matches blocks the same way
+/// `helpers.blockWanted` does on the lua side: a block is wanted if any of the
+/// name patterns are found anywhere in its name, or it has any of the tags.
+#[derive(Debug, Clone)]
+pub struct BlockMatcher {
+    names: Vec<Regex>,
+    tags: Vec<String>,
+}
+
+impl BlockMatcher {
+    /// Build a matcher from the same kind of group handed to turtles. Name
+    /// patterns are lua patterns, see [lua_pattern_to_regex].
+    pub fn new(group: &BlockGroup) -> Result<Self, PatternError> {
+        let names = group
+            .names_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(&lua_pattern_to_regex(pattern)?).map_err(|_| PatternError::Malformed {
+                    pattern: pattern.clone(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            names,
+            tags: group.tags.clone(),
+        })
+    }
+
+    /// Wether a block with this name and these tags is wanted.
+    pub fn matches(&self, name: &str, tags: &[String]) -> bool {
+        self.names.iter().any(|regex| regex.is_match(name))
+            || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+/// Translate a lua pattern, as used by `string.find`, into the equivalent
+/// regex. Everything but balanced matches (`%b`), frontiers (`%f`) and back
+/// references is supported.
+pub fn lua_pattern_to_regex(pattern: &str) -> Result<String, PatternError> {
+    let malformed = || PatternError::Malformed {
+        pattern: pattern.to_owned(),
+    };
+    let mut regex = String::new();
+    let mut chars = pattern.chars().peekable();
+    // Quantifiers only mean something right after a single character class,
+    // anywhere else they are just characters.
+    let mut quantifiable = false;
+    if chars.next_if_eq(&'^').is_some() {
+        regex.push('^');
+    }
+    while let Some(char) = chars.next() {
+        let mut class = true;
+        match char {
+            '*' | '+' | '?' if quantifiable => {
+                regex.push(char);
+                class = false;
+            }
+            // Lazy `*`.
+            '-' if quantifiable => {
+                regex.push_str("*?");
+                class = false;
+            }
+            '$' if chars.peek().is_none() => regex.push('$'),
+            '.' => regex.push_str("(?s:.)"),
+            '(' | ')' => {
+                regex.push(char);
+                class = false;
+            }
+            '%' => {
+                let escaped = chars.next().ok_or_else(malformed)?;
+                regex.push_str(&lua_class(pattern, escaped)?);
+            }
+            '[' => {
+                regex.push('[');
+                if chars.next_if_eq(&'^').is_some() {
+                    regex.push('^');
+                }
+                // A `]` right at the start is just a `]`.
+                if chars.next_if_eq(&']').is_some() {
+                    regex.push_str("\\]");
+                }
+                loop {
+                    match chars.next().ok_or_else(malformed)? {
+                        ']' => break,
+                        '%' => {
+                            let escaped = chars.next().ok_or_else(malformed)?;
+                            regex.push_str(&lua_class(pattern, escaped)?);
+                        }
+                        '-' => regex.push('-'),
+                        other => regex.push_str(&regex::escape(&other.to_string())),
+                    }
+                }
+                regex.push(']');
+            }
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+        quantifiable = class;
+    }
+    Ok(regex)
+}
+
+/// The regex for a `%` escape in a lua pattern. Always a single item, so it
+/// works both on its own and inside a set.
+fn lua_class(pattern: &str, class: char) -> Result<String, PatternError> {
+    let unsupported = |what: &str| PatternError::Unsupported {
+        pattern: pattern.to_owned(),
+        what: what.to_owned(),
+    };
+    let set = match class.to_ascii_lowercase() {
+        'a' => "A-Za-z",
+        'c' => "\\x00-\\x1F\\x7F",
+        'd' => "0-9",
+        'g' => "!-~",
+        'l' => "a-z",
+        'p' => "!-/:-@\\[-`{-~",
+        's' => "\\t-\\r ",
+        'u' => "A-Z",
+        'w' => "A-Za-z0-9",
+        'x' => "0-9A-Fa-f",
+        'b' => return Err(unsupported("balanced matches (%b)")),
+        'f' => return Err(unsupported("frontiers (%f)")),
+        digit if digit.is_ascii_digit() => return Err(unsupported("back references")),
+        // Anything else is escaped by the `%`.
+        _ => return Ok(regex::escape(&class.to_string())),
+    };
+    if class.is_ascii_uppercase() {
+        Ok(format!("[^{set}]"))
+    } else {
+        Ok(format!("[{set}]"))
+    }
+}
+
+/// The taxicab distance from a position to the closest block in a section.
+fn section_distance(section: SectionPosition, from: CoordinatePosition) -> u64 {
+    let (low, high) = (section.min_block(), section.max_block());
+    from.taxicab_distance(CoordinatePosition {
+        x: from.x.clamp(low.x, high.x),
+        y: from.y.clamp(low.y, high.y),
+        z: from.z.clamp(low.z, high.z),
+    })
+}
+
+impl WorldModel {
+    /// The tags we have learned a block has, by its full name. Empty if we
+    /// were never told, since only json encoded blocks carry their tags.
+    pub fn tags_of(&self, name: &str) -> &[String] {
+        self.tags.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The closest block the matcher wants, and what we know about it. Ties go
+    /// to whichever is found first.
+    ///
+    /// Blocks a turtle has done something to since they were seen are skipped,
+    /// since they were probably dug out.
+    pub fn nearest(
+        &self,
+        from: CoordinatePosition,
+        matcher: &BlockMatcher,
+    ) -> Option<(CoordinatePosition, Observation<'_>)> {
+        let names: HashSet<&str> = self
+            .index
+            .names()
+            .filter(|name| matcher.matches(name, self.tags_of(name)))
+            .collect();
+        let mut sections: Vec<(u64, SectionPosition)> = names
+            .iter()
+            .flat_map(|name| self.index.sections_of(name))
+            .map(|section| (section_distance(section, from), section))
+            .collect();
+        sections.sort_unstable();
+        sections.dedup();
+
+        let mut best: Option<(u64, CoordinatePosition, Observation<'_>)> = None;
+        for (lower_bound, section_position) in sections {
+            if best.is_some_and(|(distance, ..)| lower_bound >= distance) {
+                break;
+            }
+            let Some(section) = self.sections.get(&section_position) else {
+                continue;
+            };
+            for (index, observation) in section.iter() {
+                if observation.invalidated_at.is_some()
+                    || !names.contains(observation.block.name.as_str())
+                {
+                    continue;
+                }
+                let position = section_position.block_at(index);
+                let distance = position.taxicab_distance(from);
+                if best.is_none_or(|(closest, ..)| distance < closest) {
+                    best = Some((distance, position, observation));
+                }
+            }
+        }
+        best.map(|(_, position, observation)| (position, observation))
+    }
+
+    /// Every block we know of in an area, in no particular order. Blocks a
+    /// turtle has done something to since are still included, see
+    /// [Observation::invalidated_at].
+    pub fn blocks_in(
+        &self,
+        area: Cuboid,
+    ) -> impl Iterator<Item = (CoordinatePosition, Observation<'_>)> {
+        self.sections_overlapping(area)
+            .into_iter()
+            .flat_map(move |(section_position, section)| {
+                let whole = Cuboid::new(section_position.min_block(), section_position.max_block());
+                // Only the part of the section inside the area.
+                whole
+                    .intersection(&area)
+                    .into_iter()
+                    .flat_map(|part| part.blocks(AxisOrder::Xzy))
+                    .filter_map(move |position| {
+                        Some((position, section.get(SectionPosition::index_of(position))?))
+                    })
+            })
+    }
+
+    /// How many of each block, by name, we know of in an area. Air is counted
+    /// like any other block. Blocks a turtle has done something to since they
+    /// were seen are not counted.
+    pub fn count_in(&self, area: Cuboid) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (_, observation) in self.blocks_in(area) {
+            if observation.invalidated_at.is_none() {
+                *counts.entry(observation.block.name.clone()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// The closest position we know nothing about, no further away than
+    /// `max_distance`. Ties go to whichever is found first.
+    ///
+    /// Cells a turtle moved through without anyone seeing what is there are
+    /// still unexplored.
+    pub fn closest_unexplored(
+        &self,
+        from: CoordinatePosition,
+        max_distance: u64,
+    ) -> Option<CoordinatePosition> {
+        // Every position at exactly this distance, one distance at a time.
+        (0..=max_distance as i64).find_map(|distance| {
+            (-distance..=distance).find_map(|x| {
+                let left = distance - x.abs();
+                (-left..=left).find_map(|y| {
+                    let z = left - y.abs();
+                    [z, -z]
+                        .into_iter()
+                        .map(|z| from.with_offset(CoordinatePosition { x, y, z }))
+                        .find(|position| self.get(*position).is_none())
+                })
+            })
+        })
+    }
+
+    /// Every section we have that overlaps an area, without looking at any of
+    /// the sections in between that we don't have.
+    fn sections_overlapping(&self, area: Cuboid) -> Vec<(SectionPosition, &Section)> {
+        let spanned = Cuboid::new(
+            SectionPosition::containing(area.min()).min_block(),
+            SectionPosition::containing(area.max()).max_block(),
+        )
+        .volume()
+            / SECTION_VOLUME as u64;
+        if spanned > self.sections.len() as u64 {
+            return self
+                .sections
+                .iter()
+                .filter(|(position, _)| {
+                    Cuboid::new(position.min_block(), position.max_block()).intersects(&area)
+                })
+                .map(|(position, section)| (*position, section))
+                .collect();
+        }
+        SectionPosition::touched_by(area)
+            .filter_map(|position| Some((position, self.sections.get(&position)?)))
+            .collect()
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::world::{TurtleAction, test_block};
+
+#[test]
+/// Lua patterns should match the same names they match on the lua side.
+fn lua_patterns() {
+    let matches = |pattern: &str, name: &str| {
+        Regex::new(&lua_pattern_to_regex(pattern).unwrap())
+            .unwrap()
+            .is_match(name)
+    };
+    assert!(matches("log$", "minecraft:oak_log"));
+    assert!(!matches("log$", "minecraft:oak_logs"));
+    assert!(matches("^minecraft:", "minecraft:stone"));
+    assert!(!matches("^stone", "minecraft:stone"));
+    assert!(matches("minecraft:stone", "minecraft:stone_bricks"));
+    assert!(matches("%a+_ore$", "minecraft:deepslate_iron_ore"));
+    assert!(matches("[ab]ir[ch]h?", "minecraft:birch_log"));
+    assert!(!matches("[^ab]irch", "minecraft:birch_log"));
+    assert!(matches("oak.-log", "minecraft:oak_log"));
+    // Escaped, and not a quantifier at the start.
+    assert!(matches("%.", "a.b"));
+    assert!(!matches("%.", "ab"));
+    assert!(matches("*", "a*b"));
+    assert!(matches("%d%d", "level_15"));
+
+    assert!(matches!(
+        lua_pattern_to_regex("%b()"),
+        Err(PatternError::Unsupported { .. })
+    ));
+    assert!(matches!(
+        lua_pattern_to_regex("oak%"),
+        Err(PatternError::Malformed { .. })
+    ));
+    assert!(matches!(
+        lua_pattern_to_regex("[oak"),
+        Err(PatternError::Malformed { .. })
+    ));
+}
+
+#[test]
+/// Queries should find the right blocks, and the index should keep up with
+/// blocks changing.
+fn spatial_queries() {
+    let origin = CoordinatePosition { x: 0, y: 0, z: 0 };
+    let mut world = WorldModel::new();
+    for x in -20..20 {
+        world.observe(1, 100, &test_block("stone", x, 0, 0));
+    }
+    world.observe(1, 100, &test_block("oak_log", 15, 0, 0));
+    world.observe(1, 100, &test_block("birch_log", -40, 3, 0));
+    world.observe(
+        1,
+        100,
+        &test_block("iron_ore", 3, -1, 0).with_tags(vec!["minecraft:iron_ores".into()]),
+    );
+
+    let logs = BlockMatcher::new(&BlockGroup {
+        names_patterns: vec!["log$".into()],
+        tags: vec![],
+    })
+    .unwrap();
+    let (position, log) = world.nearest(origin, &logs).unwrap();
+    assert_eq!(position, CoordinatePosition { x: 15, y: 0, z: 0 });
+    assert_eq!(log.block.name, "minecraft:oak_log");
+    let far = CoordinatePosition { x: -30, y: 0, z: 0 };
+    assert_eq!(
+        world.nearest(far, &logs).unwrap().1.block.name,
+        "minecraft:birch_log"
+    );
+
+    // Replaced, and the index keeps up.
+    world.observe(2, 200, &test_block("air", 15, 0, 0));
+    assert_eq!(
+        world.nearest(origin, &logs).unwrap().0,
+        CoordinatePosition { x: -40, y: 3, z: 0 }
+    );
+    // Dug out.
+    world.record_action(
+        300,
+        TurtleAction::Dug(CoordinatePosition { x: -40, y: 3, z: 0 }),
+    );
+    assert!(world.nearest(origin, &logs).is_none());
+
+    // Found by tag, even though the name doesn't match.
+    let iron = BlockMatcher::new(&BlockGroup {
+        names_patterns: vec!["raw_iron".into()],
+        tags: vec!["minecraft:iron_ores".into()],
+    })
+    .unwrap();
+    assert_eq!(world.tags_of("minecraft:iron_ore"), ["minecraft:iron_ores"]);
+    assert_eq!(
+        world.nearest(far, &iron).unwrap().0,
+        CoordinatePosition { x: 3, y: -1, z: 0 }
+    );
+
+    // Across sections.
+    let area = Cuboid::new(
+        CoordinatePosition { x: 4, y: 0, z: 0 },
+        CoordinatePosition { x: -2, y: -1, z: 0 },
+    );
+    assert_eq!(world.blocks_in(area).count(), 8);
+    let counts = world.count_in(area);
+    assert_eq!(counts["minecraft:stone"], 7);
+    assert_eq!(counts["minecraft:iron_ore"], 1);
+    assert_eq!(counts.len(), 2);
+    let everything = Cuboid::new(
+        CoordinatePosition {
+            x: i64::MIN,
+            y: i64::MIN,
+            z: i64::MIN,
+        },
+        CoordinatePosition {
+            x: i64::MAX,
+            y: i64::MAX,
+            z: i64::MAX,
+        },
+    );
+    assert_eq!(world.blocks_in(everything).count(), world.len());
+
+    // Stone runs along x, so the closest unknown is off to the side.
+    let unexplored = world.closest_unexplored(origin, 10).unwrap();
+    assert_eq!(unexplored.taxicab_distance(origin), 1);
+    assert!(world.get(unexplored).is_none());
+    assert_eq!(
+        world.closest_unexplored(CoordinatePosition { x: 5, y: 5, z: 5 }, 0),
+        Some(CoordinatePosition { x: 5, y: 5, z: 5 })
+    );
+    let mut boxed = WorldModel::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                boxed.observe(1, 100, &test_block("stone", x, y, z));
+            }
+        }
+    }
+    assert!(boxed.closest_unexplored(origin, 1).is_none());
+    assert_eq!(
+        boxed
+            .closest_unexplored(origin, 10)
+            .unwrap()
+            .taxicab_distance(origin),
+        2
+    );
+}
@@ -0,0 +1,412 @@
+// A 16x16x16 piece of the world model.
+//
+// Same shape as a Minecraft chunk section, see `SectionPosition`. Blocks are
+// not stored by name in every cell, since most of a section is usually the same
+// handful of blocks. Instead every section has a palette of the blocks in it,
+// and each cell only holds an index into that palette.
+//
+// Cells also keep track of when they were last seen, and when a turtle last did
+// something that might have changed them. Newer news always wins, so a turtle
+// that reports late can't undo what another turtle saw after it.
+//
+// Some blocks come without saying when they were seen, IE everything a turtle
+// remembers, dumped all at once. Those could be from long ago, so they only
+// fill in cells we know nothing about, and anything dated wins over them.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::minecraft::{
+    types::CHUNK_SIZE,
+    vanilla::block_type::{HasMinecraftBlock, LuaMinecraftBlockState, MinecraftBlock},
+};
+
+/// How many blocks are in a section.
+pub const SECTION_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// A block, without where it is. Only holds the states the lua side keeps
+/// track of.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PaletteEntry {
+    /// IE `minecraft:stone`.
+    pub name: String,
+    pub(crate) state: LuaMinecraftBlockState,
+}
+
+impl PaletteEntry {
+    pub fn of(block: &MinecraftBlock) -> Self {
+        Self {
+            name: block.get_full_name().into_owned(),
+            state: block.lua_state(),
+        }
+    }
+
+    /// Look the block back up. None if we no longer know of a block with this
+    /// name, IE a mod was removed.
+    pub fn block(&self) -> Option<MinecraftBlock> {
+        MinecraftBlock::from_lua_state(&self.name, Some(self.state.clone()))
+    }
+}
+
+/// What we know about a single block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observation<'a> {
+    pub block: &'a PaletteEntry,
+    /// The turtle that saw it.
+    pub observer: u16,
+    /// When it was seen, in milliseconds since the unix epoch.
+    pub observed_at: u64,
+    /// When a turtle did something that might have changed this block since it
+    /// was seen, if one has.
+    pub invalidated_at: Option<u64>,
+    /// The block came without saying when it was seen, so `observed_at` is
+    /// only when we were told about it. See [Section::observe_undated].
+    pub undated: bool,
+}
+
+impl Observation<'_> {
+    /// Wether this block may not be there anymore, as of some point in time.
+    /// That is, it was last seen before then, or something has happened to it
+    /// since it was seen.
+    pub fn is_stale(&self, as_of: u64) -> bool {
+        self.invalidated_at.is_some() || self.observed_at < as_of
+    }
+}
+
+/// A single cell of a section.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cell {
+    /// One past the index into the palette, zero is a block we know nothing
+    /// about.
+    block: u16,
+    observer: u16,
+    observed_at: u64,
+    /// Zero if the cell has not been invalidated since it was seen. Can be set
+    /// on cells we know nothing about, so late reports of what used to be
+    /// there are still turned away.
+    invalidated_at: u64,
+    /// See [Observation::undated].
+    undated: bool,
+}
+
+/// See the top of this file.
+#[derive(Debug, Clone)]
+pub struct Section {
+    palette: Vec<PaletteEntry>,
+    /// Where each block is in the palette.
+    lookup: HashMap<PaletteEntry, u16>,
+    /// Indexed by `SectionPosition::index_of`.
+    cells: Box<[Cell]>,
+    /// How many cells hold a block.
+    known: usize,
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Self {
+            palette: Vec::new(),
+            lookup: HashMap::new(),
+            cells: vec![Cell::default(); SECTION_VOLUME].into_boxed_slice(),
+            known: 0,
+        }
+    }
+}
+
+impl Section {
+    /// What we know about the block at an index, if anything.
+    pub fn get(&self, index: usize) -> Option<Observation<'_>> {
+        let cell = self.cells.get(index)?;
+        let block = self.palette.get(usize::from(cell.block).checked_sub(1)?)?;
+        Some(Observation {
+            block,
+            observer: cell.observer,
+            observed_at: cell.observed_at,
+            invalidated_at: (cell.invalidated_at != 0).then_some(cell.invalidated_at),
+            undated: cell.undated,
+        })
+    }
+
+    /// Record a block at an index, replacing whatever was there no matter how
+    /// new it was.
+    pub fn set(&mut self, index: usize, block: &PaletteEntry, observer: u16, observed_at: u64) {
+        self.set_cell(index, block, observer, observed_at, false);
+    }
+
+    /// Record a block at an index, unless we already heard something newer
+    /// about it. Returns wether the block was recorded.
+    ///
+    /// Ties go to the newest report, since it came in last.
+    pub fn observe(
+        &mut self,
+        index: usize,
+        block: &PaletteEntry,
+        observer: u16,
+        observed_at: u64,
+    ) -> bool {
+        let cell = &self.cells[index];
+        let newest = if cell.block == 0 || cell.undated {
+            cell.invalidated_at
+        } else {
+            cell.observed_at.max(cell.invalidated_at)
+        };
+        if observed_at < newest {
+            return false;
+        }
+        self.set(index, block, observer, observed_at);
+        true
+    }
+
+    /// Record a block that we don't know when was seen, only that it was by
+    /// `told_at`. Returns wether the block was recorded.
+    ///
+    /// It could be older than anything, so it is only recorded if all we know
+    /// of the cell is other undated blocks we were told about before.
+    pub fn observe_undated(
+        &mut self,
+        index: usize,
+        block: &PaletteEntry,
+        observer: u16,
+        told_at: u64,
+    ) -> bool {
+        let cell = &self.cells[index];
+        let only_undated = cell.block == 0 || cell.undated;
+        if !only_undated || cell.invalidated_at != 0 || told_at < cell.observed_at {
+            return false;
+        }
+        self.set_cell(index, block, observer, told_at, true);
+        true
+    }
+
+    /// Flag the block at an index as possibly changed, at some point in time.
+    /// Returns wether a block we knew about was flagged.
+    ///
+    /// Does nothing if the block was seen after that.
+    ///
+    /// Undated blocks are always flagged, since they may have been seen before
+    /// then.
+    pub fn invalidate(&mut self, index: usize, at: u64) -> bool {
+        let cell = &mut self.cells[index];
+        if cell.block != 0 && !cell.undated && at < cell.observed_at {
+            return false;
+        }
+        cell.invalidated_at = cell.invalidated_at.max(at);
+        cell.block != 0
+    }
+
+    /// Forget the block at an index.
+    pub fn clear(&mut self, index: usize) {
+        let cell = &mut self.cells[index];
+        if cell.block != 0 {
+            self.known -= 1;
+        }
+        *cell = Cell::default();
+    }
+
+    /// How many blocks in this section we know about.
+    pub fn known(&self) -> usize {
+        self.known
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known == 0
+    }
+
+    /// Every block we know about, by index.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Observation<'_>)> {
+        (0..SECTION_VOLUME).filter_map(|index| Some((index, self.get(index)?)))
+    }
+
+    /// Every cell holding an undated block, by index.
+    pub fn undated(&self) -> impl Iterator<Item = usize> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.block != 0 && cell.undated)
+            .map(|(index, _)| index)
+    }
+
+    /// Every cell that has been invalidated, and when, by index. Includes
+    /// cells we know nothing about.
+    pub fn invalidations(&self) -> impl Iterator<Item = (usize, u64)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.invalidated_at != 0)
+            .map(|(index, cell)| (index, cell.invalidated_at))
+    }
+
+    fn set_cell(
+        &mut self,
+        index: usize,
+        block: &PaletteEntry,
+        observer: u16,
+        observed_at: u64,
+        undated: bool,
+    ) {
+        let block = self.palette_index(block);
+        let cell = &mut self.cells[index];
+        if cell.block == 0 {
+            self.known += 1;
+        }
+        *cell = Cell {
+            block,
+            observer,
+            observed_at,
+            invalidated_at: 0,
+            undated,
+        };
+    }
+
+    /// Find a block in the palette, adding it if needed.
+    ///
+    /// Blocks stay in the palette after they are overwritten, until the
+    /// palette fills up. They are also dropped when the section is saved.
+    fn palette_index(&mut self, block: &PaletteEntry) -> u16 {
+        if let Some(index) = self.lookup.get(block) {
+            return *index;
+        }
+        if self.palette.len() >= usize::from(u16::MAX) {
+            self.compact();
+        }
+        self.palette.push(block.clone());
+        // At most one entry per cell after compacting, so this fits.
+        let index = self.palette.len() as u16;
+        self.lookup.insert(block.clone(), index);
+        index
+    }
+
+    /// Drop every palette entry no cell uses anymore.
+    fn compact(&mut self) {
+        let old = std::mem::take(&mut self.palette);
+        self.lookup.clear();
+        for cell in self.cells.iter_mut().filter(|cell| cell.block != 0) {
+            let entry = &old[usize::from(cell.block) - 1];
+            cell.block = match self.lookup.get(entry) {
+                Some(index) => *index,
+                None => {
+                    self.palette.push(entry.clone());
+                    let index = self.palette.len() as u16;
+                    self.lookup.insert(entry.clone(), index);
+                    index
+                }
+            };
+        }
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Cells should share palette entries, and keep count of what is known.
+fn section_palette() {
+    let stone = PaletteEntry {
+        name: "minecraft:stone".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let dirt = PaletteEntry {
+        name: "minecraft:dirt".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let mut section = Section::default();
+    section.set(0, &stone, 1, 100);
+    section.set(1, &stone, 2, 200);
+    section.set(2, &dirt, 1, 300);
+    assert_eq!(section.known(), 3);
+    assert_eq!(section.palette.len(), 2);
+
+    section.set(0, &dirt, 3, 400);
+    let cell = section.get(0).unwrap();
+    assert_eq!(
+        (cell.block, cell.observer, cell.observed_at),
+        (&dirt, 3, 400)
+    );
+    assert_eq!(section.known(), 3);
+
+    section.clear(1);
+    section.clear(1);
+    assert!(section.get(1).is_none());
+    assert_eq!(section.iter().count(), 2);
+
+    // Stone is not used anymore.
+    section.compact();
+    assert_eq!(section.palette.len(), 1);
+    assert_eq!(section.get(2).unwrap().block, &dirt);
+}
+
+#[test]
+/// Newer news should always win, no matter what order it comes in.
+fn section_freshness() {
+    let stone = PaletteEntry {
+        name: "minecraft:stone".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let air = PaletteEntry {
+        name: "minecraft:air".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let mut section = Section::default();
+    assert!(section.observe(0, &air, 2, 200));
+    // A turtle that saw stone there earlier reports late.
+    assert!(!section.observe(0, &stone, 1, 100));
+    assert_eq!(section.get(0).unwrap().block, &air);
+    assert!(section.observe(0, &stone, 1, 200));
+    assert_eq!(section.get(0).unwrap().observer, 1);
+
+    // A turtle dug it.
+    assert!(!section.invalidate(0, 150));
+    assert!(section.invalidate(0, 300));
+    let dug = section.get(0).unwrap();
+    assert_eq!(dug.invalidated_at, Some(300));
+    assert!(dug.is_stale(0));
+    assert!(!section.observe(0, &stone, 3, 250));
+    assert!(section.observe(0, &air, 3, 300));
+    let seen = section.get(0).unwrap();
+    assert_eq!(seen.invalidated_at, None);
+    assert!(!seen.is_stale(300));
+    assert!(seen.is_stale(301));
+
+    // Never seen, but something happened to it.
+    assert!(!section.invalidate(1, 400));
+    assert!(section.get(1).is_none());
+    assert!(!section.observe(1, &stone, 1, 399));
+    assert_eq!(section.invalidations().collect::<Vec<_>>(), [(1, 400)]);
+}
+
+#[test]
+/// Undated blocks should only ever fill in cells we know nothing about.
+fn section_undated() {
+    let stone = PaletteEntry {
+        name: "minecraft:stone".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let air = PaletteEntry {
+        name: "minecraft:air".into(),
+        state: LuaMinecraftBlockState::default(),
+    };
+    let mut section = Section::default();
+    assert!(section.observe(0, &air, 2, 200));
+    assert!(!section.observe_undated(0, &stone, 1, 300));
+    assert_eq!(section.get(0).unwrap().block, &air);
+
+    assert!(section.observe_undated(1, &stone, 1, 300));
+    assert!(section.get(1).unwrap().undated);
+    // Newer dumps replace older ones.
+    assert!(!section.observe_undated(1, &air, 3, 250));
+    assert!(section.observe_undated(1, &air, 3, 400));
+    // Anything dated wins, no matter how old.
+    assert!(section.observe(1, &stone, 2, 100));
+    let seen = section.get(1).unwrap();
+    assert_eq!((seen.block, seen.undated), (&stone, false));
+    assert_eq!(section.undated().count(), 0);
+
+    // Something happened to it, but we don't know if that was before or
+    // after it was seen.
+    assert!(section.observe_undated(2, &stone, 1, 500));
+    assert!(section.invalidate(2, 100));
+    assert!(!section.observe_undated(2, &stone, 1, 600));
+    assert!(!section.observe(2, &air, 2, 99));
+    assert!(section.observe(2, &air, 2, 100));
+}